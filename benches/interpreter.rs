@@ -0,0 +1,60 @@
+#![feature(test)]
+
+extern crate test;
+
+use minecrab::asm;
+use minecrab::vm::emulator::Emulator;
+
+use test::Bencher;
+
+// Touches plain and special registers alike, plus some memory.
+const LOOP_SOURCE: &str = "
+:loop
+    add16 r0, ct
+    xor16 r1, rr
+    add16 r2, re
+    load16 r3, [sp+r0*2]
+    store16 [sp+r1*2], r3
+    mov16 re, r0
+    mov16 pc, :loop
+";
+
+// Plain registers only, so most of the time goes to fetching and dispatching.
+const ARITHMETIC_SOURCE: &str = "
+:loop
+    add16 r0, r1
+    add16 r0, r1
+    add16 r0, r1
+    mov16 pc, :loop
+";
+
+const CYCLES_PER_ITER: usize = 100_000;
+
+fn new_emulator(source: &str) -> Emulator {
+    let program = asm::assemble(source).unwrap();
+    Emulator::new(program.memory, program.bytecode, 0)
+}
+
+#[bench]
+fn bench_register_loop(b: &mut Bencher) {
+    let mut emulator = new_emulator(LOOP_SOURCE);
+    b.iter(|| {
+        let mut cycle_count = CYCLES_PER_ITER;
+        emulator.run_until_interrupt(&mut cycle_count)
+    });
+}
+
+#[bench]
+fn bench_arithmetic_loop(b: &mut Bencher) {
+    let mut emulator = new_emulator(ARITHMETIC_SOURCE);
+    b.iter(|| {
+        let mut cycle_count = CYCLES_PER_ITER;
+        emulator.run_until_interrupt(&mut cycle_count)
+    });
+}
+
+#[bench]
+fn bench_emulator_clone(b: &mut Bencher) {
+    let emulator = new_emulator(LOOP_SOURCE);
+    b.iter(|| emulator.clone());
+}
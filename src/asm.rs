@@ -0,0 +1,652 @@
+use crate::vm::emulator::{BYTECODE_SIZE, MEMORY_SIZE};
+use crate::vm::instructions::{Operand, OPCODE_TABLE};
+use crate::vm::register::RegisterName;
+
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+pub struct Program {
+    pub bytecode: Vec<u8>,
+    pub memory: Vec<u8>,
+}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    Assembler::new().assemble(source)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Code,
+    Data,
+}
+
+enum Term {
+    Number(i64),
+    Label(String),
+    Constant(String),
+}
+
+// A sum of terms, each of which may be negated.
+struct Expr {
+    terms: Vec<(bool, Term)>,
+}
+
+struct MemArg {
+    base: RegisterName,
+    index: Option<(RegisterName, u8)>,
+    displacement: Option<Expr>,
+}
+
+enum Arg {
+    Reg(RegisterName),
+    Imm(Expr),
+    Mem(MemArg),
+}
+
+enum Encoded {
+    Reg(RegisterName),
+    RegReg(RegisterName, RegisterName),
+    Imm8(Expr),
+    Imm16(Expr),
+    Mem(MemArg),
+}
+
+impl Encoded {
+    fn len(&self) -> usize {
+        match self {
+            Encoded::Reg(_) | Encoded::RegReg(..) | Encoded::Imm8(_) => 1,
+            Encoded::Imm16(_) => 2,
+            Encoded::Mem(mem) => {
+                1 + mem.index.map_or(0, |_| 1) + mem.displacement.as_ref().map_or(0, |_| 2)
+            }
+        }
+    }
+}
+
+enum Item {
+    Instruction(u8, Vec<Encoded>),
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+    Raw(Vec<u8>),
+}
+
+struct Statement {
+    line: usize,
+    section: Section,
+    address: usize,
+    item: Item,
+}
+
+struct Assembler {
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i64>,
+    section: Section,
+    code_cursor: usize,
+    data_cursor: usize,
+    statements: Vec<Statement>,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Self {
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            section: Section::Code,
+            code_cursor: 0,
+            data_cursor: 0,
+            statements: Vec::new(),
+        }
+    }
+
+    fn assemble(mut self, source: &str) -> Result<Program, AsmError> {
+        for (idx, line) in source.lines().enumerate() {
+            self.parse_line(idx + 1, line)
+                .map_err(|message| AsmError::new(idx + 1, message))?;
+        }
+
+        let mut program = Program {
+            bytecode: vec![0; BYTECODE_SIZE],
+            memory: vec![0; MEMORY_SIZE],
+        };
+        for statement in &self.statements {
+            let bytes = self
+                .encode(&statement.item)
+                .map_err(|message| AsmError::new(statement.line, message))?;
+            let image = match statement.section {
+                Section::Code => &mut program.bytecode,
+                Section::Data => &mut program.memory,
+            };
+            image[statement.address..statement.address + bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok(program)
+    }
+
+    fn parse_line(&mut self, line_number: usize, line: &str) -> Result<(), String> {
+        let mut line = strip_comment(line).trim();
+        if let Some(rest) = line.strip_prefix(':') {
+            let (name, rest) = split_first_word(rest);
+            if !is_identifier(name) {
+                return Err(format!("invalid label name `{}`", name));
+            }
+            let address = self.cursor();
+            if self
+                .labels
+                .insert(name.to_string(), address as u16)
+                .is_some()
+            {
+                return Err(format!("duplicate label `:{}`", name));
+            }
+            line = rest;
+        }
+        if line.is_empty() {
+            return Ok(());
+        }
+
+        let (word, rest) = split_first_word(line);
+        let item = match word.to_ascii_lowercase().as_str() {
+            ".code" => return self.expect_no_args(rest, Section::Code),
+            ".data" => return self.expect_no_args(rest, Section::Data),
+            ".org" => {
+                let address = self.eval_now(&parse_expr(rest)?)?;
+                if !(0..self.section_size() as i64).contains(&address) {
+                    return Err(format!("address {:#x} is out of range", address));
+                }
+                *self.cursor_mut() = address as usize;
+                return Ok(());
+            }
+            ".const" => {
+                let (name, value) = split_first_word(rest);
+                if !is_identifier(name) || name.parse::<RegisterName>().is_ok() {
+                    return Err(format!("invalid constant name `{}`", name));
+                }
+                let value = self.eval_now(&parse_expr(value)?)?;
+                if self.constants.insert(name.to_string(), value).is_some() {
+                    return Err(format!("duplicate constant `{}`", name));
+                }
+                return Ok(());
+            }
+            ".byte" => Item::Bytes(
+                split_args(rest)?
+                    .map(parse_expr)
+                    .collect::<Result<_, _>>()?,
+            ),
+            ".word" => Item::Words(
+                split_args(rest)?
+                    .map(parse_expr)
+                    .collect::<Result<_, _>>()?,
+            ),
+            ".ascii" => Item::Raw(parse_string(rest)?),
+            directive if directive.starts_with('.') => {
+                return Err(format!("unknown directive `{}`", word));
+            }
+            _ => {
+                if self.section != Section::Code {
+                    return Err("instructions are only allowed in the code section".to_string());
+                }
+                self.parse_instruction(word, rest)?
+            }
+        };
+
+        let len = match &item {
+            Item::Instruction(_, operands) => 1 + operands.iter().map(Encoded::len).sum::<usize>(),
+            Item::Bytes(values) => values.len(),
+            Item::Words(values) => values.len() * 2,
+            Item::Raw(bytes) => bytes.len(),
+        };
+        let address = self.cursor();
+        if address + len > self.section_size() {
+            return Err("section overflows past address 0xffff".to_string());
+        }
+        *self.cursor_mut() += len;
+        self.statements.push(Statement {
+            line: line_number,
+            section: self.section,
+            address,
+            item,
+        });
+        Ok(())
+    }
+
+    fn parse_instruction(&self, mnemonic: &str, operands: &str) -> Result<Item, String> {
+        let candidates: Vec<_> = OPCODE_TABLE
+            .get_instructions_by_mnemonic(mnemonic)
+            .collect();
+        if candidates.is_empty() {
+            return Err(format!("unknown instruction `{}`", mnemonic));
+        }
+        let args = split_args(operands)?
+            .map(parse_arg)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for instruction in &candidates {
+            if !matches_operands(instruction.get_operands(), &args) {
+                continue;
+            }
+            let mut args = args.into_iter();
+            let encoded = instruction
+                .get_operands()
+                .iter()
+                .map(|operand| match (operand, args.next()) {
+                    (Operand::Reg, Some(Arg::Reg(reg))) => Encoded::Reg(reg),
+                    (Operand::RegReg, Some(Arg::Reg(reg1))) => match args.next() {
+                        Some(Arg::Reg(reg2)) => Encoded::RegReg(reg1, reg2),
+                        _ => unreachable!(),
+                    },
+                    (Operand::Imm8, Some(Arg::Imm(expr))) => Encoded::Imm8(expr),
+                    (Operand::Imm16, Some(Arg::Imm(expr))) => Encoded::Imm16(expr),
+                    (Operand::Mem, Some(Arg::Mem(mem))) => Encoded::Mem(mem),
+                    _ => unreachable!(),
+                })
+                .collect();
+            return Ok(Item::Instruction(instruction.get_opcode(), encoded));
+        }
+
+        let formats: Vec<_> = candidates
+            .iter()
+            .map(|instruction| format_operands(instruction.get_operands()))
+            .collect();
+        Err(format!(
+            "invalid operands for `{}`, expected one of: {}",
+            mnemonic,
+            formats.join("; ")
+        ))
+    }
+
+    fn encode(&self, item: &Item) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        match item {
+            Item::Instruction(opcode, operands) => {
+                bytes.push(*opcode);
+                for operand in operands {
+                    match operand {
+                        Encoded::Reg(reg) => bytes.push(*reg as u8),
+                        Encoded::RegReg(reg1, reg2) => bytes.push(*reg1 as u8 | (*reg2 as u8) << 4),
+                        Encoded::Imm8(expr) => bytes.push(self.eval_u8(expr)?),
+                        Encoded::Imm16(expr) => bytes.extend(self.eval_u16(expr)?.to_le_bytes()),
+                        Encoded::Mem(mem) => {
+                            let mode = match (&mem.index, &mem.displacement) {
+                                (None, None) => 0b00,
+                                (None, Some(_)) => 0b01,
+                                (Some(_), None) => 0b10,
+                                (Some(_), Some(_)) => 0b11,
+                            };
+                            bytes.push(mode << 6 | mem.base as u8);
+                            if let Some((index, scale)) = mem.index {
+                                bytes.push(index as u8 | scale << 4);
+                            }
+                            if let Some(expr) = &mem.displacement {
+                                bytes.extend(self.eval_u16(expr)?.to_le_bytes());
+                            }
+                        }
+                    }
+                }
+            }
+            Item::Bytes(values) => {
+                for expr in values {
+                    bytes.push(self.eval_u8(expr)?);
+                }
+            }
+            Item::Words(values) => {
+                for expr in values {
+                    bytes.extend(self.eval_u16(expr)?.to_le_bytes());
+                }
+            }
+            Item::Raw(raw) => bytes.extend(raw),
+        }
+        Ok(bytes)
+    }
+
+    fn eval(&self, expr: &Expr, allow_labels: bool) -> Result<i64, String> {
+        let mut value = 0i64;
+        for (negated, term) in &expr.terms {
+            let term_value = match term {
+                Term::Number(number) => *number,
+                Term::Label(name) if allow_labels => *self
+                    .labels
+                    .get(name)
+                    .ok_or_else(|| format!("undefined label `:{}`", name))?
+                    as i64,
+                Term::Label(name) => {
+                    return Err(format!("label `:{}` cannot be used here", name));
+                }
+                Term::Constant(name) => *self
+                    .constants
+                    .get(name)
+                    .ok_or_else(|| format!("undefined constant `{}`", name))?,
+            };
+            value = if *negated {
+                value.checked_sub(term_value)
+            } else {
+                value.checked_add(term_value)
+            }
+            .ok_or("arithmetic overflow")?;
+        }
+        Ok(value)
+    }
+
+    // Used by directives, which have to be resolved before all labels are known.
+    fn eval_now(&self, expr: &Expr) -> Result<i64, String> {
+        self.eval(expr, false)
+    }
+
+    fn eval_u8(&self, expr: &Expr) -> Result<u8, String> {
+        let value = self.eval(expr, true)?;
+        if !(i8::MIN as i64..=u8::MAX as i64).contains(&value) {
+            return Err(format!("value {} does not fit in 8 bits", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn eval_u16(&self, expr: &Expr) -> Result<u16, String> {
+        let value = self.eval(expr, true)?;
+        if !(i16::MIN as i64..=u16::MAX as i64).contains(&value) {
+            return Err(format!("value {} does not fit in 16 bits", value));
+        }
+        Ok(value as u16)
+    }
+
+    fn expect_no_args(&mut self, rest: &str, section: Section) -> Result<(), String> {
+        if !rest.is_empty() {
+            return Err(format!("unexpected `{}`", rest));
+        }
+        self.section = section;
+        Ok(())
+    }
+
+    fn section_size(&self) -> usize {
+        match self.section {
+            Section::Code => BYTECODE_SIZE,
+            Section::Data => MEMORY_SIZE,
+        }
+    }
+
+    fn cursor(&self) -> usize {
+        match self.section {
+            Section::Code => self.code_cursor,
+            Section::Data => self.data_cursor,
+        }
+    }
+
+    fn cursor_mut(&mut self) -> &mut usize {
+        match self.section {
+            Section::Code => &mut self.code_cursor,
+            Section::Data => &mut self.data_cursor,
+        }
+    }
+}
+
+fn matches_operands(operands: &[Operand], args: &[Arg]) -> bool {
+    let mut args = args.iter();
+    operands.iter().all(|operand| match operand {
+        Operand::Reg => matches!(args.next(), Some(Arg::Reg(_))),
+        Operand::RegReg => {
+            matches!(args.next(), Some(Arg::Reg(_))) && matches!(args.next(), Some(Arg::Reg(_)))
+        }
+        Operand::Imm8 | Operand::Imm16 => matches!(args.next(), Some(Arg::Imm(_))),
+        Operand::Mem => matches!(args.next(), Some(Arg::Mem(_))),
+    }) && args.next().is_none()
+}
+
+fn format_operands(operands: &[Operand]) -> String {
+    let formats: Vec<_> = operands.iter().map(Operand::to_string).collect();
+    if formats.is_empty() {
+        "no operands".to_string()
+    } else {
+        formats.join(", ")
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '/' if !in_string && line[idx + 1..].starts_with('/') => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn split_first_word(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+// Splits at every unquoted occurrence of one of `separators`, returning each
+// part along with the separator that preceded it.
+fn split_unquoted<'a>(string: &'a str, separators: &[char]) -> Vec<(Option<char>, &'a str)> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut separator = None;
+    let mut start = 0;
+    for (idx, c) in string.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quote.is_some() => escaped = true,
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            _ if quote == Some(c) => quote = None,
+            _ if quote.is_none() && separators.contains(&c) => {
+                parts.push((separator, &string[start..idx]));
+                separator = Some(c);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push((separator, &string[start..]));
+    parts
+}
+
+fn split_args(args: &str) -> Result<impl Iterator<Item = &str>, String> {
+    let args = args.trim();
+    let parts: Vec<_> = if args.is_empty() {
+        Vec::new()
+    } else {
+        split_unquoted(args, &[','])
+            .into_iter()
+            .map(|(_, part)| part.trim())
+            .collect()
+    };
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err("empty operand".to_string());
+    }
+    Ok(parts.into_iter())
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_arg(arg: &str) -> Result<Arg, String> {
+    if let Ok(reg) = arg.parse() {
+        return Ok(Arg::Reg(reg));
+    }
+    match arg.strip_prefix('[') {
+        Some(inner) => {
+            let inner = inner
+                .strip_suffix(']')
+                .ok_or_else(|| format!("missing `]` in `{}`", arg))?;
+            parse_mem(inner).map(Arg::Mem)
+        }
+        None => parse_expr(arg).map(Arg::Imm),
+    }
+}
+
+fn parse_mem(inner: &str) -> Result<MemArg, String> {
+    let mut terms = split_terms(inner)?.into_iter();
+    let base = match terms.next() {
+        Some((false, base)) => base
+            .parse()
+            .map_err(|_| format!("expected base register, found `{}`", base))?,
+        _ => return Err("expected base register".to_string()),
+    };
+
+    let mut index = None;
+    let mut displacement = Vec::new();
+    for (negated, term) in terms {
+        let (reg, scale) = match term.split_once('*') {
+            Some((reg, scale)) => (reg.trim(), Some(scale.trim())),
+            None => (term, None),
+        };
+        let Ok(reg) = reg.parse::<RegisterName>() else {
+            if scale.is_some() {
+                return Err(format!("expected index register, found `{}`", reg));
+            }
+            displacement.push((negated, parse_term(term)?));
+            continue;
+        };
+        if negated || index.is_some() {
+            return Err(format!("invalid index `{}`", term));
+        }
+        let scale = match scale.map(parse_number).transpose()?.unwrap_or(1) {
+            scale @ (1 | 2 | 4 | 8) => scale as u8,
+            16 => 0xf,
+            scale => return Err(format!("invalid scale {}, must be 1, 2, 4, 8 or 16", scale)),
+        };
+        index = Some((reg, scale));
+    }
+
+    Ok(MemArg {
+        base,
+        index,
+        displacement: (!displacement.is_empty()).then_some(Expr {
+            terms: displacement,
+        }),
+    })
+}
+
+fn parse_expr(expr: &str) -> Result<Expr, String> {
+    let terms = split_terms(expr)?
+        .into_iter()
+        .map(|(negated, term)| Ok((negated, parse_term(term)?)))
+        .collect::<Result<_, String>>()?;
+    Ok(Expr { terms })
+}
+
+fn split_terms(expr: &str) -> Result<Vec<(bool, &str)>, String> {
+    let invalid = || format!("invalid expression `{}`", expr.trim());
+    let mut parts = split_unquoted(expr, &['+', '-']).into_iter().peekable();
+    // A leading sign shows up as an empty first part.
+    if parts.peek().is_some_and(|(_, part)| part.trim().is_empty()) {
+        parts.next();
+        if parts.peek().is_none() {
+            return Err(invalid());
+        }
+    }
+    parts
+        .map(|(separator, part)| {
+            let term = part.trim();
+            if term.is_empty() {
+                return Err(invalid());
+            }
+            Ok((separator == Some('-'), term))
+        })
+        .collect()
+}
+
+fn parse_term(term: &str) -> Result<Term, String> {
+    if let Some(name) = term.strip_prefix(':') {
+        if !is_identifier(name) {
+            return Err(format!("invalid label name `{}`", name));
+        }
+        return Ok(Term::Label(name.to_string()));
+    }
+    if is_identifier(term) {
+        if term.parse::<RegisterName>().is_ok() {
+            return Err(format!("unexpected register `{}`", term));
+        }
+        return Ok(Term::Constant(term.to_string()));
+    }
+    parse_number(term).map(Term::Number)
+}
+
+fn parse_number(number: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid number `{}`", number);
+    if let Some(c) = number.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        let bytes = unescape(c).ok_or_else(invalid)?;
+        return match bytes[..] {
+            [byte] => Ok(byte as i64),
+            _ => Err(invalid()),
+        };
+    }
+    let (digits, radix) = if let Some(hex) = number.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(bin) = number.strip_prefix("0b") {
+        (bin, 2)
+    } else if let Some(oct) = number.strip_prefix("0o") {
+        (oct, 8)
+    } else {
+        (number, 10)
+    };
+    i64::from_str_radix(&digits.replace('_', ""), radix).map_err(|_| invalid())
+}
+
+fn parse_string(string: &str) -> Result<Vec<u8>, String> {
+    string
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .and_then(unescape)
+        .ok_or_else(|| format!("invalid string {}", string))
+}
+
+fn unescape(string: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).bytes());
+            continue;
+        }
+        let byte = match chars.next()? {
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            '0' => b'\0',
+            '\\' => b'\\',
+            '\'' => b'\'',
+            '"' => b'"',
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16).ok()?
+            }
+            _ => return None,
+        };
+        bytes.push(byte);
+    }
+    Some(bytes)
+}
@@ -0,0 +1,36 @@
+use minecrab::asm;
+
+use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+#[derive(Parser)]
+struct Args {
+    source_path: PathBuf,
+    #[arg(short, long)]
+    bytecode_path: PathBuf,
+    #[arg(short, long)]
+    memory_path: PathBuf,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let source = fs::read_to_string(&args.source_path).expect("Failed to read source file");
+    let program = match asm::assemble(&source) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!(
+                "{}:{}: {}",
+                args.source_path.display(),
+                err.line,
+                err.message
+            );
+            exit(1);
+        }
+    };
+
+    fs::write(&args.bytecode_path, program.bytecode).expect("Failed to write bytecode");
+    fs::write(&args.memory_path, program.memory).expect("Failed to write memory");
+}
@@ -1,9 +1,10 @@
 use crate::game::map::Location;
+use crate::game::mapfile::{MapFile, MapFormat};
 use crate::vm::emulator;
 
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -31,7 +32,22 @@ pub struct GameConfiguration {
     pub initd_lifetime: u32,
     pub max_processes: usize,
     pub mapdata_path: PathBuf,
-    pub crypto_spawn: HashMap<String, Vec<(i64, f64)>>,
+    // The width and height of the map. Required for raw maps of other than the
+    // default size, while other formats know their own sizes.
+    #[serde(default)]
+    pub map_size: Option<(usize, usize)>,
+    // Whether the map stops at its borders instead of wrapping around.
+    #[serde(default)]
+    pub bounded_map: bool,
+    pub crypto_spawn: BTreeMap<String, Vec<(i64, f64)>>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub max_ticks: Option<u64>,
+    #[serde(default)]
+    pub last_user_standing: bool,
+    #[serde(default)]
+    pub score_threshold: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -39,7 +55,9 @@ pub struct RawUserConfiguration {
     pub initd_bytecode: PathBuf,
     pub initd_memory: PathBuf,
     pub uid: u16,
-    pub spawn_point: Location,
+    // Taken from the spawn markers of the map file if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn_point: Option<Location>,
 }
 
 pub struct UserConfiguration {
@@ -49,11 +67,16 @@ pub struct UserConfiguration {
     pub spawn_point: Location,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct KernelConfiguration {
     pub max_processes: usize,
     pub initd_lifetime: u32,
     pub default_nice: u16,
-    pub crypto_spawn: HashMap<String, Vec<(i64, f64)>>,
+    pub crypto_spawn: BTreeMap<String, Vec<(i64, f64)>>,
+    pub seed: u64,
+    pub max_ticks: Option<u64>,
+    pub last_user_standing: bool,
+    pub score_threshold: Option<i64>,
 }
 
 impl GameConfiguration {
@@ -71,14 +94,26 @@ impl GameConfiguration {
             initd_lifetime: self.initd_lifetime,
             default_nice: self.default_nice,
             crypto_spawn: self.crypto_spawn.clone(),
+            // An unseeded game draws its own seed, which the kernel reports in
+            // the first event of the replay and keeps in snapshots.
+            seed: self.seed.unwrap_or_else(rand::random),
+            max_ticks: self.max_ticks,
+            last_user_standing: self.last_user_standing,
+            score_threshold: self.score_threshold,
         }
     }
 
-    pub fn read_mapdata(&self) -> Result<Vec<u8>, ConfigIoError> {
+    // The format of the map file is chosen by its extension, see
+    // `MapFormat::from_path`.
+    pub fn read_map_file(&self) -> Result<MapFile, ConfigIoError> {
         let mut file = File::open(&self.mapdata_path)?;
         let mut bytes = vec![];
         file.read_to_end(&mut bytes)?;
-        Ok(bytes)
+        Ok(MapFile::parse(
+            &bytes,
+            MapFormat::from_path(&self.mapdata_path),
+            self.map_size,
+        )?)
     }
 
     fn read_bytes_from_file(&self, path: &Path, length: usize) -> Result<Vec<u8>, ConfigIoError> {
@@ -94,10 +129,27 @@ impl GameConfiguration {
         }
     }
 
-    pub fn get_user_configs(&self) -> Result<Vec<UserConfiguration>, ConfigIoError> {
+    pub fn get_user_configs(
+        &self,
+        map_file: &MapFile,
+    ) -> Result<Vec<UserConfiguration>, ConfigIoError> {
         self.user_configs
             .iter()
             .map(|user_config| {
+                let spawn_point = user_config
+                    .spawn_point
+                    .or_else(|| map_file.spawn_points.get(&user_config.uid).copied())
+                    .ok_or_else(|| ConfigIoError {
+                        message: format!("No spawn point for user {}", user_config.uid),
+                    })?;
+                if !map_file.geometry(false)?.contains(spawn_point) {
+                    return Err(ConfigIoError {
+                        message: format!(
+                            "Spawn point {:?} of user {} is outside of the map",
+                            spawn_point, user_config.uid
+                        ),
+                    });
+                }
                 let initd_memory =
                     self.read_bytes_from_file(&user_config.initd_memory, emulator::MEMORY_SIZE)?;
                 let initd_bytecode = self
@@ -106,7 +158,7 @@ impl GameConfiguration {
                     initd_memory,
                     initd_bytecode,
                     uid: user_config.uid,
-                    spawn_point: user_config.spawn_point,
+                    spawn_point,
                 })
             })
             .collect::<Result<_, _>>()
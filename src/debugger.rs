@@ -0,0 +1,336 @@
+use crate::disasm;
+use crate::kernel::syscall::SYSCALL_TABLE;
+use crate::vm::emulator::{CpuFlag, Emulator, ExecutionObserver, NasalDemons, StepResult};
+use crate::vm::instructions::OpcodeTable;
+use crate::vm::register::RegisterName;
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+step [n]           execute n instructions (default 1)
+next               like step, but runs calls until they return
+continue           run until a breakpoint is hit
+break <addr>       stop before executing the instruction at addr
+delete <addr>      remove a breakpoint
+bs <syscall>       stop before the syscall with this number or name
+ds <syscall>       remove a syscall breakpoint
+info               list breakpoints
+regs               show registers, flags and cycles left
+x <addr> [len]     dump memory
+xc <addr> [len]    dump code
+dis [addr] [n]     disassemble n instructions (default: 8 from PC)
+quit               detach from the process";
+
+enum Resume {
+    Step(usize),
+    Continue,
+    Detach,
+}
+
+pub struct Debugger {
+    pid: u16,
+    attached: bool,
+    breakpoints: BTreeSet<u16>,
+    syscall_breakpoints: BTreeSet<u8>,
+    // Set by `next` to stop once the call returns.
+    return_address: Option<u16>,
+    steps_left: Option<usize>,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+impl Debugger {
+    // Starts attached and paused before the next instruction of `pid`.
+    pub fn new(pid: u16) -> Self {
+        Self::with_io(pid, Box::new(io::stdin().lock()), Box::new(io::stdout()))
+    }
+
+    // Reads commands from `input` instead of stdin and prints to `output`.
+    pub fn with_io(pid: u16, input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Debugger {
+            pid,
+            attached: true,
+            breakpoints: BTreeSet::new(),
+            syscall_breakpoints: BTreeSet::new(),
+            return_address: None,
+            steps_left: Some(1),
+            input,
+            output,
+        }
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.attached
+    }
+
+    pub fn get_pid(&self) -> u16 {
+        self.pid
+    }
+
+    fn get_stop_reason(&mut self, emulator: &Emulator) -> Option<String> {
+        let pc = emulator.peek_reg(RegisterName::PC);
+        if let Some(steps) = self.steps_left.as_mut() {
+            *steps -= 1;
+            if *steps == 0 {
+                self.steps_left = None;
+                return Some("step".to_string());
+            }
+        }
+        if self.return_address == Some(pc) {
+            self.return_address = None;
+            return Some("next".to_string());
+        }
+        if self.breakpoints.contains(&pc) {
+            return Some(format!("breakpoint at {:#06x}", pc));
+        }
+        if emulator.peek_from_pc::<u8, 1>() == OpcodeTable::get_syscall_opcode() {
+            let number = emulator.peek_reg(RegisterName::AX) as u8;
+            if self.syscall_breakpoints.contains(&number) {
+                let name = SYSCALL_TABLE.get_syscall(number).get_name();
+                return Some(format!("syscall {:#04x} ({})", number, name));
+            }
+        }
+        None
+    }
+
+    fn print_line(&mut self, line: impl Display) {
+        writeln!(self.output, "{}", line).expect("Failed to write debugger output");
+    }
+
+    fn prompt(&mut self, emulator: &Emulator, cycle_count: usize) -> Resume {
+        let mut line = String::new();
+        loop {
+            write!(self.output, "(mdb) ").expect("Failed to write debugger output");
+            self.output
+                .flush()
+                .expect("Failed to write debugger output");
+            line.clear();
+            if self.input.read_line(&mut line).unwrap_or(0) == 0 {
+                self.print_line("");
+                return Resume::Detach;
+            }
+            let words: Vec<_> = line.split_whitespace().collect();
+            let Some((&command, args)) = words.split_first() else {
+                continue;
+            };
+            match self.run_command(command, args, emulator, cycle_count) {
+                Ok(Some(resume)) => return resume,
+                Ok(None) => {}
+                Err(message) => self.print_line(message),
+            }
+        }
+    }
+
+    fn run_command(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        emulator: &Emulator,
+        cycle_count: usize,
+    ) -> Result<Option<Resume>, String> {
+        let arg = |idx: usize| -> Result<Option<u16>, String> {
+            args.get(idx)
+                .map(|arg| parse_value(arg, emulator))
+                .transpose()
+        };
+        match command {
+            "s" | "step" => {
+                let count = arg(0)?.unwrap_or(1).max(1);
+                return Ok(Some(Resume::Step(count as usize)));
+            }
+            "n" | "next" => {
+                let pc = emulator.peek_reg(RegisterName::PC);
+                let instruction = disasm::decode_instruction(emulator.get_bytecode(), pc);
+                let mnemonic = instruction.instruction.map(|inner| inner.get_mnemonic());
+                if mnemonic == Some("call") {
+                    self.return_address = Some(pc.wrapping_add(instruction.bytes.len() as u16));
+                    return Ok(Some(Resume::Continue));
+                }
+                return Ok(Some(Resume::Step(1)));
+            }
+            "c" | "continue" => return Ok(Some(Resume::Continue)),
+            "b" | "break" => {
+                let addr = arg(0)?.ok_or("Usage: break <addr>")?;
+                self.breakpoints.insert(addr);
+            }
+            "d" | "delete" => {
+                let addr = arg(0)?.ok_or("Usage: delete <addr>")?;
+                if !self.breakpoints.remove(&addr) {
+                    return Err(format!("No breakpoint at {:#06x}", addr));
+                }
+            }
+            "bs" | "ds" => {
+                let name = args.first().ok_or("Usage: bs|ds <syscall>")?;
+                let number = parse_syscall(name)?;
+                if command == "bs" {
+                    self.syscall_breakpoints.insert(number);
+                } else if !self.syscall_breakpoints.remove(&number) {
+                    return Err(format!("No breakpoint on syscall {:#04x}", number));
+                }
+            }
+            "i" | "info" => {
+                let breakpoints = self
+                    .breakpoints
+                    .iter()
+                    .map(|addr| format!("break {:#06x}", addr));
+                let syscall_breakpoints = self.syscall_breakpoints.iter().map(|&number| {
+                    let name = SYSCALL_TABLE.get_syscall(number).get_name();
+                    format!("bs {:#04x} ({})", number, name)
+                });
+                let lines: Vec<_> = breakpoints.chain(syscall_breakpoints).collect();
+                for line in lines {
+                    self.print_line(line);
+                }
+            }
+            "r" | "regs" => self.print_line(format_registers(emulator, cycle_count)),
+            "x" | "xc" => {
+                let addr = arg(0)?.ok_or("Usage: x|xc <addr> [len]")?;
+                let len = arg(1)?.unwrap_or(64) as usize;
+                let bytes = if command == "x" {
+                    emulator.peek_bytes_from_mem(addr, len)
+                } else {
+                    let bytecode = emulator.get_bytecode();
+                    (0..len)
+                        .map(|offset| bytecode[(addr as usize + offset) % bytecode.len()])
+                        .collect()
+                };
+                write!(self.output, "{}", hexdump(addr, &bytes))
+                    .expect("Failed to write debugger output");
+            }
+            "dis" => {
+                let mut addr = arg(0)?.unwrap_or(emulator.peek_reg(RegisterName::PC));
+                let count = arg(1)?.unwrap_or(8);
+                for _ in 0..count {
+                    let instruction = disasm::decode_instruction(emulator.get_bytecode(), addr);
+                    self.print_line(format_line(&instruction, emulator));
+                    addr = addr.wrapping_add(instruction.bytes.len() as u16);
+                }
+            }
+            "q" | "quit" => return Ok(Some(Resume::Detach)),
+            "h" | "help" => self.print_line(HELP),
+            _ => return Err(format!("Unknown command `{}`, try `help`", command)),
+        }
+        Ok(None)
+    }
+}
+
+impl ExecutionObserver for Debugger {
+    fn observes(&self, pid: u16) -> bool {
+        self.attached && pid == self.pid
+    }
+
+    fn before_instruction(&mut self, pid: u16, emulator: &mut Emulator, cycle_count: usize) {
+        let Some(reason) = self.get_stop_reason(emulator) else {
+            return;
+        };
+        let pc = emulator.peek_reg(RegisterName::PC);
+        let instruction = disasm::decode_instruction(emulator.get_bytecode(), pc);
+        self.print_line(format!("pid {} stopped: {}", pid, reason));
+        self.print_line(format_line(&instruction, emulator));
+        match self.prompt(emulator, cycle_count) {
+            Resume::Step(count) => self.steps_left = Some(count),
+            Resume::Continue => self.steps_left = None,
+            Resume::Detach => self.attached = false,
+        }
+    }
+
+    fn after_instruction(
+        &mut self,
+        pid: u16,
+        _emulator: &mut Emulator,
+        _cycle_count: usize,
+        result: &StepResult,
+        nasal_demons: &[NasalDemons],
+    ) {
+        for effect in nasal_demons {
+            self.print_line(format!("pid {} nasal demons: {:?}", pid, effect));
+        }
+        if let (StepResult::Syscall(syscall), Some(_)) = (result, self.steps_left) {
+            self.print_line(format!("pid {} calls {}", pid, syscall.get_name()));
+        }
+    }
+}
+
+// Accepts decimal or 0x-prefixed numbers, or a register name for its value.
+fn parse_value(text: &str, emulator: &Emulator) -> Result<u16, String> {
+    if let Ok(register) = text.parse::<RegisterName>() {
+        return Ok(emulator.peek_reg(register));
+    }
+    match text.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|_| format!("Invalid value `{}`", text))
+}
+
+fn parse_syscall(text: &str) -> Result<u8, String> {
+    let number = match text.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    };
+    number
+        .or_else(|| {
+            SYSCALL_TABLE
+                .get_syscall_by_name(text)
+                .map(|syscall| syscall.get_number())
+        })
+        .ok_or_else(|| format!("Unknown syscall `{}`", text))
+}
+
+fn format_line(instruction: &disasm::DecodedInstruction, emulator: &Emulator) -> String {
+    let marker = if instruction.address == emulator.peek_reg(RegisterName::PC) {
+        "=>"
+    } else {
+        "  "
+    };
+    format!("{} {:04x}: {}", marker, instruction.address, instruction)
+}
+
+fn format_registers(emulator: &Emulator, cycle_count: usize) -> String {
+    let mut output = String::new();
+    for row in (0..16u8).collect::<Vec<_>>().chunks(4) {
+        let cells: Vec<_> = row
+            .iter()
+            .map(|&idx| {
+                let register = RegisterName::from(idx);
+                format!(
+                    "{:<2} {:#06x}",
+                    register.name(),
+                    emulator.peek_reg(register)
+                )
+            })
+            .collect();
+        output += &format!("{}\n", cells.join("   "));
+    }
+    let flags: Vec<_> = CpuFlag::ALL
+        .into_iter()
+        .map(|flag| format!("{}={}", flag.name(), emulator.get_cpu_flag(flag) as u8))
+        .collect();
+    output + &format!("{}   cycles left {}", flags.join(" "), cycle_count)
+}
+
+fn hexdump(addr: u16, bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<_> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        output += &format!(
+            "{:04x}: {:<48}{}\n",
+            addr.wrapping_add(row as u16 * 16),
+            hex.join(" "),
+            ascii
+        );
+    }
+    output
+}
@@ -0,0 +1,238 @@
+use crate::vm::instructions::{Instruction, Operand, OPCODE_TABLE};
+use crate::vm::paged::PagedBytes;
+use crate::vm::register::RegisterName;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Write};
+
+pub enum DecodedOperand {
+    Reg(RegisterName),
+    RegReg(RegisterName, RegisterName),
+    Imm8(u8),
+    Imm16(u16),
+    Mem {
+        base: RegisterName,
+        index: Option<(RegisterName, u16)>,
+        displacement: Option<u16>,
+    },
+}
+
+pub struct DecodedInstruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    // `None` if the opcode is not in the opcode table.
+    pub instruction: Option<&'static dyn Instruction>,
+    pub operands: Vec<DecodedOperand>,
+}
+
+impl DecodedInstruction {
+    // The absolute address this instruction calls or jumps to, if it is known
+    // without running the code.
+    pub fn get_branch_target(&self) -> Option<u16> {
+        let mnemonic = self.instruction?.get_mnemonic();
+        match self.operands[..] {
+            [DecodedOperand::Imm16(target)] if mnemonic == "call" => Some(target),
+            [DecodedOperand::Reg(RegisterName::PC), DecodedOperand::Imm16(target)]
+                if mnemonic == "mov16" || mnemonic.starts_with("cmov") =>
+            {
+                Some(target)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_instruction(self, &BTreeMap::new()))
+    }
+}
+
+struct Decoder<'a> {
+    bytecode: &'a PagedBytes,
+    address: usize,
+    bytes: Vec<u8>,
+}
+
+impl Decoder<'_> {
+    fn next_u8(&mut self) -> u8 {
+        let byte = self.bytecode[self.address];
+        self.address = (self.address + 1) % self.bytecode.len();
+        self.bytes.push(byte);
+        byte
+    }
+
+    fn next_u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.next_u8(), self.next_u8()])
+    }
+
+    fn next_operand(&mut self, operand: Operand) -> DecodedOperand {
+        match operand {
+            Operand::Reg => DecodedOperand::Reg(self.next_u8().into()),
+            Operand::RegReg => {
+                let indices = self.next_u8();
+                DecodedOperand::RegReg(indices.into(), (indices >> 4).into())
+            }
+            Operand::Imm8 => DecodedOperand::Imm8(self.next_u8()),
+            Operand::Imm16 => DecodedOperand::Imm16(self.next_u16()),
+            Operand::Mem => {
+                let mode_base = self.next_u8();
+                let mode = mode_base >> 6;
+                let index = (mode & 0b10 != 0).then(|| {
+                    let scale_index = self.next_u8();
+                    let scale = (scale_index >> 4).next_power_of_two() as u16;
+                    (scale_index.into(), scale)
+                });
+                let displacement = (mode & 0b01 != 0).then(|| self.next_u16());
+                DecodedOperand::Mem {
+                    base: mode_base.into(),
+                    index,
+                    displacement,
+                }
+            }
+        }
+    }
+}
+
+// Decodes the instruction at `address`, wrapping around at the end of the
+// bytecode like the emulator does.
+pub fn decode_instruction(bytecode: &PagedBytes, address: u16) -> DecodedInstruction {
+    let mut decoder = Decoder {
+        bytecode,
+        address: address as usize % bytecode.len(),
+        bytes: Vec::new(),
+    };
+    let opcode = decoder.next_u8();
+    let instruction = OPCODE_TABLE.get_instruction(opcode);
+    if instruction.get_opcode() != opcode {
+        return DecodedInstruction {
+            address,
+            bytes: decoder.bytes,
+            instruction: None,
+            operands: Vec::new(),
+        };
+    }
+    let operands = instruction
+        .get_operands()
+        .iter()
+        .map(|&operand| decoder.next_operand(operand))
+        .collect();
+    DecodedInstruction {
+        address,
+        bytes: decoder.bytes,
+        instruction: Some(instruction),
+        operands,
+    }
+}
+
+// Decodes the instructions starting from `start`, one after another, until
+// reaching `end`.
+pub fn decode_range(bytecode: &PagedBytes, start: u16, end: usize) -> Vec<DecodedInstruction> {
+    let mut instructions = Vec::new();
+    let mut address = start as usize;
+    while address < end {
+        let instruction = decode_instruction(bytecode, address as u16);
+        address += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+// Produces source text that `asm::assemble` turns back into the same bytes,
+// except for bits the emulator ignores, like the unused half of a single
+// register operand or a scale that is not a power of 2.
+pub fn disassemble(bytecode: &PagedBytes, start: u16, end: usize) -> String {
+    let instructions = decode_range(bytecode, start, end);
+    let starts: HashSet<_> = instructions.iter().map(|instr| instr.address).collect();
+    let mut labels = BTreeMap::new();
+    for instruction in &instructions {
+        let Some(target) = instruction.get_branch_target() else {
+            continue;
+        };
+        if starts.contains(&target) {
+            let prefix = if instruction.operands.len() == 1 {
+                "sub"
+            } else {
+                "loc"
+            };
+            labels
+                .entry(target)
+                .or_insert_with(|| format!("{}_{:04x}", prefix, target));
+        }
+    }
+
+    let mut output = String::new();
+    for instruction in &instructions {
+        if let Some(label) = labels.get(&instruction.address) {
+            writeln!(output, ":{}", label).unwrap();
+        }
+        let text = format_instruction(instruction, &labels);
+        let bytes: Vec<_> = instruction
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        writeln!(
+            output,
+            "    {:<40} // {:04x}: {}",
+            text,
+            instruction.address,
+            bytes.join(" ")
+        )
+        .unwrap();
+    }
+    output
+}
+
+fn format_instruction(instruction: &DecodedInstruction, labels: &BTreeMap<u16, String>) -> String {
+    let Some(inner) = instruction.instruction else {
+        return format!(".byte {:#04x}", instruction.bytes[0]);
+    };
+    let target = instruction.get_branch_target();
+    let operands: Vec<_> = instruction
+        .operands
+        .iter()
+        .map(|operand| match operand {
+            DecodedOperand::Imm16(value) if Some(*value) == target => match labels.get(value) {
+                Some(label) => format!(":{}", label),
+                None => format!("{:#06x}", value),
+            },
+            operand => format_operand(operand),
+        })
+        .collect();
+    if operands.is_empty() {
+        inner.get_mnemonic().to_string()
+    } else {
+        format!("{} {}", inner.get_mnemonic(), operands.join(", "))
+    }
+}
+
+fn format_register(reg: RegisterName) -> String {
+    reg.name().to_ascii_lowercase()
+}
+
+pub fn format_operand(operand: &DecodedOperand) -> String {
+    match operand {
+        DecodedOperand::Reg(reg) => format_register(*reg),
+        DecodedOperand::RegReg(reg1, reg2) => {
+            format!("{}, {}", format_register(*reg1), format_register(*reg2))
+        }
+        DecodedOperand::Imm8(value) => format!("{:#04x}", value),
+        DecodedOperand::Imm16(value) => format!("{:#06x}", value),
+        DecodedOperand::Mem {
+            base,
+            index,
+            displacement,
+        } => {
+            let mut text = format!("[{}", format_register(*base));
+            if let Some((index, scale)) = index {
+                write!(text, "+{}*{}", format_register(*index), scale).unwrap();
+            }
+            if let Some(displacement) = displacement {
+                write!(text, "+{:#06x}", displacement).unwrap();
+            }
+            text.push(']');
+            text
+        }
+    }
+}
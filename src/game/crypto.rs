@@ -1,10 +1,14 @@
+use crate::game::rng::GameRng;
+
 use murmurhash3::murmurhash3_x86_32;
-use rand::random;
-use serde::Serialize;
+use rand::Rng;
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha2::{Digest, Sha256};
 
 pub const CRYPTO_TYPES: usize = std::mem::variant_count::<CryptoCurrency>();
 
+#[derive(Clone, Copy)]
 pub enum CryptoCurrency {
     DogeCoin,
     StarSleepShortage,
@@ -14,7 +18,29 @@ pub enum CryptoCurrency {
     Ｅｘｐｌｏｓｉｏｎ,
 }
 
-#[derive(Default, Serialize)]
+impl CryptoCurrency {
+    pub const ALL: [CryptoCurrency; CRYPTO_TYPES] = [
+        CryptoCurrency::DogeCoin,
+        CryptoCurrency::StarSleepShortage,
+        CryptoCurrency::Ethereum,
+        CryptoCurrency::BitCoin,
+        CryptoCurrency::CrabCoin,
+        CryptoCurrency::Ｅｘｐｌｏｓｉｏｎ,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CryptoCurrency::DogeCoin => "DogeCoin",
+            CryptoCurrency::StarSleepShortage => "StarSleepShortage",
+            CryptoCurrency::Ethereum => "Ethereum",
+            CryptoCurrency::BitCoin => "BitCoin",
+            CryptoCurrency::CrabCoin => "CrabCoin",
+            CryptoCurrency::Ｅｘｐｌｏｓｉｏｎ => "Ｅｘｐｌｏｓｉｏｎ",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Wallet {
     assets: [i64; CRYPTO_TYPES],
 }
@@ -30,6 +56,13 @@ impl Wallet {
         self
     }
 
+    pub fn iter_nonzero(&self) -> impl Iterator<Item = (&'static str, i64)> + '_ {
+        CryptoCurrency::ALL
+            .into_iter()
+            .map(|currency| (currency.name(), self.get_currency(currency)))
+            .filter(|&(_, amount)| amount != 0)
+    }
+
     pub fn can_afford(&self, cost: &Wallet) -> bool {
         self.assets
             .iter()
@@ -76,18 +109,33 @@ macro_rules! wallet {
 }
 pub(crate) use wallet;
 
-pub trait CryptoChallenge {
+pub trait CryptoChallenge: ChallengeState {
     fn get_numeric_id(&self) -> u16;
     fn get_name(&self) -> &'static str;
-    fn generate(&mut self, difficulty: i64);
+    fn generate(&mut self, difficulty: i64, rng: &mut GameRng);
     fn get_reward(&self) -> Wallet;
     fn get_difficulty(&self) -> u16;
     fn get_challenge_data(&self) -> Vec<u8>;
     fn verify(&self, nonce: (u16, u16, u16, u16)) -> bool;
 }
 
-pub fn generate_crypto_challenge(name: &str, difficulty: i64) -> Option<Box<dyn CryptoChallenge>> {
-    let mut challenge: Box<dyn CryptoChallenge> = match name {
+pub trait ChallengeState {
+    fn save_state(&self) -> serde_json::Value;
+    fn load_state(&mut self, state: serde_json::Value) -> serde_json::Result<()>;
+}
+
+impl<T: Serialize + DeserializeOwned> ChallengeState for T {
+    fn save_state(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+    fn load_state(&mut self, state: serde_json::Value) -> serde_json::Result<()> {
+        *self = serde_json::from_value(state)?;
+        Ok(())
+    }
+}
+
+fn new_crypto_challenge(name: &str) -> Option<Box<dyn CryptoChallenge>> {
+    let challenge: Box<dyn CryptoChallenge> = match name {
         "bed" => Box::new(BedChallenge::default()),
         "dog" => Box::new(DogChallenge::default()),
         "ether" => Box::new(EtherChallenge::default()),
@@ -95,11 +143,52 @@ pub fn generate_crypto_challenge(name: &str, difficulty: i64) -> Option<Box<dyn
         "crab" => Box::new(CrabChallenge::default()),
         _ => return None,
     };
-    challenge.generate(difficulty);
     Some(challenge)
 }
 
-#[derive(Default)]
+pub fn generate_crypto_challenge(
+    name: &str,
+    difficulty: i64,
+    rng: &mut GameRng,
+) -> Option<Box<dyn CryptoChallenge>> {
+    let mut challenge = new_crypto_challenge(name)?;
+    challenge.generate(difficulty, rng);
+    Some(challenge)
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedChallenge {
+    name: String,
+    state: serde_json::Value,
+}
+
+// The box itself is also `ChallengeState` through the blanket impl, so the
+// state methods must be called on the challenge inside it.
+impl Serialize for Box<dyn CryptoChallenge> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let challenge = self.as_ref();
+        SavedChallenge {
+            name: challenge.get_name().to_string(),
+            state: challenge.save_state(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn CryptoChallenge> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = SavedChallenge::deserialize(deserializer)?;
+        let mut challenge = new_crypto_challenge(&saved.name)
+            .ok_or_else(|| D::Error::custom(format!("unknown challenge {:?}", saved.name)))?;
+        challenge
+            .as_mut()
+            .load_state(saved.state)
+            .map_err(D::Error::custom)?;
+        Ok(challenge)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
 pub struct BedChallenge {
     difficulty: i64,
 }
@@ -111,7 +200,7 @@ impl CryptoChallenge for BedChallenge {
     fn get_name(&self) -> &'static str {
         "bed"
     }
-    fn generate(&mut self, difficulty: i64) {
+    fn generate(&mut self, difficulty: i64, _rng: &mut GameRng) {
         self.difficulty = difficulty;
     }
     fn get_reward(&self) -> Wallet {
@@ -128,7 +217,7 @@ impl CryptoChallenge for BedChallenge {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct DogChallenge {
     difficulty: i64,
     challenge: (u16, u16, u16, u16),
@@ -141,9 +230,9 @@ impl CryptoChallenge for DogChallenge {
     fn get_name(&self) -> &'static str {
         "dog"
     }
-    fn generate(&mut self, difficulty: i64) {
+    fn generate(&mut self, difficulty: i64, rng: &mut GameRng) {
         self.difficulty = difficulty;
-        self.challenge = random();
+        self.challenge = rng.gen();
     }
     fn get_reward(&self) -> Wallet {
         wallet!(
@@ -168,7 +257,7 @@ impl CryptoChallenge for DogChallenge {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct EtherChallenge {
     difficulty: i64,
     challenge: u16,
@@ -181,9 +270,9 @@ impl CryptoChallenge for EtherChallenge {
     fn get_name(&self) -> &'static str {
         "ether"
     }
-    fn generate(&mut self, difficulty: i64) {
+    fn generate(&mut self, difficulty: i64, rng: &mut GameRng) {
         self.difficulty = difficulty;
-        self.challenge = random::<u16>() | 1;
+        self.challenge = rng.gen::<u16>() | 1;
     }
     fn get_reward(&self) -> Wallet {
         wallet!(
@@ -202,7 +291,7 @@ impl CryptoChallenge for EtherChallenge {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct BabyBtcChallenge {
     challenge: [u8; 32],
 }
@@ -214,8 +303,8 @@ impl CryptoChallenge for BabyBtcChallenge {
     fn get_name(&self) -> &'static str {
         "btc"
     }
-    fn generate(&mut self, _difficulty: i64) {
-        self.challenge = random();
+    fn generate(&mut self, _difficulty: i64, rng: &mut GameRng) {
+        self.challenge = rng.gen();
     }
     fn get_reward(&self) -> Wallet {
         wallet!(
@@ -240,7 +329,7 @@ impl CryptoChallenge for BabyBtcChallenge {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct CrabChallenge {
     seed: u32,
     hash: u32,
@@ -254,10 +343,10 @@ impl CryptoChallenge for CrabChallenge {
     fn get_name(&self) -> &'static str {
         "crab"
     }
-    fn generate(&mut self, _difficulty: i64) {
-        self.challenge = random();
-        self.seed = random();
-        self.hash = random();
+    fn generate(&mut self, _difficulty: i64, rng: &mut GameRng) {
+        self.challenge = rng.gen();
+        self.seed = rng.gen();
+        self.hash = rng.gen();
     }
     fn get_reward(&self) -> Wallet {
         wallet!(
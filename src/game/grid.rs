@@ -0,0 +1,130 @@
+use crate::game::map::{Location, MapGeometry};
+
+// The width and height of the square of cells covered by a bucket.
+const BUCKET_SIZE: usize = 16;
+
+// A set of locations on a map, bucketed by location so that the nearest ones
+// to a point can be found without looking at the others. Buckets are indexed
+// in x-major order, like cells.
+pub struct LocationGrid {
+    geometry: MapGeometry,
+    columns: usize,
+    rows: usize,
+    buckets: Vec<Vec<Location>>,
+}
+
+impl Default for LocationGrid {
+    fn default() -> Self {
+        Self::new(MapGeometry::default())
+    }
+}
+
+impl LocationGrid {
+    pub fn new(geometry: MapGeometry) -> Self {
+        let columns = geometry.width.div_ceil(BUCKET_SIZE);
+        let rows = geometry.height.div_ceil(BUCKET_SIZE);
+        LocationGrid {
+            geometry,
+            columns,
+            rows,
+            buckets: vec![vec![]; columns * rows],
+        }
+    }
+
+    fn bucket_of(&self, (x, y): Location) -> usize {
+        x as usize / BUCKET_SIZE * self.rows + y as usize / BUCKET_SIZE
+    }
+
+    // The bucket `dx` columns and `dy` rows away from the one at `(bx, by)`,
+    // which is wrapped around the map, or `None` if it lies outside of a
+    // bounded map.
+    fn offset_bucket(&self, (bx, by): (usize, usize), dx: isize, dy: isize) -> Option<usize> {
+        let (columns, rows) = (self.columns as isize, self.rows as isize);
+        let (x, y) = (bx as isize + dx, by as isize + dy);
+        if !self.geometry.bounded {
+            Some((x.rem_euclid(columns) * rows + y.rem_euclid(rows)) as usize)
+        } else if (0..columns).contains(&x) && (0..rows).contains(&y) {
+            Some((x * rows + y) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, location: Location) {
+        let bucket = self.bucket_of(location);
+        self.buckets[bucket].push(location);
+    }
+
+    // Returns whether `location` was in the grid.
+    pub fn remove(&mut self, location: Location) -> bool {
+        let bucket = self.bucket_of(location);
+        let bucket = &mut self.buckets[bucket];
+        let Some(idx) = bucket.iter().position(|&other| other == location) else {
+            return false;
+        };
+        bucket.swap_remove(idx);
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.iter().all(Vec::is_empty)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Location> + '_ {
+        self.buckets.iter().flatten().copied()
+    }
+
+    // The nearest location within `radius` of `center` for which `filter`
+    // holds, going by `MapGeometry::distance` and then by location.
+    //
+    // Buckets are searched ring by ring around the bucket of `center`. The
+    // buckets between `center` and a bucket `k` rings away span at least
+    // `k - 1` buckets, of which only the last one of a wrapping map can be
+    // narrower than `BUCKET_SIZE`, so no cell that far is within
+    // `(k - 2) * BUCKET_SIZE` of `center`. The search stops at the first ring
+    // beyond the radius or the nearest location found so far, or once it has
+    // covered the whole map.
+    pub fn find_nearest<F: Fn(Location) -> bool>(
+        &self,
+        center: Location,
+        radius: u16,
+        filter: F,
+    ) -> Option<Location> {
+        let center_bucket = (
+            center.0 as usize / BUCKET_SIZE,
+            center.1 as usize / BUCKET_SIZE,
+        );
+        let last_ring = if self.geometry.bounded {
+            self.columns.max(self.rows) - 1
+        } else {
+            self.columns.max(self.rows) / 2
+        };
+        let mut nearest: Option<(u8, Location)> = None;
+        for ring in 0..=last_ring {
+            let limit = nearest.map_or(radius, |(distance, _)| radius.min(distance as u16));
+            if ring >= 2 && ((ring - 2) * BUCKET_SIZE) as u16 >= limit {
+                break;
+            }
+            let ring = ring as isize;
+            for dy in -ring..=ring {
+                // Only the top and bottom rows of a ring are full.
+                let step = if dy.abs() == ring { 1 } else { 2 * ring };
+                for dx in (-ring..=ring).step_by(step as usize) {
+                    let Some(bucket) = self.offset_bucket(center_bucket, dx, dy) else {
+                        continue;
+                    };
+                    for &location in &self.buckets[bucket] {
+                        let distance = self.geometry.distance(center, location);
+                        if distance as u16 <= radius
+                            && nearest.is_none_or(|best| (distance, location) < best)
+                            && filter(location)
+                        {
+                            nearest = Some((distance, location));
+                        }
+                    }
+                }
+            }
+        }
+        nearest.map(|(_, location)| location)
+    }
+}
@@ -1,29 +1,210 @@
 use crate::game::crypto::*;
-use crate::game::replay::{log_event, GameEvent};
+use crate::game::grid::LocationGrid;
+use crate::game::replay::{EventLogger, GameEvent};
+use crate::game::rng::{derive_rng, GameRng};
 
-use rand::{thread_rng, Rng};
-use std::collections::{HashMap, VecDeque};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt;
 
+// The default size of a map, which is also the largest since coordinates are
+// bytes.
 pub const MAP_WIDTH: usize = 256;
 pub const MAP_HEIGHT: usize = 256;
 
-#[derive(PartialEq)]
+// The codes of the cell types, in map data and in `MapCell::status`. Codes 2
+// and 3 are taken by the processes and challenges of `MapCell::status_detail`.
+pub const LAND: u8 = 0;
+pub const WALL: u8 = 1;
+pub const WATER: u8 = 4;
+pub const PORTAL: u8 = 5;
+pub const DESTRUCTIBLE_WALL: u8 = 6;
+
+// Map data tells portal pairs apart: the two portals of pair `n` are
+// `PORTAL_PAIR_BASE + n` there, instead of `PORTAL`.
+pub const PORTAL_PAIR_BASE: u8 = 0x80;
+pub const MAX_PORTAL_PAIRS: u8 = 52;
+
+// Entering water costs this much DogeCoin on top of the move itself.
+pub const WATER_EXTRA_COST: u32 = 2;
+
+// The number of `Attack1` hits that break down a destructible wall.
+pub const DESTRUCTIBLE_WALL_HP: u8 = 8;
+
+#[derive(PartialEq, Serialize, Deserialize)]
 pub enum CellType {
     Land,
     Wall,
+    Water,
+    // Leads to the other portal of its pair.
+    Portal { pair: u8, destination: Location },
+    DestructibleWall { hp: u8 },
 }
 
 pub type Location = (u8, u8);
 
-pub fn chebyshev_distance((x1, y1): Location, (x2, y2): Location) -> u8 {
-    let dx = std::cmp::min(x1.wrapping_sub(x2), x2.wrapping_sub(x1));
-    let dy = std::cmp::min(y1.wrapping_sub(y2), y2.wrapping_sub(y1));
-    std::cmp::max(dx, dy)
+// The size of a map and what happens at its borders. A map wraps around like a
+// torus unless it is bounded, in which case nothing lies beyond its borders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapGeometry {
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub bounded: bool,
+}
+
+impl Default for MapGeometry {
+    fn default() -> Self {
+        Self {
+            width: MAP_WIDTH,
+            height: MAP_HEIGHT,
+            bounded: false,
+        }
+    }
+}
+
+impl MapGeometry {
+    pub fn new(width: usize, height: usize, bounded: bool) -> Result<Self, GameMapError> {
+        if !(1..=MAP_WIDTH).contains(&width) || !(1..=MAP_HEIGHT).contains(&height) {
+            return Err(GameMapError {
+                message: format!(
+                    "Map size must be between 1x1 and {}x{}, not {}x{}",
+                    MAP_WIDTH, MAP_HEIGHT, width, height
+                ),
+            });
+        }
+        Ok(MapGeometry {
+            width,
+            height,
+            bounded,
+        })
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn contains(&self, (x, y): Location) -> bool {
+        (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    // The location at `(x, y)`, which is wrapped around the map, or `None` if it
+    // lies outside of a bounded map.
+    pub fn locate(&self, x: i32, y: i32) -> Option<Location> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        if !self.bounded {
+            Some((x.rem_euclid(width) as u8, y.rem_euclid(height) as u8))
+        } else if (0..width).contains(&x) && (0..height).contains(&y) {
+            Some((x as u8, y as u8))
+        } else {
+            None
+        }
+    }
+
+    pub fn offset(&self, (x, y): Location, dx: i32, dy: i32) -> Option<Location> {
+        self.locate(x as i32 + dx, y as i32 + dy)
+    }
+
+    // The Chebyshev distance between two locations on the map, i.e. the number
+    // of moves between them if nothing is in the way.
+    pub fn distance(&self, (x1, y1): Location, (x2, y2): Location) -> u8 {
+        let dx = (x1 as usize).abs_diff(x2 as usize);
+        let dy = (y1 as usize).abs_diff(y2 as usize);
+        if self.bounded {
+            dx.max(dy) as u8
+        } else {
+            dx.min(self.width - dx).max(dy.min(self.height - dy)) as u8
+        }
+    }
+
+    // Cells are indexed in x-major order, like `GameMap::to_map_data`.
+    pub fn cell_index(&self, (x, y): Location) -> usize {
+        x as usize * self.height + y as usize
+    }
+
+    pub fn cell_location(&self, idx: usize) -> Location {
+        ((idx / self.height) as u8, (idx % self.height) as u8)
+    }
 }
 
 #[derive(Debug)]
-pub struct GameMapError {}
+pub struct GameMapError {
+    pub message: String,
+}
 
+impl fmt::Display for GameMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for GameMapError {}
+
+// Checks that `map_data` holds a valid status for every cell of a map of the
+// given size, in the order of `GameMap::to_map_data`.
+pub fn check_map_data(map_data: &[u8], geometry: MapGeometry) -> Result<(), GameMapError> {
+    MapGeometry::new(geometry.width, geometry.height, geometry.bounded)?;
+    if map_data.len() != geometry.cell_count() {
+        return Err(GameMapError {
+            message: format!(
+                "Map data must be {} bytes, not {}",
+                geometry.cell_count(),
+                map_data.len()
+            ),
+        });
+    }
+    let is_valid = |cell| {
+        matches!(cell, LAND | WALL | WATER | DESTRUCTIBLE_WALL)
+            || (PORTAL_PAIR_BASE..PORTAL_PAIR_BASE + MAX_PORTAL_PAIRS).contains(&cell)
+    };
+    if let Some(idx) = map_data.iter().position(|&cell| !is_valid(cell)) {
+        return Err(GameMapError {
+            message: format!(
+                "Invalid cell value {} at {:?}",
+                map_data[idx],
+                geometry.cell_location(idx)
+            ),
+        });
+    }
+    for (pair, locations) in find_portals(map_data, geometry) {
+        if locations.len() != 2 {
+            return Err(GameMapError {
+                message: format!(
+                    "Portal pair {} must have 2 portals, not {} at {:?}",
+                    pair,
+                    locations.len(),
+                    locations
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+// The locations of the portals in `map_data`, by pair.
+fn find_portals(map_data: &[u8], geometry: MapGeometry) -> BTreeMap<u8, Vec<Location>> {
+    let mut portals = BTreeMap::<u8, Vec<Location>>::new();
+    for (idx, &cell) in map_data.iter().enumerate() {
+        if cell >= PORTAL_PAIR_BASE {
+            portals
+                .entry(cell - PORTAL_PAIR_BASE)
+                .or_default()
+                .push(geometry.cell_location(idx));
+        }
+    }
+    portals
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Path {
+    // Every cell entered on the way, ending with the destination.
+    pub locations: Vec<Location>,
+    pub cost: u32,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct MapCell {
     cell_type: CellType,
     crypto: Option<Box<dyn CryptoChallenge>>,
@@ -39,8 +220,58 @@ impl MapCell {
         }
     }
 
+    // Only plain land is empty, so processes and challenges are never put on
+    // water or portals.
     pub fn is_empty(&self) -> bool {
-        self.cell_type == CellType::Land && self.crypto.is_none() && self.process.is_none()
+        self.cell_type == CellType::Land && self.process.is_none() && self.crypto.is_none()
+    }
+
+    // Processes can only stand on land, water and portals that hold no other
+    // process. Walls never block the way between two cells, so a process may
+    // step diagonally between two walls that touch at a corner.
+    pub fn is_passable(&self) -> bool {
+        let walkable = matches!(
+            self.cell_type,
+            CellType::Land | CellType::Water | CellType::Portal { .. }
+        );
+        walkable && self.process.is_none()
+    }
+
+    // What entering this cell costs on top of the move.
+    pub fn get_extra_move_cost(&self) -> u32 {
+        match self.cell_type {
+            CellType::Water => WATER_EXTRA_COST,
+            _ => 0,
+        }
+    }
+
+    // What it costs a path to pass through this cell, or `None` if paths cannot.
+    // Paths avoid portals, which would take the process off the path.
+    pub fn get_movement_cost(&self) -> Option<u32> {
+        let is_portal = self.portal_destination().is_some();
+        (self.is_passable() && !is_portal).then(|| 1 + self.get_extra_move_cost())
+    }
+
+    pub fn portal_destination(&self) -> Option<Location> {
+        match self.cell_type {
+            CellType::Portal { destination, .. } => Some(destination),
+            _ => None,
+        }
+    }
+
+    // Takes 1 hit point from a destructible wall, which turns into land when it
+    // has none left. Returns the hit points left, or `None` if this cell is not
+    // a destructible wall.
+    pub fn damage_wall(&mut self) -> Option<u8> {
+        let CellType::DestructibleWall { ref mut hp } = self.cell_type else {
+            return None;
+        };
+        *hp -= 1;
+        let hp = *hp;
+        if hp == 0 {
+            self.cell_type = CellType::Land;
+        }
+        Some(hp)
     }
 
     pub fn get_process(&self) -> Option<u16> {
@@ -49,8 +280,19 @@ impl MapCell {
 
     pub fn status(&self) -> u8 {
         match self.cell_type {
-            CellType::Land => 0,
-            CellType::Wall => 1,
+            CellType::Land => LAND,
+            CellType::Wall => WALL,
+            CellType::Water => WATER,
+            CellType::Portal { .. } => PORTAL,
+            CellType::DestructibleWall { .. } => DESTRUCTIBLE_WALL,
+        }
+    }
+
+    // Like `status`, but tells portal pairs apart.
+    fn map_code(&self) -> u8 {
+        match self.cell_type {
+            CellType::Portal { pair, .. } => PORTAL_PAIR_BASE + pair,
+            _ => self.status(),
         }
     }
 
@@ -62,8 +304,12 @@ impl MapCell {
             [3, id as u8, (id >> 8) as u8]
         } else {
             match self.cell_type {
-                CellType::Land => [0, 0, 0],
-                CellType::Wall => [1, 0, 0],
+                CellType::Portal {
+                    destination: (x, y),
+                    ..
+                } => [PORTAL, x, y],
+                CellType::DestructibleWall { hp } => [DESTRUCTIBLE_WALL, hp, 0],
+                _ => [self.status(), 0, 0],
             }
         }
     }
@@ -93,34 +339,92 @@ impl MapCell {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GameMap {
     map: Vec<Vec<MapCell>>,
+    #[serde(default)]
+    geometry: MapGeometry,
     process_location_map: HashMap<u16, Location>,
+    // The locations of the processes and of the challenges on the map by
+    // numeric id, searched by `find_nearest_process` and
+    // `find_nearest_challenge`. They are rebuilt when a snapshot is loaded.
+    #[serde(skip)]
+    process_grid: LocationGrid,
+    #[serde(skip)]
+    challenge_location_map: BTreeMap<u16, LocationGrid>,
+    rng: GameRng,
+    #[serde(skip)]
+    events: EventLogger,
 }
 
 impl GameMap {
+    // A wrapping map of the default size.
     pub fn from_map_data(map_data: &[u8]) -> Result<Self, GameMapError> {
-        if map_data.len() != MAP_WIDTH * MAP_HEIGHT {
-            return Err(GameMapError {});
-        }
-        let rows = map_data.chunks_exact(MAP_WIDTH);
-        let parsed_map = rows
-            .map(|row| {
-                row.iter()
-                    .map(|cell| match *cell {
-                        0 => Ok(MapCell::new(CellType::Land)),
-                        1 => Ok(MapCell::new(CellType::Wall)),
-                        _ => Err(GameMapError {}),
-                    })
+        Self::new(map_data, MapGeometry::default())
+    }
+
+    pub fn new(map_data: &[u8], geometry: MapGeometry) -> Result<Self, GameMapError> {
+        check_map_data(map_data, geometry)?;
+        let portals = find_portals(map_data, geometry);
+        let parse_cell = |idx: usize| {
+            let cell_type = match map_data[idx] {
+                LAND => CellType::Land,
+                WALL => CellType::Wall,
+                WATER => CellType::Water,
+                DESTRUCTIBLE_WALL => CellType::DestructibleWall {
+                    hp: DESTRUCTIBLE_WALL_HP,
+                },
+                cell => {
+                    let pair = cell - PORTAL_PAIR_BASE;
+                    let location = geometry.cell_location(idx);
+                    let ends = &portals[&pair];
+                    let destination = if ends[0] == location {
+                        ends[1]
+                    } else {
+                        ends[0]
+                    };
+                    CellType::Portal { pair, destination }
+                }
+            };
+            MapCell::new(cell_type)
+        };
+        let parsed_map = (0..geometry.width)
+            .map(|x| {
+                (0..geometry.height)
+                    .map(|y| parse_cell(x * geometry.height + y))
                     .collect()
             })
-            .collect::<Result<_, _>>()?;
+            .collect();
         Ok(GameMap {
             map: parsed_map,
+            geometry,
             process_location_map: HashMap::new(),
+            process_grid: LocationGrid::new(geometry),
+            challenge_location_map: BTreeMap::new(),
+            rng: derive_rng(0, "map"),
+            events: EventLogger::default(),
         })
     }
 
+    pub fn to_map_data(&self) -> Vec<u8> {
+        self.map
+            .iter()
+            .flat_map(|row| row.iter().map(MapCell::map_code))
+            .collect()
+    }
+
+    pub fn geometry(&self) -> MapGeometry {
+        self.geometry
+    }
+
+    pub fn set_event_logger(&mut self, events: EventLogger) {
+        self.events = events;
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = derive_rng(seed, "map");
+    }
+
     pub fn get_cell(&self, location: Location) -> &MapCell {
         &self.map[location.0 as usize][location.1 as usize]
     }
@@ -137,28 +441,154 @@ impl GameMap {
         *self.process_location_map.get(&pid).unwrap()
     }
 
+    pub fn has_process(&self, pid: u16) -> bool {
+        self.process_location_map.contains_key(&pid)
+    }
+
+    pub fn count_processes(&self) -> usize {
+        self.process_location_map.len()
+    }
+
     pub fn add_process_to_map(&mut self, pid: u16, location: Location) {
-        self.process_location_map.insert(pid, location);
+        self.place_process(pid, location);
     }
 
     pub fn remove_process_from_map(&mut self, pid: u16) {
-        self.get_cell_mut(self.get_process_location(pid)).process = None;
-        self.process_location_map.remove(&pid);
+        self.unplace_process(pid);
+    }
+
+    // `process_location_map`, `process_grid` and the `process` of each cell
+    // index the same placements, so they are only ever changed together, here.
+    fn place_process(&mut self, pid: u16, location: Location) {
+        let cell = self.get_cell_mut(location);
+        assert!(
+            cell.process.is_none(),
+            "cannot place process {} on {:?}, which holds process {}",
+            pid,
+            location,
+            cell.process.unwrap()
+        );
+        cell.process = Some(pid);
+        let old_location = self.process_location_map.insert(pid, location);
+        assert!(
+            old_location.is_none(),
+            "process {} is already on the map",
+            pid
+        );
+        self.process_grid.insert(location);
+    }
+
+    fn unplace_process(&mut self, pid: u16) -> Location {
+        let location = self.process_location_map.remove(&pid).unwrap();
+        self.get_cell_mut(location).process = None;
+        self.process_grid.remove(location);
+        location
+    }
+
+    fn index_challenge(&mut self, id: u16, location: Location) {
+        let geometry = self.geometry;
+        self.challenge_location_map
+            .entry(id)
+            .or_insert_with(|| LocationGrid::new(geometry))
+            .insert(location);
+    }
+
+    pub fn rebuild_indexes(&mut self) {
+        self.process_grid = LocationGrid::new(self.geometry);
+        for &location in self.process_location_map.values() {
+            self.process_grid.insert(location);
+        }
+        self.challenge_location_map.clear();
+        for x in 0..self.geometry.width {
+            for y in 0..self.geometry.height {
+                let location = (x as u8, y as u8);
+                if let Some(ref crypto) = self.get_cell(location).crypto {
+                    self.index_challenge(crypto.get_numeric_id(), location);
+                }
+            }
+        }
+    }
+
+    // Panics unless the challenges on the map are exactly the indexed ones.
+    pub fn check_challenge_index(&self) {
+        let indexed = self
+            .challenge_location_map
+            .iter()
+            .flat_map(|(&id, locations)| locations.iter().map(move |location| (id, location)));
+        let mut count = 0;
+        for (id, location) in indexed {
+            let cell_id = self
+                .get_cell(location)
+                .crypto
+                .as_ref()
+                .map(|crypto| crypto.get_numeric_id());
+            assert_eq!(
+                cell_id,
+                Some(id),
+                "challenge {:#x} is missing from its cell {:?}",
+                id,
+                location
+            );
+            count += 1;
+        }
+        let cell_count = self
+            .map
+            .iter()
+            .flatten()
+            .filter(|cell| cell.crypto.is_some())
+            .count();
+        assert_eq!(
+            count, cell_count,
+            "cells hold challenges that are not indexed"
+        );
+    }
+
+    // Panics unless every process on the map can be found both by its pid and
+    // by its location, and is in the process grid.
+    pub fn check_process_index(&self) {
+        for (&pid, &location) in &self.process_location_map {
+            assert_eq!(
+                self.get_cell(location).process,
+                Some(pid),
+                "process {} is missing from its cell {:?}",
+                pid,
+                location
+            );
+        }
+        let mut grid_locations: Vec<_> = self.process_grid.iter().collect();
+        let mut locations: Vec<_> = self.process_location_map.values().copied().collect();
+        grid_locations.sort();
+        locations.sort();
+        assert_eq!(
+            grid_locations, locations,
+            "the process grid disagrees with the process locations"
+        );
+        let cell_count = self
+            .map
+            .iter()
+            .flatten()
+            .filter(|cell| cell.process.is_some())
+            .count();
+        assert_eq!(
+            cell_count,
+            self.process_location_map.len(),
+            "cells hold processes that have no location"
+        );
     }
 
     pub fn find_empty_location_nearby(
-        &self,
-        (x, y): Location,
+        &mut self,
+        location: Location,
         attempts: usize,
         range: i8,
     ) -> Option<Location> {
-        let mut rng = rand::thread_rng();
         for _ in 0..attempts {
-            let x_diff = rng.gen_range(-range..=range);
-            let y_diff = rng.gen_range(-range..=range);
-            let new_x = x.wrapping_add_signed(x_diff);
-            let new_y = y.wrapping_add_signed(y_diff);
-            let new_location = (new_x, new_y);
+            let x_diff = self.rng.gen_range(-range..=range);
+            let y_diff = self.rng.gen_range(-range..=range);
+            let Some(new_location) = self.geometry.offset(location, x_diff as i32, y_diff as i32)
+            else {
+                continue;
+            };
             if self.get_cell(new_location).is_empty() {
                 return Some(new_location);
             }
@@ -167,80 +597,206 @@ impl GameMap {
     }
 
     fn try_add_crypto_at_random(&mut self, challenge: Box<dyn CryptoChallenge>) {
-        let location = (rand::random(), rand::random());
+        // Scaled down from the largest map size, which keeps the locations
+        // drawn on maps of that size as they always were.
+        let (x, y): Location = self.rng.gen();
+        let location = (
+            (x as usize * self.geometry.width / MAP_WIDTH) as u8,
+            (y as usize * self.geometry.height / MAP_HEIGHT) as u8,
+        );
         let cell = self.get_cell(location);
         if cell.is_empty() {
-            log_event(GameEvent::NewChallenge {
-                challenge_type: challenge.get_name(),
+            self.events.log_event(GameEvent::NewChallenge {
+                challenge_type: challenge.get_name().into(),
                 difficulty: challenge.get_difficulty(),
                 location,
             });
+            self.index_challenge(challenge.get_numeric_id(), location);
             self.get_cell_mut(location).crypto = Some(challenge);
         }
     }
 
+    // See `MapCell::solve_crypto`.
+    pub fn solve_challenge(
+        &mut self,
+        location: Location,
+        nonce: (u16, u16, u16, u16),
+    ) -> Option<Wallet> {
+        let cell = self.get_cell(location);
+        let id = cell.crypto.as_ref()?.get_numeric_id();
+        let reward = self.get_cell_mut(location).solve_crypto(nonce)?;
+        let locations = self.challenge_location_map.get_mut(&id).unwrap();
+        locations.remove(location);
+        if locations.is_empty() {
+            self.challenge_location_map.remove(&id);
+        }
+        Some(reward)
+    }
+
+    // The nearest challenge with the given numeric id, see
+    // `LocationGrid::find_nearest`.
+    pub fn find_nearest_challenge(
+        &self,
+        center: Location,
+        id: u16,
+        radius: u16,
+    ) -> Option<Location> {
+        let locations = self.challenge_location_map.get(&id)?;
+        locations.find_nearest(center, radius, |_| true)
+    }
+
+    // The nearest process for which `filter` holds, see
+    // `LocationGrid::find_nearest`.
+    pub fn find_nearest_process<F: Fn(u16) -> bool>(
+        &self,
+        center: Location,
+        radius: u16,
+        filter: F,
+    ) -> Option<Location> {
+        self.process_grid.find_nearest(center, radius, |location| {
+            filter(self.get_process_at(location).unwrap())
+        })
+    }
+
     fn add_cryptos(&mut self, name: &str, distributions: &[(i64, f64)]) {
-        let mut rng = thread_rng();
         for &(difficulty, probability) in distributions {
-            if rng.gen::<f64>() < probability {
-                if let Some(challenge) = generate_crypto_challenge(name, difficulty) {
+            if self.rng.gen::<f64>() < probability {
+                if let Some(challenge) = generate_crypto_challenge(name, difficulty, &mut self.rng)
+                {
                     self.try_add_crypto_at_random(challenge);
                 }
             }
         }
     }
 
-    pub fn tick(&mut self, config: &HashMap<String, Vec<(i64, f64)>>) {
+    pub fn tick(&mut self, config: &BTreeMap<String, Vec<(i64, f64)>>) {
         for (name, distributions) in config.iter() {
             self.add_cryptos(name, distributions);
         }
     }
 
     pub fn move_process_to(&mut self, pid: u16, location: Location) -> bool {
-        if self.get_cell(location).process.is_some() {
+        if !self.get_cell(location).is_passable() {
             return false;
         }
-        self.get_cell_mut(self.get_process_location(pid)).process = None;
-        *self.process_location_map.get_mut(&pid).unwrap() = location;
-        self.get_cell_mut(location).process = Some(pid);
-        log_event(GameEvent::Move { pid, location });
+        self.unplace_process(pid);
+        self.place_process(pid, location);
+        self.events.log_event(GameEvent::Move { pid, location });
+        // A process stepping on a portal comes out of the other one, unless
+        // another process stands there.
+        if let Some(destination) = self.get_cell(location).portal_destination() {
+            if self.get_cell(destination).is_passable() {
+                self.unplace_process(pid);
+                self.place_process(pid, destination);
+                self.events.log_event(GameEvent::Move {
+                    pid,
+                    location: destination,
+                });
+            }
+        }
         true
     }
 
-    pub fn pathfind(
+    // See `MapCell::damage_wall`.
+    pub fn damage_wall(&mut self, pid: u16, location: Location) -> bool {
+        let Some(hp) = self.get_cell_mut(location).damage_wall() else {
+            return false;
+        };
+        self.events
+            .log_event(GameEvent::WallDamaged { pid, location, hp });
+        if hp == 0 {
+            self.events
+                .log_event(GameEvent::WallDestroyed { pid, location });
+        }
+        true
+    }
+
+    // Walls can only be built on empty land.
+    pub fn build_wall(&mut self, pid: u16, location: Location) -> bool {
+        let cell = self.get_cell_mut(location);
+        if !cell.is_empty() {
+            return false;
+        }
+        cell.cell_type = CellType::Wall;
+        self.events
+            .log_event(GameEvent::WallBuilt { pid, location });
+        true
+    }
+
+    // Turns a wall, destructible or not, into land.
+    pub fn dig_wall(&mut self, pid: u16, location: Location) -> bool {
+        let cell = self.get_cell_mut(location);
+        if !matches!(
+            cell.cell_type,
+            CellType::Wall | CellType::DestructibleWall { .. }
+        ) {
+            return false;
+        }
+        cell.cell_type = CellType::Land;
+        self.events
+            .log_event(GameEvent::WallDestroyed { pid, location });
+        true
+    }
+
+    // Finds a cheapest path on the map's own terrain costs, see
+    // `MapCell::get_movement_cost`.
+    pub fn pathfind(&self, start: Location, end: Location, max_cost: u32) -> Option<Path> {
+        self.find_path(start, end, max_cost, MapCell::get_movement_cost)
+    }
+
+    // A* search for a cheapest path from `start` to `end` whose cost is at most
+    // `max_cost`, where entering a cell costs `cell_cost` of it, or is
+    // impossible if that is `None`. Costs below 1 count as 1, which keeps the
+    // Chebyshev distance an admissible heuristic.
+    pub fn find_path<F: Fn(&MapCell) -> Option<u32>>(
         &self,
         start: Location,
         end: Location,
-        max_len: usize,
-    ) -> Option<Vec<Location>> {
-        let mut prev = HashMap::new();
-        prev.insert(start, start);
-        let mut queue = VecDeque::new();
-        queue.push_back((0, start));
-        while queue.front().is_some_and(|&(dis, _)| dis <= max_len) {
-            let (dis, cur_loc) = queue.pop_front().unwrap();
+        max_cost: u32,
+        cell_cost: F,
+    ) -> Option<Path> {
+        let geometry = self.geometry;
+        let heuristic = |location| geometry.distance(location, end) as u32;
+        // Indexed like `to_map_data`. Every cell index fits in a u16.
+        let mut costs = vec![u32::MAX; geometry.cell_count()];
+        let mut prev = vec![0u16; geometry.cell_count()];
+        let mut queue = BinaryHeap::new();
+        let start_idx = geometry.cell_index(start);
+        costs[start_idx] = 0;
+        queue.push(Reverse((heuristic(start), 0, start_idx)));
+        while let Some(Reverse((_, cost, idx))) = queue.pop() {
+            if cost > costs[idx] {
+                continue;
+            }
+            let cur_loc = geometry.cell_location(idx);
             if cur_loc == end {
-                let mut cur = end;
-                let mut path = vec![];
-                while cur != start {
-                    path.push(cur);
-                    cur = *prev.get(&cur).unwrap();
+                let mut locations = vec![];
+                let mut idx = idx;
+                while idx != start_idx {
+                    locations.push(geometry.cell_location(idx));
+                    idx = prev[idx] as usize;
                 }
-                path.reverse();
-                return Some(path);
+                locations.reverse();
+                return Some(Path { locations, cost });
             }
             for dx in -1..=1 {
                 for dy in -1..=1 {
-                    let new_loc = (
-                        cur_loc.0.wrapping_add_signed(dx),
-                        cur_loc.1.wrapping_add_signed(dy),
-                    );
-                    if self.get_cell(new_loc).get_process().is_none()
-                        && !prev.contains_key(&new_loc)
+                    let Some(new_loc) = geometry.offset(cur_loc, dx, dy) else {
+                        continue;
+                    };
+                    let Some(step_cost) = cell_cost(self.get_cell(new_loc)) else {
+                        continue;
+                    };
+                    let new_cost = cost.saturating_add(step_cost.max(1));
+                    let new_idx = geometry.cell_index(new_loc);
+                    if new_cost >= costs[new_idx]
+                        || new_cost.saturating_add(heuristic(new_loc)) > max_cost
                     {
-                        prev.insert(new_loc, cur_loc);
-                        queue.push_back((dis + 1, new_loc));
+                        continue;
                     }
+                    costs[new_idx] = new_cost;
+                    prev[new_idx] = idx as u16;
+                    queue.push(Reverse((new_cost + heuristic(new_loc), new_cost, new_idx)));
                 }
             }
         }
@@ -0,0 +1,309 @@
+use crate::game::map::{
+    check_map_data, GameMapError, Location, MapGeometry, DESTRUCTIBLE_WALL, LAND, MAP_HEIGHT,
+    MAP_WIDTH, PORTAL_PAIR_BASE, WALL, WATER,
+};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// The PGM gray levels of the cell types, out of a maximum value of 3.
+const PGM_LEVELS: [(u8, u32); 4] = [(WALL, 0), (DESTRUCTIBLE_WALL, 1), (WATER, 2), (LAND, 3)];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapFormat {
+    // One byte per cell, in the order of `GameMap::to_map_data`.
+    Raw,
+    // One line per row of the map, from y = 0 down, with a character per cell:
+    // `.` for land, `#` for a wall, `~` for water, `%` for a destructible wall,
+    // a letter for a portal of the pair of that letter, and `1` to `9` for land
+    // with the spawn point of that uid.
+    Ascii,
+    // A grayscale image, binary (P5) or plain (P2), with a pixel per cell. From
+    // black to white, the gray levels in `PGM_LEVELS` are walls, destructible
+    // walls, water and land. Portals have no gray level.
+    Pgm,
+}
+
+impl MapFormat {
+    // `.txt` files are ASCII and `.pgm` files are PGM images. Anything else is
+    // raw.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("txt") => MapFormat::Ascii,
+            Some("pgm") => MapFormat::Pgm,
+            _ => MapFormat::Raw,
+        }
+    }
+}
+
+pub struct MapFile {
+    // In the format of `GameMap::to_map_data`.
+    pub map_data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    // The spawn points marked in the map, by uid. Only ASCII maps have them.
+    pub spawn_points: BTreeMap<u16, Location>,
+}
+
+impl MapFile {
+    // Raw maps are `size` cells wide and tall, by default the largest size. The
+    // other formats have their own sizes, which must match `size` if given.
+    pub fn parse(
+        bytes: &[u8],
+        format: MapFormat,
+        size: Option<(usize, usize)>,
+    ) -> Result<Self, GameMapError> {
+        let map_file = match format {
+            MapFormat::Raw => {
+                let (width, height) = size.unwrap_or((MAP_WIDTH, MAP_HEIGHT));
+                MapFile {
+                    map_data: bytes.to_vec(),
+                    width,
+                    height,
+                    spawn_points: BTreeMap::new(),
+                }
+            }
+            MapFormat::Ascii => parse_ascii(bytes)?,
+            MapFormat::Pgm => parse_pgm(bytes)?,
+        };
+        if let Some((width, height)) = size {
+            if (width, height) != (map_file.width, map_file.height) {
+                return Err(error(format!(
+                    "Map is {}x{}, not {}x{}",
+                    map_file.width, map_file.height, width, height
+                )));
+            }
+        }
+        check_map_data(&map_file.map_data, map_file.geometry(false)?)?;
+        Ok(map_file)
+    }
+
+    pub fn geometry(&self, bounded: bool) -> Result<MapGeometry, GameMapError> {
+        MapGeometry::new(self.width, self.height, bounded)
+    }
+
+    // Spawn points of uids above 9 cannot be marked and are left out of ASCII
+    // maps. Maps with portals cannot be PGM images.
+    pub fn to_bytes(&self, format: MapFormat) -> Result<Vec<u8>, GameMapError> {
+        let bytes = match format {
+            MapFormat::Raw => self.map_data.clone(),
+            MapFormat::Ascii => {
+                let geometry = self.geometry(false)?;
+                let mut chars: Vec<u8> =
+                    self.map_data.iter().map(|&cell| ascii_char(cell)).collect();
+                for (&uid, &location) in &self.spawn_points {
+                    if (1..=9).contains(&uid) {
+                        chars[geometry.cell_index(location)] = b'0' + uid as u8;
+                    }
+                }
+                let mut bytes = Vec::with_capacity((self.width + 1) * self.height);
+                for y in 0..self.height {
+                    bytes.extend((0..self.width).map(|x| chars[x * self.height + y]));
+                    bytes.push(b'\n');
+                }
+                bytes
+            }
+            MapFormat::Pgm => {
+                let mut bytes = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+                for y in 0..self.height {
+                    for x in 0..self.width {
+                        let cell = self.map_data[x * self.height + y];
+                        let Some(&(_, level)) = PGM_LEVELS.iter().find(|&&(code, _)| code == cell)
+                        else {
+                            return Err(error(format!(
+                                "Portal at {:?} cannot be stored in a PGM image",
+                                (x, y)
+                            )));
+                        };
+                        bytes.push((level * 85) as u8);
+                    }
+                }
+                bytes
+            }
+        };
+        Ok(bytes)
+    }
+}
+
+fn ascii_char(cell: u8) -> u8 {
+    match cell {
+        LAND => b'.',
+        WALL => b'#',
+        WATER => b'~',
+        DESTRUCTIBLE_WALL => b'%',
+        _ => match cell - PORTAL_PAIR_BASE {
+            pair @ 0..=25 => b'A' + pair,
+            pair => b'a' + pair - 26,
+        },
+    }
+}
+
+fn ascii_cell(byte: u8) -> Option<u8> {
+    Some(match byte {
+        b'.' => LAND,
+        b'#' => WALL,
+        b'~' => WATER,
+        b'%' => DESTRUCTIBLE_WALL,
+        b'A'..=b'Z' => PORTAL_PAIR_BASE + byte - b'A',
+        b'a'..=b'z' => PORTAL_PAIR_BASE + byte - b'a' + 26,
+        _ => return None,
+    })
+}
+
+fn error(message: String) -> GameMapError {
+    GameMapError { message }
+}
+
+fn parse_ascii(bytes: &[u8]) -> Result<MapFile, GameMapError> {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    if bytes.is_empty() {
+        return Err(error("Map has no rows".to_string()));
+    }
+    let rows: Vec<&[u8]> = bytes
+        .split(|&byte| byte == b'\n')
+        .map(|row| row.strip_suffix(b"\r").unwrap_or(row))
+        .collect();
+    let height = rows.len();
+    let width = rows[0].len();
+    let geometry = MapGeometry::new(width, height, false)?;
+    let mut map_data = vec![LAND; geometry.cell_count()];
+    let mut spawn_points = BTreeMap::new();
+    for (y, row) in rows.into_iter().enumerate() {
+        if row.len() != width {
+            return Err(error(format!(
+                "Row {} must have {} cells like the first row, not {}",
+                y,
+                width,
+                row.len()
+            )));
+        }
+        for (x, &byte) in row.iter().enumerate() {
+            let location = (x as u8, y as u8);
+            map_data[geometry.cell_index(location)] = match byte {
+                b'1'..=b'9' => {
+                    let uid = (byte - b'0') as u16;
+                    if let Some(other) = spawn_points.insert(uid, location) {
+                        return Err(error(format!(
+                            "Spawn point of uid {} marked at both {:?} and {:?}",
+                            uid, other, location
+                        )));
+                    }
+                    LAND
+                }
+                _ => ascii_cell(byte).ok_or_else(|| {
+                    error(format!(
+                        "Invalid character 0x{:02x} at {:?}",
+                        byte, location
+                    ))
+                })?,
+            };
+        }
+    }
+    Ok(MapFile {
+        map_data,
+        width,
+        height,
+        spawn_points,
+    })
+}
+
+// Splits a PGM file into whitespace-separated tokens, skipping comments, and
+// keeps track of where the pixel data of a binary image starts.
+struct PgmTokens<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PgmTokens<'a> {
+    fn next_token(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.bytes.get(self.pos)? {
+                byte if byte.is_ascii_whitespace() => self.pos += 1,
+                b'#' => {
+                    while self.bytes.get(self.pos).is_some_and(|&byte| byte != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|byte| !byte.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        Some(&self.bytes[start..self.pos])
+    }
+
+    fn next_number(&mut self, what: &str) -> Result<u32, GameMapError> {
+        let token = self
+            .next_token()
+            .ok_or_else(|| error(format!("Missing {} in PGM image", what)))?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| error(format!("Invalid {} in PGM image", what)))
+    }
+}
+
+fn parse_pgm(bytes: &[u8]) -> Result<MapFile, GameMapError> {
+    let mut tokens = PgmTokens { bytes, pos: 0 };
+    let binary = match tokens.next_token() {
+        Some(b"P5") => true,
+        Some(b"P2") => false,
+        _ => return Err(error("Not a PGM image".to_string())),
+    };
+    let width = tokens.next_number("width")? as usize;
+    let height = tokens.next_number("height")? as usize;
+    let geometry = MapGeometry::new(width, height, false)?;
+    let max_value = tokens.next_number("maximum value")?;
+    if !(1..=255).contains(&max_value) {
+        return Err(error(format!(
+            "Maximum value must be between 1 and 255, not {}",
+            max_value
+        )));
+    }
+
+    let pixels: Vec<u32> = if binary {
+        // A single whitespace character separates the header from the pixels.
+        let pixels = &bytes[(tokens.pos + 1).min(bytes.len())..];
+        if pixels.len() != geometry.cell_count() {
+            return Err(error(format!(
+                "Image must have {} pixels, not {}",
+                geometry.cell_count(),
+                pixels.len()
+            )));
+        }
+        pixels.iter().map(|&pixel| pixel as u32).collect()
+    } else {
+        (0..geometry.cell_count())
+            .map(|_| tokens.next_number("pixel"))
+            .collect::<Result<_, _>>()?
+    };
+
+    let mut map_data = vec![LAND; geometry.cell_count()];
+    for (idx, &pixel) in pixels.iter().enumerate() {
+        let location = ((idx % width) as u8, (idx / width) as u8);
+        // Levels that fall between two pixel values, like water with a
+        // maximum value of 1, cannot be drawn.
+        let cell = PGM_LEVELS
+            .iter()
+            .find(|&&(_, level)| pixel * 3 == level * max_value)
+            .map(|&(code, _)| code);
+        map_data[geometry.cell_index(location)] =
+            cell.ok_or_else(|| error(format!("Invalid pixel value {} at {:?}", pixel, location)))?;
+    }
+    Ok(MapFile {
+        map_data,
+        width,
+        height,
+        spawn_points: BTreeMap::new(),
+    })
+}
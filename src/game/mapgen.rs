@@ -0,0 +1,413 @@
+use crate::config::{GameConfiguration, RawUserConfiguration};
+use crate::game::map::{Location, MapGeometry};
+use crate::game::mapfile::MapFile;
+use crate::game::rng::{derive_rng, GameRng};
+
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+// Maps whose spawn points cannot be placed fairly are thrown away and drawn
+// again, up to this many times.
+const MAX_ATTEMPTS: usize = 16;
+// Candidates drawn for each spawn point, of which the one farthest from the
+// other spawn points is kept.
+const SPAWN_CANDIDATES: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapStyle {
+    // Cellular-automata caves.
+    Caves,
+    // A maze with corridors one cell wide.
+    Maze,
+    // Open land with scattered blocks of walls.
+    Arena,
+}
+
+impl MapStyle {
+    fn default_wall_density(self) -> f64 {
+        match self {
+            MapStyle::Caves => 0.45,
+            MapStyle::Maze => 0.0,
+            MapStyle::Arena => 0.1,
+        }
+    }
+}
+
+impl std::str::FromStr for MapStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "caves" => Ok(MapStyle::Caves),
+            "maze" => Ok(MapStyle::Maze),
+            "arena" => Ok(MapStyle::Arena),
+            _ => Err(format!("Unknown map style `{}`", s)),
+        }
+    }
+}
+
+pub struct MapGenOptions {
+    pub style: MapStyle,
+    pub geometry: MapGeometry,
+    pub seed: u64,
+    pub players: usize,
+    // The map looks the same after turning it by 360 / `symmetry` degrees, and
+    // so do the spawn points. Either 1, 2 or 4.
+    pub symmetry: usize,
+    // The share of walls in the initial noise of caves, or the share of walls
+    // in an arena. Ignored by mazes.
+    pub wall_density: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct MapGenError {
+    pub message: String,
+}
+
+impl fmt::Display for MapGenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for MapGenError {}
+
+pub struct GeneratedMap {
+    // In the format of `GameMap::to_map_data`.
+    pub map_data: Vec<u8>,
+    pub geometry: MapGeometry,
+    pub spawn_points: Vec<Location>,
+}
+
+impl GeneratedMap {
+    // The map with the spawn point of each player marked, players being
+    // numbered from uid 1.
+    pub fn to_map_file(&self) -> MapFile {
+        MapFile {
+            map_data: self.map_data.clone(),
+            width: self.geometry.width,
+            height: self.geometry.height,
+            spawn_points: (1..).zip(self.spawn_points.iter().copied()).collect(),
+        }
+    }
+
+    // A configuration for a game on this map, with placeholder paths for the
+    // bots of each player.
+    pub fn to_game_config(&self, mapdata_path: &Path) -> GameConfiguration {
+        GameConfiguration {
+            user_configs: self
+                .spawn_points
+                .iter()
+                .enumerate()
+                .map(|(idx, &spawn_point)| RawUserConfiguration {
+                    initd_bytecode: format!("user{}.bin", idx + 1).into(),
+                    initd_memory: format!("user{}.mem", idx + 1).into(),
+                    uid: idx as u16 + 1,
+                    spawn_point: Some(spawn_point),
+                })
+                .collect(),
+            default_nice: 0,
+            initd_lifetime: 1000,
+            max_processes: 16,
+            mapdata_path: mapdata_path.to_path_buf(),
+            map_size: Some((self.geometry.width, self.geometry.height)),
+            bounded_map: self.geometry.bounded,
+            crypto_spawn: BTreeMap::from([("dog".to_string(), vec![(1, 0.5)])]),
+            seed: None,
+            max_ticks: None,
+            last_user_standing: false,
+            score_threshold: None,
+        }
+    }
+}
+
+// Generates a map in which every land cell can reach every other one, along
+// with a spawn point on land for each player.
+pub fn generate(options: &MapGenOptions) -> Result<GeneratedMap, MapGenError> {
+    let geometry = options.geometry;
+    MapGeometry::new(geometry.width, geometry.height, geometry.bounded).map_err(|err| {
+        MapGenError {
+            message: err.message,
+        }
+    })?;
+    if ![1, 2, 4].contains(&options.symmetry) {
+        return Err(MapGenError {
+            message: format!("Symmetry must be 1, 2 or 4, not {}", options.symmetry),
+        });
+    }
+    if options.players == 0 || !options.players.is_multiple_of(options.symmetry) {
+        return Err(MapGenError {
+            message: format!(
+                "The number of players must be a positive multiple of the symmetry {}",
+                options.symmetry
+            ),
+        });
+    }
+    if options.symmetry == 4 && geometry.width != geometry.height {
+        return Err(MapGenError {
+            message: "Symmetry 4 needs a square map".to_string(),
+        });
+    }
+    // Rooms of a maze sit on even coordinates, which must line up across the
+    // borders of a wrapping map and reach both borders of a bounded one.
+    let room_parity = if geometry.bounded { 1 } else { 0 };
+    if options.style == MapStyle::Maze
+        && (geometry.width % 2 != room_parity || geometry.height % 2 != room_parity)
+    {
+        return Err(MapGenError {
+            message: "Mazes need even sizes on wrapping maps and odd sizes on bounded maps"
+                .to_string(),
+        });
+    }
+    let density = options
+        .wall_density
+        .unwrap_or(options.style.default_wall_density());
+    if !(0.0..1.0).contains(&density) {
+        return Err(MapGenError {
+            message: format!("Wall density must be in [0, 1), not {}", density),
+        });
+    }
+
+    let mut rng = derive_rng(options.seed, "mapgen");
+    for _ in 0..MAX_ATTEMPTS {
+        let mut walls = match options.style {
+            MapStyle::Caves => generate_caves(geometry, &mut rng, density),
+            MapStyle::Maze => generate_maze(geometry, &mut rng, options.symmetry),
+            MapStyle::Arena => generate_arena(geometry, &mut rng, density),
+        };
+        symmetrize(geometry, &mut walls, options.symmetry);
+        let reachable = find_largest_region(geometry, &walls);
+        if !is_symmetric(geometry, &reachable, options.symmetry) {
+            continue;
+        }
+        let Some(spawn_points) = choose_spawn_points(&reachable, options, &mut rng) else {
+            continue;
+        };
+        // Wall off the pockets that cannot be reached from the spawn points.
+        for (wall, reachable) in walls.iter_mut().zip(&reachable) {
+            *wall |= !reachable;
+        }
+        return Ok(GeneratedMap {
+            map_data: walls.into_iter().map(u8::from).collect(),
+            geometry,
+            spawn_points,
+        });
+    }
+    Err(MapGenError {
+        message: "Failed to generate a map with fair spawn points, try a lower wall density"
+            .to_string(),
+    })
+}
+
+fn neighbors(geometry: MapGeometry, location: Location) -> impl Iterator<Item = Location> {
+    (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter(|&offset| offset != (0, 0))
+        .filter_map(move |(dx, dy)| geometry.offset(location, dx, dy))
+}
+
+// Turns a location by 90 degrees, on a square map. A wrapping map is turned
+// around (0, 0), which is as good a center as any and keeps even coordinates
+// even, and a bounded map around its center.
+fn quarter_turn(geometry: MapGeometry, (x, y): Location) -> Location {
+    if geometry.bounded {
+        ((geometry.width - 1 - y as usize) as u8, x)
+    } else {
+        geometry.locate(-(y as i32), x as i32).unwrap()
+    }
+}
+
+fn half_turn(geometry: MapGeometry, (x, y): Location) -> Location {
+    if geometry.bounded {
+        (
+            (geometry.width - 1 - x as usize) as u8,
+            (geometry.height - 1 - y as usize) as u8,
+        )
+    } else {
+        geometry.locate(-(x as i32), -(y as i32)).unwrap()
+    }
+}
+
+// The locations that `location` is turned into by the symmetries of the map,
+// starting with itself.
+fn orbit(geometry: MapGeometry, location: Location, symmetry: usize) -> Vec<Location> {
+    match symmetry {
+        2 => vec![location, half_turn(geometry, location)],
+        4 => std::iter::successors(Some(location), |&location| {
+            Some(quarter_turn(geometry, location))
+        })
+        .take(4)
+        .collect(),
+        _ => vec![location],
+    }
+}
+
+fn generate_caves(geometry: MapGeometry, rng: &mut GameRng, density: f64) -> Vec<bool> {
+    let cell_count = geometry.cell_count();
+    let mut walls: Vec<bool> = (0..cell_count).map(|_| rng.gen_bool(density)).collect();
+    for _ in 0..5 {
+        walls = (0..cell_count)
+            .map(|idx| {
+                // Beyond the borders of a bounded map is all walls.
+                let location = geometry.cell_location(idx);
+                let count = 8 - neighbors(geometry, location)
+                    .filter(|&location| !walls[geometry.cell_index(location)])
+                    .count();
+                count >= 5 || (walls[idx] && count >= 4)
+            })
+            .collect();
+    }
+    walls
+}
+
+// Rooms sit on even coordinates and pillars on odd ones. The cells between two
+// rooms are opened along a random spanning tree of the rooms, or of their
+// orbits, so that symmetry does not cut any corridors.
+fn generate_maze(geometry: MapGeometry, rng: &mut GameRng, symmetry: usize) -> Vec<bool> {
+    let cell_count = geometry.cell_count();
+    let mut walls: Vec<bool> = (0..cell_count)
+        .map(|idx| {
+            let (x, y) = geometry.cell_location(idx);
+            x % 2 == 1 || y % 2 == 1
+        })
+        .collect();
+    let mut visited = vec![false; cell_count];
+    let mut stack = vec![(0u8, 0u8)];
+    visited[0] = true;
+    while let Some(&room) = stack.last() {
+        let unvisited: Vec<_> = [(2, 0), (-2, 0), (0, 2), (0, -2)]
+            .into_iter()
+            .filter_map(|(dx, dy)| Some(((dx, dy), geometry.offset(room, dx, dy)?)))
+            .filter(|&(_, next)| !visited[geometry.cell_index(next)])
+            .collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let ((dx, dy), next) = unvisited[rng.gen_range(0..unvisited.len())];
+        let between = geometry.offset(room, dx / 2, dy / 2).unwrap();
+        for location in orbit(geometry, between, symmetry) {
+            walls[geometry.cell_index(location)] = false;
+        }
+        for location in orbit(geometry, next, symmetry) {
+            visited[geometry.cell_index(location)] = true;
+        }
+        stack.push(next);
+    }
+    walls
+}
+
+fn generate_arena(geometry: MapGeometry, rng: &mut GameRng, density: f64) -> Vec<bool> {
+    let mut walls = vec![false; geometry.cell_count()];
+    let target = (density * geometry.cell_count() as f64) as usize;
+    let mut count = 0;
+    while count < target {
+        let (x, y): Location = rng.gen();
+        let (width, height) = (rng.gen_range(1..=5), rng.gen_range(1..=5));
+        let Some(corner) = geometry.locate(x as i32, y as i32) else {
+            continue;
+        };
+        for dx in 0..width {
+            for dy in 0..height {
+                let Some(location) = geometry.offset(corner, dx, dy) else {
+                    continue;
+                };
+                let idx = geometry.cell_index(location);
+                count += !walls[idx] as usize;
+                walls[idx] = true;
+            }
+        }
+    }
+    walls
+}
+
+// Copies every cell to its turned images, so that the first cell of each orbit
+// decides the whole orbit.
+fn symmetrize(geometry: MapGeometry, walls: &mut [bool], symmetry: usize) {
+    for idx in 0..geometry.cell_count() {
+        let first = orbit(geometry, geometry.cell_location(idx), symmetry)
+            .into_iter()
+            .map(|location| geometry.cell_index(location))
+            .min()
+            .unwrap();
+        walls[idx] = walls[first];
+    }
+}
+
+fn is_symmetric(geometry: MapGeometry, cells: &[bool], symmetry: usize) -> bool {
+    (0..geometry.cell_count()).all(|idx| {
+        orbit(geometry, geometry.cell_location(idx), symmetry)
+            .into_iter()
+            .all(|location| cells[geometry.cell_index(location)] == cells[idx])
+    })
+}
+
+// The largest set of land cells that processes can move between, the way
+// `MapCell::is_passable` allows.
+fn find_largest_region(geometry: MapGeometry, walls: &[bool]) -> Vec<bool> {
+    let cell_count = geometry.cell_count();
+    let mut regions = vec![usize::MAX; cell_count];
+    let mut largest = (0, 0);
+    for start in 0..cell_count {
+        if walls[start] || regions[start] != usize::MAX {
+            continue;
+        }
+        regions[start] = start;
+        let mut stack = vec![start];
+        let mut size = 0;
+        while let Some(idx) = stack.pop() {
+            size += 1;
+            for location in neighbors(geometry, geometry.cell_location(idx)) {
+                let next = geometry.cell_index(location);
+                if !walls[next] && regions[next] == usize::MAX {
+                    regions[next] = start;
+                    stack.push(next);
+                }
+            }
+        }
+        if size > largest.0 {
+            largest = (size, start);
+        }
+    }
+    regions
+        .into_iter()
+        .map(|region| region == largest.1)
+        .collect()
+}
+
+// Spreads the spawn points out over `reachable`, adding a whole orbit at a time.
+fn choose_spawn_points(
+    reachable: &[bool],
+    options: &MapGenOptions,
+    rng: &mut GameRng,
+) -> Option<Vec<Location>> {
+    let geometry = options.geometry;
+    let candidates: Vec<_> = (0..geometry.cell_count())
+        .filter(|&idx| reachable[idx])
+        .map(|idx| geometry.cell_location(idx))
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    let mut spawn_points: Vec<Location> = vec![];
+    while spawn_points.len() < options.players {
+        let distance_to_others = |candidate| {
+            spawn_points
+                .iter()
+                .chain(&orbit(geometry, candidate, options.symmetry)[1..])
+                .map(|&other| geometry.distance(candidate, other))
+                .min()
+                .unwrap_or(u8::MAX)
+        };
+        let best = (0..SPAWN_CANDIDATES)
+            .map(|_| candidates[rng.gen_range(0..candidates.len())])
+            .max_by_key(|&candidate| distance_to_others(candidate))?;
+        if distance_to_others(best) == 0 {
+            return None;
+        }
+        spawn_points.extend(orbit(geometry, best, options.symmetry));
+    }
+    Some(spawn_points)
+}
@@ -1,3 +1,7 @@
 pub mod crypto;
+pub mod grid;
 pub mod map;
+pub mod mapfile;
+pub mod mapgen;
 pub mod replay;
+pub mod rng;
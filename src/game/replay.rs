@@ -1,15 +1,39 @@
 use crate::game::crypto::Wallet;
 use crate::game::map::Location;
 
-use serde::Serialize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
 
-#[derive(Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum GameEvent<'a> {
+    Seed {
+        seed: u64,
+    },
+    TickStart {
+        tick: u64,
+    },
+    TickEnd {
+        tick: u64,
+    },
+    GameOver {
+        tick: u64,
+        reason: GameEndReason,
+    },
+    Ranking {
+        ranking: Vec<RankingEntry>,
+    },
     InitMap {
-        map_data: &'a [u8],
+        map_data: Cow<'a, [u8]>,
         map_height: usize,
         map_width: usize,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        bounded: bool,
     },
     Move {
         pid: u16,
@@ -19,6 +43,22 @@ pub enum GameEvent<'a> {
         attacker_pid: u16,
         defender_pid: u16,
     },
+    WallBuilt {
+        pid: u16,
+        location: Location,
+    },
+    // A hit took 1 hit point from the destructible wall at `location`, leaving
+    // it with `hp`.
+    WallDamaged {
+        pid: u16,
+        location: Location,
+        hp: u8,
+    },
+    // The wall at `location` turned into land, either dug out or broken down.
+    WallDestroyed {
+        pid: u16,
+        location: Location,
+    },
 
     NewProcess {
         uid: u16,
@@ -36,9 +76,12 @@ pub enum GameEvent<'a> {
     Detach {
         pid: u16,
     },
+    UserLeft {
+        uid: u16,
+    },
 
     NewChallenge {
-        challenge_type: &'a str,
+        challenge_type: Cow<'a, str>,
         difficulty: u16,
         location: Location,
     },
@@ -53,10 +96,224 @@ pub enum GameEvent<'a> {
     },
     WalletUpdate {
         uid: u16,
-        new_wallet: &'a Wallet,
+        new_wallet: Cow<'a, Wallet>,
     },
 }
 
-pub fn log_event(event: GameEvent) {
-    println!("EVENT|{}", serde_json::to_string(&event).unwrap());
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameEndReason {
+    AllUsersLeft,
+    LastUserStanding,
+    ScoreThreshold,
+    MaxTicks,
+    Stopped,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RankingEntry {
+    pub rank: usize,
+    pub uid: u16,
+    pub score: i64,
+    pub wallet: Wallet,
+}
+
+pub type OwnedGameEvent = GameEvent<'static>;
+
+#[derive(Serialize)]
+struct SequencedEvent<'a, 'b> {
+    seq: u64,
+    #[serde(flatten)]
+    event: &'b GameEvent<'a>,
+}
+
+impl GameEvent<'_> {
+    pub fn into_owned(self) -> OwnedGameEvent {
+        match self {
+            GameEvent::Seed { seed } => GameEvent::Seed { seed },
+            GameEvent::TickStart { tick } => GameEvent::TickStart { tick },
+            GameEvent::TickEnd { tick } => GameEvent::TickEnd { tick },
+            GameEvent::GameOver { tick, reason } => GameEvent::GameOver { tick, reason },
+            GameEvent::Ranking { ranking } => GameEvent::Ranking { ranking },
+            GameEvent::InitMap {
+                map_data,
+                map_height,
+                map_width,
+                bounded,
+            } => GameEvent::InitMap {
+                map_data: Cow::Owned(map_data.into_owned()),
+                map_height,
+                map_width,
+                bounded,
+            },
+            GameEvent::Move { pid, location } => GameEvent::Move { pid, location },
+            GameEvent::Attack {
+                attacker_pid,
+                defender_pid,
+            } => GameEvent::Attack {
+                attacker_pid,
+                defender_pid,
+            },
+            GameEvent::WallBuilt { pid, location } => GameEvent::WallBuilt { pid, location },
+            GameEvent::WallDamaged { pid, location, hp } => {
+                GameEvent::WallDamaged { pid, location, hp }
+            }
+            GameEvent::WallDestroyed { pid, location } => {
+                GameEvent::WallDestroyed { pid, location }
+            }
+            GameEvent::NewProcess {
+                uid,
+                ppid,
+                pid,
+                location,
+            } => GameEvent::NewProcess {
+                uid,
+                ppid,
+                pid,
+                location,
+            },
+            GameEvent::Renice { pid, new_nice } => GameEvent::Renice { pid, new_nice },
+            GameEvent::Kill { pid } => GameEvent::Kill { pid },
+            GameEvent::Detach { pid } => GameEvent::Detach { pid },
+            GameEvent::UserLeft { uid } => GameEvent::UserLeft { uid },
+            GameEvent::NewChallenge {
+                challenge_type,
+                difficulty,
+                location,
+            } => GameEvent::NewChallenge {
+                challenge_type: Cow::Owned(challenge_type.into_owned()),
+                difficulty,
+                location,
+            },
+            GameEvent::ChallengeSolved { pid, location } => {
+                GameEvent::ChallengeSolved { pid, location }
+            }
+            GameEvent::ScoreUpdate { uid, new_score } => GameEvent::ScoreUpdate { uid, new_score },
+            GameEvent::WalletUpdate { uid, new_wallet } => GameEvent::WalletUpdate {
+                uid,
+                new_wallet: Cow::Owned(new_wallet.into_owned()),
+            },
+        }
+    }
+}
+
+pub trait EventSink {
+    fn log_event(&self, seq: u64, event: &GameEvent);
+}
+
+#[derive(Clone)]
+pub struct EventLogger {
+    sink: Rc<dyn EventSink>,
+    count: Rc<Cell<u64>>,
+}
+
+impl EventLogger {
+    pub fn new(sink: Rc<dyn EventSink>) -> Self {
+        Self {
+            sink,
+            count: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn log_event(&self, event: GameEvent) {
+        let seq = self.count.get();
+        self.sink.log_event(seq, &event);
+        self.count.set(seq + 1);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.get()
+    }
+
+    pub fn set_sink(&mut self, sink: Rc<dyn EventSink>) {
+        self.sink = sink;
+    }
+}
+
+// Only the sequence number survives a snapshot, the sink has to be attached
+// again after loading.
+impl Serialize for EventLogger {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.count().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EventLogger {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let events = EventLogger::default();
+        events.count.set(u64::deserialize(deserializer)?);
+        Ok(events)
+    }
+}
+
+impl Default for EventLogger {
+    fn default() -> Self {
+        Self::new(Rc::new(NullSink))
+    }
+}
+
+pub struct NullSink;
+
+impl EventSink for NullSink {
+    fn log_event(&self, _seq: u64, _event: &GameEvent) {}
+}
+
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn log_event(&self, seq: u64, event: &GameEvent) {
+        let event = SequencedEvent { seq, event };
+        println!("EVENT|{}", serde_json::to_string(&event).unwrap());
+    }
+}
+
+// Events are stored in the order they were logged, so an event's index is
+// its sequence number as long as the sink was attached from the start.
+#[derive(Default)]
+pub struct MemorySink {
+    events: RefCell<Vec<OwnedGameEvent>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<OwnedGameEvent> {
+        self.events.borrow().clone()
+    }
+
+    pub fn take_events(&self) -> Vec<OwnedGameEvent> {
+        self.events.take()
+    }
+}
+
+impl EventSink for MemorySink {
+    fn log_event(&self, _seq: u64, event: &GameEvent) {
+        self.events.borrow_mut().push(event.clone().into_owned());
+    }
+}
+
+pub struct JsonLinesSink {
+    writer: RefCell<BufWriter<File>>,
+}
+
+impl JsonLinesSink {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            writer: RefCell::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.borrow_mut().flush()
+    }
+}
+
+impl EventSink for JsonLinesSink {
+    fn log_event(&self, seq: u64, event: &GameEvent) {
+        let event = SequencedEvent { seq, event };
+        let mut writer = self.writer.borrow_mut();
+        serde_json::to_writer(&mut *writer, &event).expect("Failed to write event");
+        writeln!(writer).expect("Failed to write event");
+    }
 }
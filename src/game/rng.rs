@@ -0,0 +1,15 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use sha2::{Digest, Sha256};
+
+pub type GameRng = ChaCha12Rng;
+
+// Every subsystem draws from its own stream so that adding a random draw in
+// one place does not shift the values seen everywhere else.
+pub fn derive_rng(seed: u64, stream: &str) -> GameRng {
+    let digest = Sha256::new()
+        .chain_update(seed.to_le_bytes())
+        .chain_update(stream.as_bytes())
+        .finalize();
+    GameRng::from_seed(digest.into())
+}
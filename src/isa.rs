@@ -0,0 +1,149 @@
+use crate::kernel::syscall::{Syscall, SYSCALL_TABLE};
+use crate::vm::instructions::{Instruction, Operand, OPCODE_TABLE};
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Serialize)]
+pub struct InstructionInfo {
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub operands: &'static [Operand],
+    pub latency: usize,
+    pub description: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct SyscallInfo {
+    pub number: u8,
+    pub name: &'static str,
+    pub args: &'static [&'static str],
+    // `None` if the cost depends on the arguments listed in `cost_args`.
+    pub cost: Option<BTreeMap<&'static str, i64>>,
+    pub cost_args: Vec<&'static str>,
+    pub description: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct IsaInfo {
+    pub instructions: Vec<InstructionInfo>,
+    pub syscalls: Vec<SyscallInfo>,
+}
+
+impl InstructionInfo {
+    fn new(instruction: &dyn Instruction) -> Self {
+        Self {
+            opcode: instruction.get_opcode(),
+            mnemonic: instruction.get_mnemonic(),
+            operands: instruction.get_operands(),
+            latency: instruction.get_latency(),
+            description: instruction.get_description(),
+        }
+    }
+}
+
+impl SyscallInfo {
+    fn new(syscall: &dyn Syscall) -> Self {
+        // Arguments that do not affect the cost are prefixed with `_`.
+        let cost_args: Vec<_> = syscall
+            .get_cost_arg_names()
+            .iter()
+            .copied()
+            .filter(|name| !name.starts_with('_'))
+            .collect();
+        let cost = cost_args.is_empty().then(|| {
+            syscall
+                .compute_cost((0, 0, 0, 0, 0, 0))
+                .iter_nonzero()
+                .collect()
+        });
+        Self {
+            number: syscall.get_number(),
+            name: syscall.get_name(),
+            args: syscall.get_arg_names(),
+            cost,
+            cost_args,
+            description: syscall.get_description(),
+        }
+    }
+}
+
+pub fn describe() -> IsaInfo {
+    IsaInfo {
+        instructions: OPCODE_TABLE
+            .instructions()
+            .map(InstructionInfo::new)
+            .collect(),
+        syscalls: SYSCALL_TABLE.syscalls().map(SyscallInfo::new).collect(),
+    }
+}
+
+pub fn to_json() -> String {
+    serde_json::to_string_pretty(&describe()).unwrap()
+}
+
+pub fn to_markdown() -> String {
+    let isa = describe();
+    let mut output = String::new();
+
+    writeln!(output, "## Instructions\n").unwrap();
+    writeln!(output, "|Opcode|Mnemonic|Operands|Latency|Description|").unwrap();
+    writeln!(output, "|------|--------|--------|-------|-----------|").unwrap();
+    for instruction in &isa.instructions {
+        let operands: Vec<_> = instruction
+            .operands
+            .iter()
+            .map(Operand::to_string)
+            .collect();
+        writeln!(
+            output,
+            "|0x{:02x}|`{}`|{}|{}|{}|",
+            instruction.opcode,
+            instruction.mnemonic,
+            operands.join(", "),
+            instruction.latency,
+            escape_cell(instruction.description)
+        )
+        .unwrap();
+    }
+
+    writeln!(output, "\n## System Calls\n").unwrap();
+    writeln!(output, "|Number|Name|Arguments|Cost|Description|").unwrap();
+    writeln!(output, "|------|----|---------|----|-----------|").unwrap();
+    for syscall in &isa.syscalls {
+        let cost = match &syscall.cost {
+            Some(cost) if cost.is_empty() => "Free".to_string(),
+            Some(cost) => {
+                let amounts: Vec<_> = cost
+                    .iter()
+                    .map(|(currency, amount)| format!("{} {}", amount, currency))
+                    .collect();
+                amounts.join(", ")
+            }
+            None => {
+                let args: Vec<_> = syscall
+                    .cost_args
+                    .iter()
+                    .map(|arg| format!("`{}`", arg))
+                    .collect();
+                format!("Depends on {}", args.join(", "))
+            }
+        };
+        writeln!(
+            output,
+            "|0x{:02x}|{}|{}|{}|{}|",
+            syscall.number,
+            syscall.name,
+            syscall.args.join(", "),
+            cost,
+            escape_cell(syscall.description)
+        )
+        .unwrap();
+    }
+    output
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
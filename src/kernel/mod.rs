@@ -1,36 +1,137 @@
-use crate::config::{KernelConfiguration, UserConfiguration};
+use crate::config::{ConfigIoError, KernelConfiguration, UserConfiguration};
 use crate::game::crypto::Wallet;
-use crate::game::map::{chebyshev_distance, GameMap, Location, MapCell};
-use crate::game::replay::{log_event, GameEvent};
+use crate::game::map::{GameMap, Location, MapCell, Path};
+use crate::game::replay::{EventLogger, EventSink, GameEndReason, GameEvent, RankingEntry};
+use crate::game::rng::{derive_rng, GameRng};
 use crate::kernel::process::Process;
 use crate::kernel::user::User;
-use crate::vm::emulator::Emulator;
+use crate::vm::emulator::{Emulator, ExecutionObserver};
 
 use rand::Rng;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::rc::Rc;
 
 pub mod process;
 pub mod syscall;
 pub mod user;
 
+pub struct TickSummary {
+    pub tick: u64,
+    pub events_emitted: u64,
+    pub alive_processes: BTreeMap<u16, usize>,
+    pub game_over: bool,
+    pub end_reason: Option<GameEndReason>,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Kernel {
     config: KernelConfiguration,
     process_table: HashMap<u16, Process>,
-    user_table: HashMap<u16, User>,
+    user_table: BTreeMap<u16, User>,
     game_map: GameMap,
+    rng: GameRng,
+    emulator_seeds: GameRng,
+    events: EventLogger,
+    ticks_elapsed: u64,
+    #[serde(skip)]
+    observers: Vec<Box<dyn ExecutionObserver>>,
 }
 
 impl Kernel {
-    pub fn new(kernel_config: KernelConfiguration, game_map: GameMap) -> Self {
+    pub fn new(
+        kernel_config: KernelConfiguration,
+        mut game_map: GameMap,
+        event_sink: Rc<dyn EventSink>,
+    ) -> Self {
+        let events = EventLogger::new(event_sink);
+        game_map.reseed(kernel_config.seed);
+        game_map.set_event_logger(events.clone());
+        events.log_event(GameEvent::Seed {
+            seed: kernel_config.seed,
+        });
+        let geometry = game_map.geometry();
+        events.log_event(GameEvent::InitMap {
+            map_data: Cow::Owned(game_map.to_map_data()),
+            map_width: geometry.width,
+            map_height: geometry.height,
+            bounded: geometry.bounded,
+        });
         Kernel {
             process_table: HashMap::new(),
-            user_table: HashMap::new(),
+            user_table: BTreeMap::new(),
             game_map,
+            rng: derive_rng(kernel_config.seed, "kernel"),
+            emulator_seeds: derive_rng(kernel_config.seed, "emulator"),
+            events,
+            ticks_elapsed: 0,
+            observers: Vec::new(),
             config: kernel_config,
         }
     }
 
-    pub fn setup_users(&mut self, user_configs: Vec<UserConfiguration>) {
+    pub fn save_snapshot(&self, filename: &str) -> Result<(), ConfigIoError> {
+        Ok(serde_json::to_writer(
+            BufWriter::new(File::create(filename)?),
+            self,
+        )?)
+    }
+
+    pub fn load_snapshot(
+        filename: &str,
+        event_sink: Rc<dyn EventSink>,
+    ) -> Result<Self, ConfigIoError> {
+        let mut kernel: Kernel = serde_json::from_reader(BufReader::new(File::open(filename)?))?;
+        kernel.game_map.rebuild_indexes();
+        kernel.set_event_sink(event_sink);
+        Ok(kernel)
+    }
+
+    pub fn set_event_sink(&mut self, event_sink: Rc<dyn EventSink>) {
+        self.events.set_sink(event_sink);
+        self.game_map.set_event_logger(self.events.clone());
+    }
+
+    pub fn add_observer(&mut self, mut observer: Box<dyn ExecutionObserver>) {
+        let mut pids: Vec<_> = self.process_table.keys().copied().collect();
+        pids.sort();
+        for pid in pids {
+            observer.process_created(pid, self.get_process_owner(pid));
+        }
+        self.observers.push(observer);
+    }
+
+    fn notify_process_created(&mut self, pid: u16, uid: u16) {
+        for observer in self.observers.iter_mut() {
+            observer.process_created(pid, uid);
+        }
+    }
+
+    pub fn log_event(&self, event: GameEvent) {
+        self.events.log_event(event);
+    }
+
+    pub fn setup_users(
+        &mut self,
+        user_configs: Vec<UserConfiguration>,
+    ) -> Result<(), ConfigIoError> {
+        let mut spawn_points = HashSet::new();
+        for user_config in &user_configs {
+            let spawn_point = user_config.spawn_point;
+            if self.game_map.get_process_at(spawn_point).is_some()
+                || !spawn_points.insert(spawn_point)
+            {
+                return Err(ConfigIoError {
+                    message: format!(
+                        "Spawn point {:?} of user {} is already taken",
+                        spawn_point, user_config.uid
+                    ),
+                });
+            }
+        }
         for UserConfiguration {
             initd_memory,
             initd_bytecode,
@@ -46,11 +147,14 @@ impl Kernel {
                 uid,
                 lifetime: self.config.initd_lifetime,
                 nice: self.config.default_nice,
-                emulator: Emulator::new(initd_memory, initd_bytecode),
+                emulator: Emulator::new(initd_memory, initd_bytecode, self.emulator_seeds.gen()),
+                next_build_tick: 0,
+                next_dig_tick: 0,
             };
             self.process_table.insert(pid, initd_process);
+            self.notify_process_created(pid, uid);
             self.game_map.add_process_to_map(pid, spawn_point);
-            log_event(GameEvent::NewProcess {
+            self.log_event(GameEvent::NewProcess {
                 uid,
                 ppid: None,
                 pid,
@@ -65,6 +169,7 @@ impl Kernel {
             };
             self.user_table.insert(uid, user);
         }
+        Ok(())
     }
 
     fn run_process_tick(&mut self, pid: u16) {
@@ -77,15 +182,23 @@ impl Kernel {
         let user = self.get_owner_user_mut(pid);
         let mut cycle_count = user.compute_sleep_debt(cycle_count);
         user.score += cycle_count as i64 / 100;
-        log_event(GameEvent::ScoreUpdate {
-            uid: user.uid,
-            new_score: user.score,
-        });
+        let (uid, new_score) = (user.uid, user.score);
+        self.log_event(GameEvent::ScoreUpdate { uid, new_score });
+        let observed = self.observers.iter().any(|observer| observer.observes(pid));
         loop {
             let Some(process) = self.process_table.get_mut(&pid) else {
                 return;
             };
-            match process.emulator.run_until_interrupt(&mut cycle_count) {
+            let interrupt = if observed {
+                process.emulator.run_until_interrupt_observed(
+                    pid,
+                    &mut cycle_count,
+                    &mut self.observers,
+                )
+            } else {
+                process.emulator.run_until_interrupt(&mut cycle_count)
+            };
+            match interrupt {
                 Some(syscall) => {
                     let args = process.emulator.get_syscall_args();
                     let cost = syscall.compute_cost(args);
@@ -99,9 +212,9 @@ impl Kernel {
                     match syscall.call(self, pid, args) {
                         Some(ret) => {
                             self.get_user_mut(uid).wallet -= &cost;
-                            log_event(GameEvent::WalletUpdate {
+                            self.log_event(GameEvent::WalletUpdate {
                                 uid,
-                                new_wallet: &self.get_user(uid).wallet,
+                                new_wallet: Cow::Borrowed(&self.get_user(uid).wallet),
                             });
                             let Some(process) = self.process_table.get_mut(&pid) else {
                                 return;
@@ -143,14 +256,139 @@ impl Kernel {
         }
     }
 
-    pub fn run_full_game(&mut self) {
-        while !self.process_table.is_empty() {
-            self.game_map.tick(&self.config.crypto_spawn);
-            self.tick_processes();
+    pub fn step(&mut self) -> TickSummary {
+        let events_before = self.events.count();
+        let tick = self.ticks_elapsed;
+        self.log_event(GameEvent::TickStart { tick });
+        self.game_map.tick(&self.config.crypto_spawn);
+        self.tick_processes();
+        self.log_event(GameEvent::TickEnd { tick });
+        self.ticks_elapsed += 1;
+        if cfg!(debug_assertions) {
+            self.check_invariants();
+        }
+        TickSummary {
+            tick,
+            events_emitted: self.events.count() - events_before,
+            alive_processes: self
+                .user_table
+                .values()
+                .map(|user| (user.uid, user.num_processes))
+                .collect(),
+            game_over: self.is_game_over(),
+            end_reason: self.get_end_reason(),
+        }
+    }
+
+    // Panics if the map and the process table disagree about which processes
+    // exist or where they are, or the map has lost track of its challenges.
+    pub fn check_invariants(&self) {
+        self.game_map.check_process_index();
+        self.game_map.check_challenge_index();
+        assert_eq!(
+            self.game_map.count_processes(),
+            self.process_table.len(),
+            "the map and the process table hold different processes"
+        );
+        for &pid in self.process_table.keys() {
+            assert!(
+                self.game_map.has_process(pid),
+                "process {} is not on the map",
+                pid
+            );
+        }
+    }
+
+    pub fn get_end_reason(&self) -> Option<GameEndReason> {
+        let users_alive = self
+            .user_table
+            .values()
+            .filter(|user| user.initd_pid.is_some())
+            .count();
+        if self.process_table.is_empty() {
+            Some(GameEndReason::AllUsersLeft)
+        } else if self.config.last_user_standing && self.user_table.len() > 1 && users_alive <= 1 {
+            Some(GameEndReason::LastUserStanding)
+        } else if self
+            .config
+            .score_threshold
+            .is_some_and(|threshold| self.user_table.values().any(|user| user.score >= threshold))
+        {
+            Some(GameEndReason::ScoreThreshold)
+        } else if self
+            .config
+            .max_ticks
+            .is_some_and(|max_ticks| self.ticks_elapsed >= max_ticks)
+        {
+            Some(GameEndReason::MaxTicks)
+        } else {
+            None
         }
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.get_end_reason().is_some()
+    }
+
+    pub fn get_ticks_elapsed(&self) -> u64 {
+        self.ticks_elapsed
+    }
+
+    pub fn finish(&mut self) -> Vec<RankingEntry> {
+        self.log_event(GameEvent::GameOver {
+            tick: self.ticks_elapsed,
+            reason: self.get_end_reason().unwrap_or(GameEndReason::Stopped),
+        });
+        let mut leftover_wallets = vec![];
         for user in self.user_table.values_mut() {
+            leftover_wallets.push((user.uid, user.wallet.clone()));
             user.convert_wallet_to_score();
+            self.events.log_event(GameEvent::WalletUpdate {
+                uid: user.uid,
+                new_wallet: Cow::Borrowed(&user.wallet),
+            });
+            self.events.log_event(GameEvent::ScoreUpdate {
+                uid: user.uid,
+                new_score: user.score,
+            });
+        }
+        let mut ranking = leftover_wallets
+            .into_iter()
+            .map(|(uid, wallet)| RankingEntry {
+                rank: 0,
+                uid,
+                score: self.get_user(uid).score,
+                wallet,
+            })
+            .collect::<Vec<_>>();
+        ranking.sort_by(|entry1, entry2| {
+            entry2
+                .score
+                .cmp(&entry1.score)
+                .then(entry1.uid.cmp(&entry2.uid))
+        });
+        for idx in 0..ranking.len() {
+            ranking[idx].rank = if idx > 0 && ranking[idx - 1].score == ranking[idx].score {
+                ranking[idx - 1].rank
+            } else {
+                idx + 1
+            };
+        }
+        self.log_event(GameEvent::Ranking {
+            ranking: ranking.clone(),
+        });
+        ranking
+    }
+
+    pub fn run_full_game(&mut self) -> Vec<RankingEntry> {
+        while !self.is_game_over() {
+            self.step();
         }
+        self.finish()
+    }
+
+    pub fn rng(&mut self) -> &mut GameRng {
+        &mut self.rng
     }
 
     pub fn get_user(&self, uid: u16) -> &User {
@@ -161,6 +399,10 @@ impl Kernel {
         self.user_table.get_mut(&uid).unwrap()
     }
 
+    pub fn has_user(&self, uid: u16) -> bool {
+        self.user_table.contains_key(&uid)
+    }
+
     pub fn has_process(&self, pid: u16) -> bool {
         self.process_table.contains_key(&pid)
     }
@@ -189,10 +431,23 @@ impl Kernel {
         self.game_map.get_process_location(pid)
     }
 
+    pub fn get_game_map(&self) -> &GameMap {
+        &self.game_map
+    }
+
     pub fn get_map_cell(&self, location: Location) -> &MapCell {
         self.game_map.get_cell(location)
     }
 
+    // Where the coordinates passed to a syscall point on the map. They are
+    // taken modulo 256, then wrapped around the map, or `None` if they lie
+    // outside of a bounded map.
+    pub fn locate(&self, x: u16, y: u16) -> Option<Location> {
+        self.game_map
+            .geometry()
+            .locate(x as u8 as i32, y as u8 as i32)
+    }
+
     pub fn remove_from_parent(&mut self, pid: u16) {
         let Some(ppid) = self.get_process(pid).ppid else {
             return;
@@ -202,10 +457,12 @@ impl Kernel {
     }
 
     fn kill_process(&mut self, pid: u16) {
-        log_event(GameEvent::Kill { pid });
+        self.log_event(GameEvent::Kill { pid });
         self.get_owner_user_mut(pid).num_processes -= 1;
         if self.get_process(pid).is_init() {
             self.get_owner_user_mut(pid).initd_pid = None;
+            let uid = self.get_process_owner(pid);
+            self.log_event(GameEvent::UserLeft { uid });
         }
         self.game_map.remove_process_from_map(pid);
         self.remove_from_parent(pid);
@@ -232,7 +489,7 @@ impl Kernel {
 
     pub fn move_process_to(&mut self, pid: u16, location: Location) -> bool {
         let old_location = self.game_map.get_process_location(pid);
-        if chebyshev_distance(old_location, location) != 1 {
+        if self.game_map.geometry().distance(old_location, location) != 1 {
             return false;
         }
         self.game_map.move_process_to(pid, location)
@@ -245,14 +502,44 @@ impl Kernel {
         self.game_map.move_process_to(pid, new_location)
     }
 
-    pub fn pathfind_process_to(
-        &mut self,
-        pid: u16,
-        location: Location,
-        max_len: usize,
-    ) -> Option<Vec<Location>> {
+    pub fn damage_wall(&mut self, pid: u16, location: Location) -> bool {
+        self.game_map.damage_wall(pid, location)
+    }
+
+    pub fn build_wall(&mut self, pid: u16, location: Location) -> bool {
+        let old_location = self.game_map.get_process_location(pid);
+        if self.game_map.geometry().distance(old_location, location) != 1 {
+            return false;
+        }
+        self.game_map.build_wall(pid, location)
+    }
+
+    pub fn dig_wall(&mut self, pid: u16, location: Location) -> bool {
+        let old_location = self.game_map.get_process_location(pid);
+        if self.game_map.geometry().distance(old_location, location) != 1 {
+            return false;
+        }
+        self.game_map.dig_wall(pid, location)
+    }
+
+    pub fn pathfind_process_to(&self, pid: u16, location: Location, max_cost: u32) -> Option<Path> {
         let old_location = self.game_map.get_process_location(pid);
-        self.game_map.pathfind(old_location, location, max_len)
+        self.game_map.pathfind(old_location, location, max_cost)
+    }
+
+    pub fn find_nearest_challenge(&self, pid: u16, id: u16, radius: u16) -> Option<Location> {
+        let location = self.game_map.get_process_location(pid);
+        self.game_map.find_nearest_challenge(location, id, radius)
+    }
+
+    // The nearest process of another user.
+    pub fn find_nearest_enemy(&self, pid: u16, radius: u16) -> Option<Location> {
+        let location = self.game_map.get_process_location(pid);
+        let uid = self.get_process_owner(pid);
+        self.game_map
+            .find_nearest_process(location, radius, |other| {
+                self.get_process_owner(other) != uid
+            })
     }
 
     pub fn fetch_challenge_data(&self, pid: u16) -> Option<Vec<u8>> {
@@ -262,13 +549,13 @@ impl Kernel {
 
     pub fn solve_challenge(&mut self, pid: u16, nonce: (u16, u16, u16, u16)) -> u16 {
         let location = self.game_map.get_process_location(pid);
-        if let Some(wallet) = self.game_map.get_cell_mut(location).solve_crypto(nonce) {
-            let user = self.get_owner_user_mut(pid);
-            user.wallet += &wallet;
-            log_event(GameEvent::ChallengeSolved { pid, location });
-            log_event(GameEvent::WalletUpdate {
+        if let Some(wallet) = self.game_map.solve_challenge(location, nonce) {
+            self.get_owner_user_mut(pid).wallet += &wallet;
+            let user = self.get_owner_user(pid);
+            self.log_event(GameEvent::ChallengeSolved { pid, location });
+            self.log_event(GameEvent::WalletUpdate {
                 uid: user.uid,
-                new_wallet: &user.wallet,
+                new_wallet: Cow::Borrowed(&user.wallet),
             });
             1
         } else {
@@ -277,10 +564,9 @@ impl Kernel {
         }
     }
 
-    fn allocate_pid(&self) -> u16 {
-        let mut rng = rand::thread_rng();
+    fn allocate_pid(&mut self) -> u16 {
         loop {
-            let random_pid = rng.gen_range(1..=65534);
+            let random_pid = self.rng.gen_range(1..=65534);
             if !self.process_table.contains_key(&random_pid) {
                 break random_pid;
             }
@@ -293,12 +579,12 @@ impl Kernel {
         {
             return None;
         }
-        let child_location = self.game_map.find_empty_location_nearby(
-            self.game_map.get_process_location(pid),
-            5,
-            2,
-        )?;
+        let parent_location = self.game_map.get_process_location(pid);
+        let child_location = self
+            .game_map
+            .find_empty_location_nearby(parent_location, 5, 2)?;
         let child_pid = self.allocate_pid();
+        let child_seed = self.emulator_seeds.gen();
         self.game_map.add_process_to_map(child_pid, child_location);
         let parent_process = self.get_process_mut(pid);
         let half_lifetime = parent_process.lifetime / 2;
@@ -314,14 +600,18 @@ impl Kernel {
             lifetime: half_lifetime,
             nice: 0,
             emulator: parent_process.emulator.clone(),
+            next_build_tick: parent_process.next_build_tick,
+            next_dig_tick: parent_process.next_dig_tick,
         };
-        log_event(GameEvent::NewProcess {
-            uid: parent_process.uid,
+        self.log_event(GameEvent::NewProcess {
+            uid: child_process.uid,
             ppid: Some(pid),
             pid: child_pid,
             location: (child_location.0, child_location.1),
         });
+        child_process.emulator.reseed(child_seed);
         child_process.emulator.set_syscall_return_value(0xffff);
+        self.notify_process_created(child_pid, child_process.uid);
         self.process_table.insert(child_pid, child_process);
         self.get_user_mut(self.get_process_owner(pid)).num_processes += 1;
         Some(child_pid)
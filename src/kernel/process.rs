@@ -1,6 +1,8 @@
-use crate::game::replay::{log_event, GameEvent};
 use crate::vm::emulator;
 
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
 pub struct Process {
     pub pid: u16,
     pub ppid: Option<u16>,
@@ -9,6 +11,12 @@ pub struct Process {
     pub lifetime: u32,
     pub nice: u16,
     pub emulator: emulator::Emulator,
+    // The first ticks at which the process may `Build` and `Dig` again. Forked
+    // children inherit them, so forking does not skip a cooldown.
+    #[serde(default)]
+    pub next_build_tick: u64,
+    #[serde(default)]
+    pub next_dig_tick: u64,
 }
 
 impl Process {
@@ -18,10 +26,6 @@ impl Process {
 
     pub fn renice(&mut self) {
         self.nice = self.nice.saturating_add(1);
-        log_event(GameEvent::Renice {
-            pid: self.pid,
-            new_nice: self.nice,
-        });
     }
 
     pub fn get_execution_limit(&self) -> usize {
@@ -1,14 +1,25 @@
-use rand::random;
+use rand::Rng;
 
 use crate::game::crypto::{wallet, Wallet};
-use crate::game::map::chebyshev_distance;
-use crate::game::replay::{log_event, GameEvent};
+use crate::game::replay::GameEvent;
 use crate::kernel::Kernel;
 
 pub type SyscallArgs = (u16, u16, u16, u16, u16, u16);
 
+// How far non-init processes can see processes and challenges on the map. Init
+// sees the whole map.
+pub const SIGHT_RADIUS: u16 = 4;
+
+// The number of ticks a process must wait between two `Build`s or two `Dig`s.
+pub const BUILD_COOLDOWN: u64 = 16;
+pub const DIG_COOLDOWN: u64 = 16;
+
 pub trait Syscall {
     fn get_number(&self) -> u8;
+    fn get_name(&self) -> &'static str;
+    fn get_description(&self) -> &'static str;
+    fn get_arg_names(&self) -> &'static [&'static str];
+    fn get_cost_arg_names(&self) -> &'static [&'static str];
     fn compute_cost(&self, args: SyscallArgs) -> Wallet;
     fn call(&self, kernel: &mut Kernel, pid: u16, args: SyscallArgs) -> Option<u16>;
 }
@@ -29,21 +40,45 @@ impl SyscallTable {
     pub fn get_syscall(&self, number: u8) -> &'static dyn Syscall {
         self.table[number as usize]
     }
+    pub fn syscalls(&self) -> impl Iterator<Item = &'static dyn Syscall> + '_ {
+        self.table
+            .iter()
+            .zip(self._used.iter())
+            .filter(|(_, &used)| used)
+            .map(|(&syscall, _)| syscall)
+    }
+    pub fn get_syscall_by_name(&self, name: &str) -> Option<&'static dyn Syscall> {
+        self.syscalls()
+            .find(|syscall| syscall.get_name().eq_ignore_ascii_case(name))
+    }
 }
 
 macro_rules! syscall_category {
     ($category_fn:ident() => [
-        $($name:ident<$number:literal> {
+        $($(#[doc = $doc:literal])* $name:ident<$number:literal> {
             compute_cost($($cost_args:ident),*) $cost:tt
             call($kernel:ident, $pid:ident $(,$call_args:ident)*) $call:tt
         }),* $(,)?
     ]) => {
         $(
+            $(#[doc = $doc])*
             struct $name {}
             impl Syscall for $name {
                 fn get_number(&self) -> u8 {
                     $number
                 }
+                fn get_name(&self) -> &'static str {
+                    stringify!($name)
+                }
+                fn get_description(&self) -> &'static str {
+                    concat!($($doc),*).trim()
+                }
+                fn get_arg_names(&self) -> &'static [&'static str] {
+                    &[$(stringify!($call_args)),*]
+                }
+                fn get_cost_arg_names(&self) -> &'static [&'static str] {
+                    &[$(stringify!($cost_args)),*]
+                }
                 fn compute_cost(&self, ($($cost_args,)* ..): SyscallArgs) -> Wallet {
                     $cost
                 }
@@ -79,6 +114,7 @@ pub const SYSCALL_TABLE: SyscallTable = SyscallTable::new()
 
 syscall_category! {
     make_process_syscalls() => [
+        /// Returns the PID of the calling process.
         GetPid<0x00> {
             compute_cost() {
                 wallet!()
@@ -88,6 +124,7 @@ syscall_category! {
             }
         },
 
+        /// Returns the UID of the owner of a process.
         GetUidOf<0x01> {
             compute_cost() {
                 wallet!()
@@ -100,6 +137,7 @@ syscall_category! {
             }
         },
 
+        /// Creates a copy of the calling process nearby, which gets half of its lifetime.
         Fork<0x02> {
             compute_cost() {
                 wallet!(Ethereum: 4)
@@ -109,6 +147,7 @@ syscall_category! {
             }
         },
 
+        /// Kills the calling process or one of its descendants, along with all their descendants.
         Kill<0x03> {
             compute_cost() {
                 wallet!(Ethereum: 2)
@@ -125,6 +164,7 @@ syscall_category! {
             }
         },
 
+        /// Writes information about a process to memory.
         GetProcessInfo<0x04> {
             compute_cost() {
                 wallet!()
@@ -152,6 +192,7 @@ syscall_category! {
             }
         },
 
+        /// Changes the parent of the calling process to init.
         Detach<0x05> {
             compute_cost() {
                 wallet!(Ethereum: 1)
@@ -160,7 +201,7 @@ syscall_category! {
                 if kernel.get_process(pid).is_init() {
                     return None;
                 }
-                log_event(GameEvent::Detach { pid });
+                kernel.log_event(GameEvent::Detach { pid });
                 let init_pid = kernel.get_owner_user(pid).initd_pid.unwrap();
                 kernel.remove_from_parent(pid);
                 kernel.get_process_mut(init_pid).children.push(pid);
@@ -169,20 +210,47 @@ syscall_category! {
             }
         },
 
+        /// Increases the nice value of the calling process by 1.
         Renice<0x06> {
             compute_cost() {
                 wallet!(Ethereum: 10)
             }
             call(kernel, pid) {
-                kernel.get_process_mut(pid).renice();
+                let process = kernel.get_process_mut(pid);
+                process.renice();
+                let new_nice = process.nice;
+                kernel.log_event(GameEvent::Renice { pid, new_nice });
                 Some(1)
             }
         },
     ]
 }
 
+// Searching costs as much as reading the details of the square it covers.
+fn search_cost(radius: u16) -> i64 {
+    let side = 2 * radius as i64 + 1;
+    (side * side).div_ceil(64)
+}
+
+// Non-init processes only find what they can see, like with `ReadMapDetail`.
+fn visible_radius(kernel: &Kernel, pid: u16, radius: u16) -> u16 {
+    if kernel.get_process(pid).is_init() {
+        radius
+    } else {
+        radius.min(SIGHT_RADIUS)
+    }
+}
+
+// Only the cells within the visible radius are charged for, out of the
+// `radius` paid.
+fn refund_unsearched(kernel: &mut Kernel, pid: u16, radius: u16, searched: u16) {
+    let refund = wallet!(DogeCoin: search_cost(radius) - search_cost(searched));
+    kernel.get_owner_user_mut(pid).wallet += &refund;
+}
+
 syscall_category! {
     make_game_syscalls() => [
+        /// Moves the calling process within a 3x3 square.
         Move<0x10> {
             compute_cost() {
                 wallet!(DogeCoin: 1)
@@ -191,15 +259,23 @@ syscall_category! {
                 if kernel.get_process(pid).is_init() {
                     return None;
                 }
-                let location = (x as u8, y as u8);
-                if kernel.move_process_to(pid, location) {
-                    Some(1)
-                } else {
-                    None
+                let location = kernel.locate(x, y)?;
+                // Entering water costs extra, which must be affordable along with the move.
+                let extra_cost = kernel.get_map_cell(location).get_extra_move_cost();
+                let extra = wallet!(DogeCoin: extra_cost as i64);
+                let total = wallet!(DogeCoin: 1 + extra_cost as i64);
+                if !kernel.get_owner_user(pid).wallet.can_afford(&total) {
+                    return None;
+                }
+                if !kernel.move_process_to(pid, location) {
+                    return None;
                 }
+                kernel.get_owner_user_mut(pid).wallet -= &extra;
+                Some(1)
             }
         },
 
+        /// Writes the cell types of a rectangle of the map to memory.
         ReadMap<0x11> {
             compute_cost(_addr, x1, y1, x2, y2) {
                 let x1 = x1 as u8;
@@ -217,13 +293,18 @@ syscall_category! {
                 let y2 = y2 as u8;
                 let dx = x2.wrapping_sub(x1);
                 let dy = y2.wrapping_sub(y1);
+                let geometry = kernel.get_game_map().geometry();
                 let mut data = vec![];
                 for i in 0..=dx {
                     for j in 0..=dy {
-                        let x = x1.wrapping_add(i);
-                        let y = y1.wrapping_add(j);
-                        let cell = kernel.get_map_cell((x, y));
-                        data.push(cell.status());
+                        let x = x1 as i32 + i as i32;
+                        let y = y1 as i32 + j as i32;
+                        // Cells beyond the borders of a bounded map read as walls.
+                        let status = match geometry.locate(x, y) {
+                            Some(location) => kernel.get_map_cell(location).status(),
+                            None => 1,
+                        };
+                        data.push(status);
                     }
                 }
                 kernel.get_process_mut(pid).emulator.write_bytes_to_mem(addr, &data);
@@ -231,6 +312,7 @@ syscall_category! {
             }
         },
 
+        /// Writes the cell types, challenges and processes of a rectangle of the map to memory.
         ReadMapDetail<0x12> {
             compute_cost(_addr, x1, y1, x2, y2) {
                 let x1 = x1 as u8;
@@ -253,16 +335,25 @@ syscall_category! {
                 }
                 let location = kernel.get_process_location(pid);
                 let is_init = kernel.get_process(pid).is_init();
+                let geometry = kernel.get_game_map().geometry();
+                let (px, py) = (location.0 as i32, location.1 as i32);
                 let mut data = vec![];
                 for i in 0..=dx {
                     for j in 0..=dy {
-                        let x = x1.wrapping_add(i);
-                        let y = y1.wrapping_add(j);
-                        if !is_init && chebyshev_distance(location, (x, y)) <= 4 {
-                            let cell = kernel.get_map_cell((x, y));
-                            data.extend(cell.status_detail());
+                        let x = x1 as i32 + i as i32;
+                        let y = y1 as i32 + j as i32;
+                        let target = geometry.locate(x, y);
+                        let distance = match target {
+                            Some(target) => geometry.distance(location, target) as i32,
+                            None => (x - px).abs().max((y - py).abs()),
+                        };
+                        if is_init || distance <= SIGHT_RADIUS as i32 {
+                            // Cells beyond the borders of a bounded map read as walls.
+                            data.extend(target.map_or([1, 0, 0], |target| {
+                                kernel.get_map_cell(target).status_detail()
+                            }));
                         } else {
-                            data.extend(random::<[u8; 3]>());
+                            data.extend(kernel.rng().gen::<[u8; 3]>());
                         }
                     }
                 }
@@ -271,6 +362,7 @@ syscall_category! {
             }
         },
 
+        /// Writes the crypto challenge at the location of the calling process to memory.
         FetchChallenge<0x13> {
             compute_cost() {
                 wallet!()
@@ -285,6 +377,7 @@ syscall_category! {
             }
         },
 
+        /// Submits a solution to the crypto challenge at the location of the calling process.
         SolveChallenge<0x14> {
             compute_cost() {
                 wallet!()
@@ -295,22 +388,24 @@ syscall_category! {
             }
         },
 
+        /// Takes 1 lifetime from a process, or 1 hit point from a wall, within a 9x9 square.
         Attack1<0x20> {
             compute_cost() {
                 wallet!(DogeCoin: 8)
             }
             call(kernel, pid, x, y) {
                 let attacker_location = kernel.get_process_location(pid);
-                let target_location = (x as u8, y as u8);
-                if chebyshev_distance(attacker_location, target_location) > 4 {
+                let target_location = kernel.locate(x, y)?;
+                let geometry = kernel.get_game_map().geometry();
+                if geometry.distance(attacker_location, target_location) > 4 {
                     return None;
                 }
                 let Some(target_pid) = kernel.get_map_cell(target_location).get_process() else {
-                    return None;
+                    return kernel.damage_wall(pid, target_location).then_some(1);
                 };
                 let target_process = kernel.get_process_mut(target_pid);
                 target_process.lifetime = target_process.lifetime.saturating_sub(1);
-                log_event(GameEvent::Attack {
+                kernel.log_event(GameEvent::Attack {
                     attacker_pid:pid,
                     defender_pid: target_pid,
                 });
@@ -318,14 +413,16 @@ syscall_category! {
             }
         },
 
+        /// Makes the process at a location within a 5x5 square execute undefined behavior.
         Attack2<0x21> {
             compute_cost() {
                 wallet!(DogeCoin: 16)
             }
             call(kernel, pid, x, y) {
                 let attacker_location = kernel.get_process_location(pid);
-                let target_location = (x as u8, y as u8);
-                if chebyshev_distance(attacker_location, target_location) > 2 {
+                let target_location = kernel.locate(x, y)?;
+                let geometry = kernel.get_game_map().geometry();
+                if geometry.distance(attacker_location, target_location) > 2 {
                     return None;
                 }
                 let Some(target_pid) = kernel.get_map_cell(target_location).get_process() else {
@@ -333,7 +430,7 @@ syscall_category! {
                 };
                 let target_process = kernel.get_process_mut(target_pid);
                 target_process.emulator.nasal_demons();
-                log_event(GameEvent::Attack {
+                kernel.log_event(GameEvent::Attack {
                     attacker_pid: pid,
                     defender_pid: target_pid,
                 });
@@ -341,21 +438,93 @@ syscall_category! {
             }
         },
 
+        /// Finds a cheapest path to a location and writes it to memory.
         PathFind<0x15> {
             compute_cost(_addr, _x, _y, n) {
                 wallet!(DogeCoin: n as i64)
             }
             call(kernel, pid, addr, x, y, n) {
-                if n > 16 {
+                let location = kernel.locate(x, y)?;
+                let path = kernel.pathfind_process_to(pid, location, n as u32)?;
+                // Only the cost of the path is charged out of the `n` paid.
+                let refund = wallet!(DogeCoin: (n as u32 - path.cost) as i64);
+                kernel.get_owner_user_mut(pid).wallet += &refund;
+                let data = path.locations.iter().map(|&(x, y)| [x, y]).flatten().collect::<Vec<_>>();
+                kernel.get_process_mut(pid).emulator.write_bytes_to_mem(addr, &data);
+                Some(path.locations.len() as u16)
+            }
+        },
+
+        /// Finds the nearest challenge of a type within a radius and writes its location to memory.
+        FindChallenge<0x18> {
+            compute_cost(_addr, _id, radius) {
+                wallet!(DogeCoin: search_cost(radius))
+            }
+            call(kernel, pid, addr, id, radius) {
+                let searched = visible_radius(kernel, pid, radius);
+                let (x, y) = kernel.find_nearest_challenge(pid, id, searched)?;
+                refund_unsearched(kernel, pid, radius, searched);
+                kernel.get_process_mut(pid).emulator.write_bytes_to_mem(addr, &[x, y]);
+                Some(2)
+            }
+        },
+
+        /// Finds the nearest process of another user within a radius and writes it to memory.
+        FindEnemy<0x19> {
+            compute_cost(_addr, radius) {
+                wallet!(DogeCoin: search_cost(radius))
+            }
+            call(kernel, pid, addr, radius) {
+                let searched = visible_radius(kernel, pid, radius);
+                let (x, y) = kernel.find_nearest_enemy(pid, searched)?;
+                refund_unsearched(kernel, pid, radius, searched);
+                let target_pid = kernel.get_map_cell((x, y)).get_process().unwrap();
+                let [pid_lo, pid_hi] = target_pid.to_le_bytes();
+                let data = [x, y, pid_lo, pid_hi];
+                kernel.get_process_mut(pid).emulator.write_bytes_to_mem(addr, &data);
+                Some(4)
+            }
+        },
+
+        /// Builds a wall on an empty cell within a 3x3 square.
+        Build<0x16> {
+            compute_cost() {
+                wallet!(DogeCoin: 16, Ethereum: 1)
+            }
+            call(kernel, pid, x, y) {
+                let tick = kernel.get_ticks_elapsed();
+                if tick < kernel.get_process(pid).next_build_tick {
                     return None;
                 }
-                let path = kernel.pathfind_process_to(pid, (x as u8, y as u8), n as usize)?;
-                let data = path.iter().map(|&(x, y)| [x, y]).flatten().collect::<Vec<_>>();
-                kernel.get_process_mut(pid).emulator.write_bytes_to_mem(addr, &data);
-                Some(path.len() as u16)
+                let location = kernel.locate(x, y)?;
+                if !kernel.build_wall(pid, location) {
+                    return None;
+                }
+                kernel.get_process_mut(pid).next_build_tick = tick + BUILD_COOLDOWN;
+                Some(1)
+            }
+        },
+
+        /// Digs out a wall within a 3x3 square, leaving land.
+        Dig<0x17> {
+            compute_cost() {
+                wallet!(DogeCoin: 24, Ethereum: 2)
+            }
+            call(kernel, pid, x, y) {
+                let tick = kernel.get_ticks_elapsed();
+                if tick < kernel.get_process(pid).next_dig_tick {
+                    return None;
+                }
+                let location = kernel.locate(x, y)?;
+                if !kernel.dig_wall(pid, location) {
+                    return None;
+                }
+                kernel.get_process_mut(pid).next_dig_tick = tick + DIG_COOLDOWN;
+                Some(1)
             }
         },
 
+        /// Kills all non-init processes within a 15x15 square, once per user per game.
         エクスプロージョン<0x40> {
             compute_cost() {
                 wallet!(
@@ -367,14 +536,16 @@ syscall_category! {
                 if kernel.get_process(pid).is_init() {
                     return None;
                 }
-                let x = x as u8;
-                let y = y as u8;
+                let center = kernel.locate(x, y)?;
+                let geometry = kernel.get_game_map().geometry();
                 let mut ret = 0;
                 for i in -7..=7 {
                     for j in -7..=7 {
-                        let tx = x.wrapping_add_signed(i);
-                        let ty = y.wrapping_add_signed(j);
-                        let Some(target_pid) = kernel.get_map_cell((tx, ty)).get_process() else {
+                        let Some(target_location) = geometry.offset(center, i, j) else {
+                            continue;
+                        };
+                        let cell = kernel.get_map_cell(target_location);
+                        let Some(target_pid) = cell.get_process() else {
                             continue;
                         };
                         if kernel.get_process(target_pid).is_init() {
@@ -382,7 +553,7 @@ syscall_category! {
                         }
                         kernel.kill_process_recursive(target_pid);
                         ret += 1;
-                        log_event(GameEvent::Attack {
+                        kernel.log_event(GameEvent::Attack {
                             attacker_pid: pid,
                             defender_pid: target_pid,
                         });
@@ -392,6 +563,7 @@ syscall_category! {
             }
         },
 
+        /// Moves the calling process to a random empty cell near a process of the same user.
         Teleport<0x41> {
             compute_cost() {
                 wallet!(DogeCoin: 32)
@@ -415,6 +587,7 @@ syscall_category! {
 
 syscall_category! {
     make_misc_syscalls() => [
+        /// Copies memory into the bytecode of the calling process.
         UpdateCode<0x30> {
             compute_cost(_mem_addr, _code_addr, n) {
                 wallet!(Ethereum: n.div_ceil(1024) as _)
@@ -428,6 +601,7 @@ syscall_category! {
             }
         },
 
+        /// Copies memory of the calling process into the memory of a descendant.
         ShareMemory<0x31> {
             compute_cost(_target_pid, _dst_addr, _src_addr, n) {
                 wallet!(Ethereum: n.div_ceil(1024) as _)
@@ -448,6 +622,7 @@ syscall_category! {
             }
         },
 
+        /// Reserved for future use, calling it is undefined behavior.
         Reserved<0xff> {
             compute_cost() {
                 wallet!(StarSleepShortage: -10)
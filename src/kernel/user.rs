@@ -1,6 +1,8 @@
 use crate::game::crypto::{CryptoCurrency, Wallet};
-use crate::game::replay::{log_event, GameEvent};
 
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
 pub struct User {
     pub uid: u16,
     pub initd_pid: Option<u16>,
@@ -19,13 +21,5 @@ impl User {
         let score = self.wallet.convert_to_score();
         self.wallet = Wallet::default();
         self.score += score;
-        log_event(GameEvent::WalletUpdate {
-            uid: self.uid,
-            new_wallet: &self.wallet,
-        });
-        log_event(GameEvent::ScoreUpdate {
-            uid: self.uid,
-            new_score: self.score,
-        });
     }
 }
@@ -2,7 +2,14 @@
 #![feature(bigint_helper_methods)]
 #![feature(int_roundings)]
 
+pub mod asm;
 pub mod config;
+pub mod debugger;
+pub mod disasm;
 pub mod game;
+pub mod isa;
 pub mod kernel;
+pub mod profile;
+pub mod runner;
+pub mod trace;
 pub mod vm;
@@ -1,36 +1,456 @@
 use minecrab::config::GameConfiguration;
-use minecrab::game::map::{GameMap, MAP_HEIGHT, MAP_WIDTH};
-use minecrab::game::replay::{log_event, GameEvent};
-use minecrab::kernel;
+use minecrab::debugger::Debugger;
+use minecrab::disasm;
+use minecrab::game::map::{GameMap, MapGeometry, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::mapfile::{MapFile, MapFormat};
+use minecrab::game::mapgen::{self, MapGenOptions, MapStyle};
+use minecrab::game::replay::{EventSink, JsonLinesSink, NullSink, StdoutSink};
+use minecrab::isa;
+use minecrab::kernel::Kernel;
+use minecrab::profile::{Profiler, DEFAULT_REPORT_ROWS};
+use minecrab::runner::{BotRunner, MockSyscalls, DEFAULT_CYCLES_PER_TICK};
+use minecrab::trace::{Tracer, DEFAULT_TRACE_LIMIT};
+use minecrab::vm::emulator::{Emulator, ExecutionObserver, BYTECODE_SIZE, MEMORY_SIZE};
+use minecrab::vm::paged::PagedBytes;
 
-use clap::Parser;
+use clap::{value_parser, ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::exit;
+use std::rc::Rc;
 
 #[derive(Parser)]
-struct Args {
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    game: GameArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Disassemble a bytecode file or the code of a process in a snapshot
+    Disasm(DisasmArgs),
+    /// Export the instruction set and system call table
+    Isa(IsaArgs),
+    /// Step through a bot, either on its own or as a process in a game
+    Debug(DebugArgs),
+    /// Run a bot on its own with mocked syscalls and report its final state
+    Run(RunArgs),
+    /// Generate a map and a game configuration to go with it
+    Mapgen(MapgenArgs),
+    /// Convert a map file between the raw, ASCII and PGM formats
+    Mapconv(MapconvArgs),
+}
+
+#[derive(Args)]
+struct GameArgs {
+    #[arg(short, long, required_unless_present = "resume_path")]
+    config_path: Option<String>,
     #[arg(short, long)]
-    config_path: String,
+    seed: Option<u64>,
+    #[arg(short, long)]
+    events_path: Option<PathBuf>,
+    #[arg(long, conflicts_with_all = ["config_path", "seed"])]
+    resume_path: Option<String>,
+    #[arg(long, requires = "snapshot_interval")]
+    snapshot_path: Option<String>,
+    #[arg(long, requires = "snapshot_path", value_parser = value_parser!(u64).range(1..))]
+    snapshot_interval: Option<u64>,
+    #[arg(long)]
+    trace_path: Option<PathBuf>,
+    // Traces every process if not given.
+    #[arg(long, value_delimiter = ',', requires = "trace_path")]
+    trace_pids: Vec<u16>,
+    // Maximum size of the trace file in bytes.
+    #[arg(long, default_value_t = DEFAULT_TRACE_LIMIT, requires = "trace_path")]
+    trace_limit: u64,
+    // Writes a profile of every user's code here at the end of the game.
+    #[arg(long)]
+    profile_path: Option<PathBuf>,
 }
 
-fn main() {
-    let args = Args::parse();
+#[derive(Args)]
+struct DisasmArgs {
+    #[arg(required_unless_present = "snapshot_path")]
+    bytecode_path: Option<PathBuf>,
+    #[arg(long, conflicts_with = "bytecode_path", requires = "pid")]
+    snapshot_path: Option<String>,
+    #[arg(long, requires = "snapshot_path")]
+    pid: Option<u16>,
+    #[arg(long, value_parser = parse_address)]
+    start: Option<u16>,
+    #[arg(long, value_parser = parse_address)]
+    end: Option<u16>,
+}
 
-    let game_config =
-        GameConfiguration::load(&args.config_path).expect("Failed to load game config");
+#[derive(Args)]
+struct IsaArgs {
+    #[arg(short, long, value_enum, default_value_t = IsaFormat::Json)]
+    format: IsaFormat,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("source").required(true).args(["bytecode_path", "config_path", "resume_path"])))]
+struct DebugArgs {
+    #[arg(long, requires = "memory_path")]
+    bytecode_path: Option<PathBuf>,
+    #[arg(long, requires = "bytecode_path")]
+    memory_path: Option<PathBuf>,
+    // Cycles given to a standalone emulator every tick.
+    #[arg(long, default_value_t = 1000, requires = "bytecode_path")]
+    cycles: usize,
+    #[arg(short, long, conflicts_with_all = ["bytecode_path", "resume_path"])]
+    config_path: Option<String>,
+    #[arg(short, long, requires = "config_path")]
+    seed: Option<u64>,
+    #[arg(long, conflicts_with = "bytecode_path")]
+    resume_path: Option<String>,
+    #[arg(short, long, conflicts_with = "bytecode_path")]
+    events_path: Option<PathBuf>,
+    #[arg(long, conflicts_with = "bytecode_path", required_unless_present_any = ["bytecode_path", "uid"])]
+    pid: Option<u16>,
+    // Attaches to the init process of this user.
+    #[arg(long, conflicts_with_all = ["bytecode_path", "pid"])]
+    uid: Option<u16>,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    bytecode_path: String,
+    memory_path: String,
+    #[arg(short, long)]
+    ticks: u64,
+    #[arg(long, default_value_t = DEFAULT_CYCLES_PER_TICK)]
+    cycles: usize,
+    // Canned syscall return values, see docs/game-setup.md.
+    #[arg(long)]
+    script_path: Option<String>,
+    #[arg(long)]
+    memory_dump_path: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct MapgenArgs {
+    #[arg(long, default_value = "caves")]
+    style: MapStyle,
+    // Chosen randomly and reported if not given.
+    #[arg(short, long)]
+    seed: Option<u64>,
+    #[arg(long, default_value_t = 2)]
+    players: usize,
+    #[arg(long, default_value_t = 1)]
+    symmetry: usize,
+    #[arg(long)]
+    wall_density: Option<f64>,
+    #[arg(long, default_value_t = MAP_WIDTH)]
+    width: usize,
+    #[arg(long, default_value_t = MAP_HEIGHT)]
+    height: usize,
+    // Makes a map that stops at its borders instead of wrapping around.
+    #[arg(long)]
+    bounded: bool,
+    #[arg(short, long)]
+    output_path: PathBuf,
+    // Writes a game configuration using the map and its spawn points here.
+    #[arg(long)]
+    config_path: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct MapconvArgs {
+    // The formats of both files are chosen by their extensions.
+    input_path: PathBuf,
+    output_path: PathBuf,
+    // The size of a raw input map, if not the default.
+    #[arg(long, requires = "height")]
+    width: Option<usize>,
+    #[arg(long, requires = "width")]
+    height: Option<usize>,
+}
+
+#[derive(Clone, ValueEnum)]
+enum IsaFormat {
+    Json,
+    Markdown,
+}
+
+fn parse_address(address: &str) -> Result<u16, std::num::ParseIntError> {
+    match address.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => address.parse(),
+    }
+}
+
+fn new_game(config_path: &str, seed: Option<u64>, event_sink: Rc<dyn EventSink>) -> Kernel {
+    let mut game_config = GameConfiguration::load(config_path).expect("Failed to load game config");
+    game_config.seed = seed.or(game_config.seed);
     let kernel_config = game_config.get_kernel_config();
+    let map_file = game_config
+        .read_map_file()
+        .expect("Failed to load map data");
     let user_configs = game_config
-        .get_user_configs()
+        .get_user_configs(&map_file)
         .expect("Failed to load user config");
 
-    let map_data = game_config.read_mapdata().expect("Failed to load map data");
-    log_event(GameEvent::InitMap {
-        map_data: &map_data,
-        map_width: MAP_WIDTH,
-        map_height: MAP_HEIGHT,
+    let geometry = map_file
+        .geometry(game_config.bounded_map)
+        .expect("Failed to load map data");
+    let game_map = GameMap::new(&map_file.map_data, geometry).expect("Failed to build map data");
+
+    let mut kernel = Kernel::new(kernel_config, game_map, event_sink);
+    kernel
+        .setup_users(user_configs)
+        .expect("Failed to set up users");
+    kernel
+}
+
+fn run_disasm(args: DisasmArgs) {
+    let bytecode = match (&args.bytecode_path, &args.snapshot_path, args.pid) {
+        (Some(path), _, _) => fs::read(path).expect("Failed to read bytecode"),
+        (_, Some(path), Some(pid)) => {
+            let kernel =
+                Kernel::load_snapshot(path, Rc::new(NullSink)).expect("Failed to load snapshot");
+            if !kernel.has_process(pid) {
+                eprintln!("No process with pid {} in snapshot", pid);
+                exit(1);
+            }
+            kernel.get_process(pid).emulator.get_bytecode().to_vec()
+        }
+        _ => unreachable!(),
+    };
+    if bytecode.len() != BYTECODE_SIZE {
+        eprintln!("Bytecode must be exactly {} bytes", BYTECODE_SIZE);
+        exit(1);
+    }
+
+    // Skip the zero padding after the code unless asked for it.
+    let end = match args.end {
+        Some(end) => end as usize + 1,
+        None => bytecode
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |idx| idx + 1),
+    };
+    print!(
+        "{}",
+        disasm::disassemble(&PagedBytes::from(bytecode), args.start.unwrap_or(0), end)
+    );
+}
+
+fn run_isa(args: IsaArgs) {
+    match args.format {
+        IsaFormat::Json => println!("{}", isa::to_json()),
+        IsaFormat::Markdown => print!("{}", isa::to_markdown()),
+    }
+}
+
+fn run_debug(args: DebugArgs) {
+    if let (Some(bytecode_path), Some(memory_path)) = (&args.bytecode_path, &args.memory_path) {
+        let bytecode = fs::read(bytecode_path).expect("Failed to read bytecode");
+        let memory = fs::read(memory_path).expect("Failed to read memory");
+        if bytecode.len() != BYTECODE_SIZE || memory.len() != MEMORY_SIZE {
+            eprintln!(
+                "Bytecode and memory must be exactly {} and {} bytes",
+                BYTECODE_SIZE, MEMORY_SIZE
+            );
+            exit(1);
+        }
+        return debug_standalone(Emulator::new(memory, bytecode, 0), args.cycles);
+    }
+
+    let event_sink: Rc<dyn EventSink> = match &args.events_path {
+        Some(path) => Rc::new(JsonLinesSink::create(path).expect("Failed to create event log")),
+        None => Rc::new(NullSink),
+    };
+    let mut kernel = match (&args.resume_path, &args.config_path) {
+        (Some(resume_path), _) => {
+            Kernel::load_snapshot(resume_path, event_sink).expect("Failed to load snapshot")
+        }
+        (_, Some(config_path)) => new_game(config_path, args.seed, event_sink),
+        _ => unreachable!(),
+    };
+    let pid = match (args.pid, args.uid) {
+        (Some(pid), _) => pid,
+        (_, Some(uid)) => match kernel.has_user(uid).then(|| kernel.get_user(uid).initd_pid) {
+            Some(Some(pid)) => pid,
+            _ => {
+                eprintln!("User {} has no init process", uid);
+                exit(1);
+            }
+        },
+        _ => unreachable!(),
+    };
+    if !kernel.has_process(pid) {
+        eprintln!("No process with pid {}", pid);
+        exit(1);
+    }
+
+    let debugger = Rc::new(RefCell::new(Debugger::new(pid)));
+    kernel.add_observer(Box::new(debugger.clone()));
+    while !kernel.is_game_over() && debugger.borrow().is_attached() {
+        kernel.step();
+        if !kernel.has_process(pid) {
+            println!("pid {} exited at tick {}", pid, kernel.get_ticks_elapsed());
+            return;
+        }
+    }
+    if kernel.is_game_over() {
+        println!("Game over at tick {}", kernel.get_ticks_elapsed());
+    }
+}
+
+// Runs the emulator tick by tick without a kernel. Every syscall returns 0.
+fn debug_standalone(mut emulator: Emulator, cycles: usize) {
+    let debugger = Rc::new(RefCell::new(Debugger::new(0)));
+    let mut observers: Vec<Box<dyn ExecutionObserver>> = vec![Box::new(debugger.clone())];
+    let mut tick = 0;
+    while debugger.borrow().is_attached() {
+        emulator.increment_ts();
+        let mut cycle_count = cycles;
+        while let Some(syscall) =
+            emulator.run_until_interrupt_observed(0, &mut cycle_count, &mut observers)
+        {
+            if !debugger.borrow().is_attached() {
+                return;
+            }
+            println!("tick {}: syscall {}", tick, syscall.get_name());
+            emulator.set_syscall_return_value(0);
+        }
+        tick += 1;
+    }
+}
+
+fn run_bot(args: RunArgs) {
+    let mock = match &args.script_path {
+        Some(path) => MockSyscalls::load(path).expect("Failed to load mock script"),
+        None => MockSyscalls::new(),
+    };
+    let mut runner = BotRunner::load(&args.bytecode_path, &args.memory_path, mock)
+        .expect("Failed to load bot")
+        .with_cycles_per_tick(args.cycles);
+    runner.run(args.ticks);
+    let report = runner.report();
+    if let Some(path) = args.memory_dump_path {
+        fs::write(path, &report.memory).expect("Failed to write memory dump");
+    }
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
+
+fn run_mapgen(args: MapgenArgs) {
+    let geometry = match MapGeometry::new(args.width, args.height, args.bounded) {
+        Ok(geometry) => geometry,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
+    let options = MapGenOptions {
+        style: args.style,
+        geometry,
+        seed: args.seed.unwrap_or_else(rand::random),
+        players: args.players,
+        symmetry: args.symmetry,
+        wall_density: args.wall_density,
+    };
+    let map = match mapgen::generate(&options) {
+        Ok(map) => map,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
+    let format = MapFormat::from_path(&args.output_path);
+    let bytes = map
+        .to_map_file()
+        .to_bytes(format)
+        .expect("Generated maps have no portals");
+    fs::write(&args.output_path, bytes).expect("Failed to write map");
+    if let Some(path) = args.config_path {
+        let config = map.to_game_config(&args.output_path);
+        config
+            .dump(path.to_str().expect("Invalid config path"))
+            .expect("Failed to write game config");
+    }
+    println!("seed {}", options.seed);
+    for (idx, (x, y)) in map.spawn_points.iter().enumerate() {
+        println!("spawn point {}: [{}, {}]", idx + 1, x, y);
+    }
+}
+
+fn run_mapconv(args: MapconvArgs) {
+    let bytes = fs::read(&args.input_path).expect("Failed to read map");
+    let size = args.width.zip(args.height);
+    let map_file = match MapFile::parse(&bytes, MapFormat::from_path(&args.input_path), size) {
+        Ok(map_file) => map_file,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
+    let bytes = match map_file.to_bytes(MapFormat::from_path(&args.output_path)) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    };
+    fs::write(&args.output_path, bytes).expect("Failed to write map");
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let args = match cli.command {
+        Some(Command::Disasm(args)) => return run_disasm(args),
+        Some(Command::Isa(args)) => return run_isa(args),
+        Some(Command::Debug(args)) => return run_debug(args),
+        Some(Command::Run(args)) => return run_bot(args),
+        Some(Command::Mapgen(args)) => return run_mapgen(args),
+        Some(Command::Mapconv(args)) => return run_mapconv(args),
+        None => cli.game,
+    };
+
+    let event_sink: Rc<dyn EventSink> = match args.events_path {
+        Some(path) => Rc::new(JsonLinesSink::create(path).expect("Failed to create event log")),
+        None => Rc::new(StdoutSink),
+    };
+
+    let mut kernel = match args.resume_path {
+        Some(resume_path) => {
+            Kernel::load_snapshot(&resume_path, event_sink).expect("Failed to load snapshot")
+        }
+        None => new_game(&args.config_path.unwrap(), args.seed, event_sink),
+    };
+
+    if let Some(path) = args.trace_path {
+        let pids = args.trace_pids.into_iter().collect();
+        let tracer = Tracer::create(path, pids, args.trace_limit).expect("Failed to create trace");
+        kernel.add_observer(Box::new(tracer));
+    }
+
+    let profiler = args.profile_path.as_ref().map(|_| {
+        let profiler = Rc::new(RefCell::new(Profiler::new()));
+        kernel.add_observer(Box::new(profiler.clone()));
+        profiler
     });
 
-    let game_map = GameMap::from_map_data(&map_data).expect("Failed to build map data");
+    while !kernel.is_game_over() {
+        let summary = kernel.step();
+        if let (Some(path), Some(interval)) = (&args.snapshot_path, args.snapshot_interval) {
+            if (summary.tick + 1) % interval == 0 {
+                kernel.save_snapshot(path).expect("Failed to save snapshot");
+            }
+        }
+    }
+    kernel.finish();
 
-    let mut kernel = kernel::Kernel::new(kernel_config, game_map);
-    kernel.setup_users(user_configs);
-    kernel.run_full_game();
+    if let (Some(path), Some(profiler)) = (args.profile_path, profiler) {
+        let mut file = File::create(path).expect("Failed to create profile");
+        profiler
+            .borrow()
+            .write_report(&mut file, DEFAULT_REPORT_ROWS)
+            .expect("Failed to write profile");
+    }
 }
@@ -0,0 +1,232 @@
+use crate::kernel::syscall::SYSCALL_TABLE;
+use crate::vm::emulator::{Emulator, ExecutionObserver, NasalDemons, StepResult};
+use crate::vm::instructions::OPCODE_TABLE;
+use crate::vm::register::RegisterName;
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+pub const DEFAULT_REPORT_ROWS: usize = 10;
+
+#[derive(Clone, Copy, Default)]
+pub struct Counter {
+    pub count: u64,
+    pub cycles: u64,
+}
+
+impl Counter {
+    fn add(&mut self, cycles: u64) {
+        self.count += 1;
+        self.cycles += cycles;
+    }
+
+    fn merge(&mut self, other: &Counter) {
+        self.count += other.count;
+        self.cycles += other.cycles;
+    }
+}
+
+#[derive(Default)]
+pub struct ProcessProfile {
+    pub total: Counter,
+    pub opcodes: BTreeMap<u8, Counter>,
+    // Keyed by PC, with the last opcode executed at that address.
+    pub addresses: BTreeMap<u16, (u8, Counter)>,
+    pub syscalls: BTreeMap<u8, Counter>,
+}
+
+impl ProcessProfile {
+    fn merge(&mut self, other: &ProcessProfile) {
+        self.total.merge(&other.total);
+        for (opcode, counter) in &other.opcodes {
+            self.opcodes.entry(*opcode).or_default().merge(counter);
+        }
+        for (pc, (opcode, counter)) in &other.addresses {
+            let entry = self
+                .addresses
+                .entry(*pc)
+                .or_insert((*opcode, Counter::default()));
+            entry.1.merge(counter);
+        }
+        for (number, counter) in &other.syscalls {
+            self.syscalls.entry(*number).or_default().merge(counter);
+        }
+    }
+}
+
+// Counts the instructions and cycles of every process, by opcode, by address
+// and by syscall.
+#[derive(Default)]
+pub struct Profiler {
+    owners: HashMap<u16, u16>,
+    // Keyed by uid first so that the processes of a user form one range, and
+    // a pid freed by one user and reused by another is not merged.
+    processes: BTreeMap<(u16, u16), ProcessProfile>,
+    pending: Option<(u16, u8, usize)>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_process_profiles(&self) -> &BTreeMap<(u16, u16), ProcessProfile> {
+        &self.processes
+    }
+
+    pub fn get_user_profiles(&self) -> BTreeMap<u16, ProcessProfile> {
+        let mut users: BTreeMap<u16, ProcessProfile> = BTreeMap::new();
+        for ((uid, _), profile) in &self.processes {
+            users.entry(*uid).or_default().merge(profile);
+        }
+        users
+    }
+
+    // Lists the hottest addresses and most expensive opcodes of each user,
+    // showing at most `rows` entries per table.
+    pub fn write_report<W: Write>(&self, writer: &mut W, rows: usize) -> io::Result<()> {
+        for (uid, profile) in self.get_user_profiles() {
+            writeln!(writer, "== User {} ==", uid)?;
+            writeln!(
+                writer,
+                "{} instructions, {} cycles",
+                profile.total.count, profile.total.cycles
+            )?;
+
+            writeln!(writer, "\nProcesses:")?;
+            writeln!(writer, "{:>6} {:>12} {:>12}", "pid", "count", "cycles")?;
+            for ((_, pid), process) in self.processes.range((uid, 0)..=(uid, u16::MAX)) {
+                writeln!(
+                    writer,
+                    "{:>6} {:>12} {:>12}",
+                    pid, process.total.count, process.total.cycles
+                )?;
+            }
+
+            writeln!(writer, "\nHot addresses:")?;
+            writeln!(
+                writer,
+                "{:>6} {:<10} {:>12} {:>12} {:>7}",
+                "pc", "mnemonic", "count", "cycles", "%"
+            )?;
+            let mut addresses: Vec<_> = profile.addresses.iter().collect();
+            addresses.sort_by_key(|(pc, (_, counter))| (std::cmp::Reverse(counter.cycles), **pc));
+            for (pc, (opcode, counter)) in addresses.into_iter().take(rows) {
+                writeln!(
+                    writer,
+                    "{:#06x} {:<10} {:>12} {:>12} {:>6.2}%",
+                    pc,
+                    get_mnemonic(*opcode),
+                    counter.count,
+                    counter.cycles,
+                    percentage(counter.cycles, profile.total.cycles)
+                )?;
+            }
+
+            writeln!(writer, "\nExpensive opcodes:")?;
+            writeln!(
+                writer,
+                "{:>6} {:<10} {:>7} {:>12} {:>12} {:>7}",
+                "opcode", "mnemonic", "latency", "count", "cycles", "%"
+            )?;
+            let mut opcodes: Vec<_> = profile.opcodes.iter().collect();
+            opcodes.sort_by_key(|(opcode, counter)| (std::cmp::Reverse(counter.cycles), **opcode));
+            for (opcode, counter) in opcodes.into_iter().take(rows) {
+                writeln!(
+                    writer,
+                    "{:>6} {:<10} {:>7} {:>12} {:>12} {:>6.2}%",
+                    format!("{:#04x}", opcode),
+                    get_mnemonic(*opcode),
+                    OPCODE_TABLE.get_instruction(*opcode).get_latency(),
+                    counter.count,
+                    counter.cycles,
+                    percentage(counter.cycles, profile.total.cycles)
+                )?;
+            }
+
+            writeln!(writer, "\nSyscalls:")?;
+            writeln!(
+                writer,
+                "{:>6} {:<24} {:>12} {:>12}",
+                "number", "name", "count", "cycles"
+            )?;
+            let mut syscalls: Vec<_> = profile.syscalls.iter().collect();
+            syscalls.sort_by_key(|(number, counter)| (std::cmp::Reverse(counter.count), **number));
+            for (number, counter) in syscalls {
+                writeln!(
+                    writer,
+                    "{:>6} {:<24} {:>12} {:>12}",
+                    format!("{:#04x}", number),
+                    SYSCALL_TABLE.get_syscall(*number).get_name(),
+                    counter.count,
+                    counter.cycles
+                )?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl ExecutionObserver for Profiler {
+    fn observes(&self, _pid: u16) -> bool {
+        true
+    }
+
+    fn process_created(&mut self, pid: u16, uid: u16) {
+        self.owners.insert(pid, uid);
+    }
+
+    fn before_instruction(&mut self, _pid: u16, emulator: &mut Emulator, cycle_count: usize) {
+        let pc = emulator.peek_reg(RegisterName::PC);
+        self.pending = Some((pc, emulator.peek_from_pc(), cycle_count));
+    }
+
+    fn after_instruction(
+        &mut self,
+        pid: u16,
+        _emulator: &mut Emulator,
+        cycle_count: usize,
+        result: &StepResult,
+        _nasal_demons: &[NasalDemons],
+    ) {
+        let Some((pc, opcode, cycles_before)) = self.pending.take() else {
+            return;
+        };
+        let cycles = (cycles_before - cycle_count) as u64;
+        let uid = self.owners.get(&pid).copied().unwrap_or_default();
+        let profile = self.processes.entry((uid, pid)).or_default();
+        profile.total.add(cycles);
+        profile.opcodes.entry(opcode).or_default().add(cycles);
+        let address = profile
+            .addresses
+            .entry(pc)
+            .or_insert((opcode, Counter::default()));
+        address.0 = opcode;
+        address.1.add(cycles);
+        if let StepResult::Syscall(syscall) = result {
+            profile
+                .syscalls
+                .entry(syscall.get_number())
+                .or_default()
+                .add(cycles);
+        }
+    }
+}
+
+fn get_mnemonic(opcode: u8) -> &'static str {
+    let instruction = OPCODE_TABLE.get_instruction(opcode);
+    if instruction.get_opcode() == opcode {
+        instruction.get_mnemonic()
+    } else {
+        "(invalid)"
+    }
+}
+
+fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
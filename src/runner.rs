@@ -0,0 +1,235 @@
+use crate::config::ConfigIoError;
+use crate::kernel::syscall::{SyscallArgs, SYSCALL_TABLE};
+use crate::vm::emulator::{Emulator, BYTECODE_SIZE, MEMORY_SIZE};
+use crate::vm::register::RegisterName;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::BufReader;
+
+// The execution limit of a process with a nice value of 0.
+pub const DEFAULT_CYCLES_PER_TICK: usize = 1000;
+
+// Decides the return value of each syscall made by a bot running without a
+// kernel.
+pub trait SyscallHandler {
+    fn handle(&mut self, tick: u64, number: u8, args: SyscallArgs) -> u16;
+}
+
+impl<F: FnMut(u64, u8, SyscallArgs) -> u16> SyscallHandler for F {
+    fn handle(&mut self, tick: u64, number: u8, args: SyscallArgs) -> u16 {
+        self(tick, number, args)
+    }
+}
+
+#[derive(Default, Deserialize)]
+pub struct MockSyscall {
+    // Returned by the first calls, in order.
+    #[serde(default)]
+    pub returns: Vec<u16>,
+    // Returned once `returns` runs out.
+    #[serde(default)]
+    pub default: Option<u16>,
+}
+
+// The JSON format of a mock script. Syscalls are keyed by name or number.
+#[derive(Default, Deserialize)]
+pub struct MockScript {
+    #[serde(default)]
+    pub default: u16,
+    #[serde(default)]
+    pub syscalls: BTreeMap<String, MockSyscall>,
+}
+
+// Returns canned values for each syscall, and 0 for syscalls it knows nothing
+// about unless a different default is set.
+#[derive(Default)]
+pub struct MockSyscalls {
+    returns: HashMap<u8, VecDeque<u16>>,
+    defaults: HashMap<u8, u16>,
+    default: u16,
+}
+
+impl MockSyscalls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_script(script: MockScript) -> Result<Self, ConfigIoError> {
+        let mut mock = MockSyscalls::new().with_default(script.default);
+        for (key, syscall) in script.syscalls {
+            let number = parse_syscall(&key).ok_or_else(|| ConfigIoError {
+                message: format!("Unknown syscall {:?}", key),
+            })?;
+            for value in syscall.returns {
+                mock.push_return(number, value);
+            }
+            if let Some(value) = syscall.default {
+                mock.set_default_return(number, value);
+            }
+        }
+        Ok(mock)
+    }
+
+    pub fn load(filename: &str) -> Result<Self, ConfigIoError> {
+        let script = serde_json::from_reader(BufReader::new(File::open(filename)?))?;
+        Self::from_script(script)
+    }
+
+    pub fn with_default(mut self, value: u16) -> Self {
+        self.default = value;
+        self
+    }
+
+    pub fn push_return(&mut self, number: u8, value: u16) {
+        self.returns.entry(number).or_default().push_back(value);
+    }
+
+    pub fn set_default_return(&mut self, number: u8, value: u16) {
+        self.defaults.insert(number, value);
+    }
+}
+
+impl SyscallHandler for MockSyscalls {
+    fn handle(&mut self, _tick: u64, number: u8, _args: SyscallArgs) -> u16 {
+        self.returns
+            .get_mut(&number)
+            .and_then(VecDeque::pop_front)
+            .or_else(|| self.defaults.get(&number).copied())
+            .unwrap_or(self.default)
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct SyscallRecord {
+    pub tick: u64,
+    pub number: u8,
+    pub name: &'static str,
+    pub args: SyscallArgs,
+    pub return_value: u16,
+}
+
+#[derive(Serialize)]
+pub struct RunReport {
+    pub ticks: u64,
+    pub registers: BTreeMap<&'static str, u16>,
+    pub calls: Vec<SyscallRecord>,
+    #[serde(skip)]
+    pub memory: Vec<u8>,
+}
+
+// Runs a single bot tick by tick like the kernel does, except that syscalls are
+// answered by a `SyscallHandler` and cost nothing.
+pub struct BotRunner<H: SyscallHandler> {
+    emulator: Emulator,
+    handler: H,
+    cycles_per_tick: usize,
+    ticks_elapsed: u64,
+    calls: Vec<SyscallRecord>,
+}
+
+impl<H: SyscallHandler> BotRunner<H> {
+    pub fn new(emulator: Emulator, handler: H) -> Self {
+        BotRunner {
+            emulator,
+            handler,
+            cycles_per_tick: DEFAULT_CYCLES_PER_TICK,
+            ticks_elapsed: 0,
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn load(bytecode_path: &str, memory_path: &str, handler: H) -> Result<Self, ConfigIoError> {
+        let bytecode = read_exact_file(bytecode_path, BYTECODE_SIZE)?;
+        let memory = read_exact_file(memory_path, MEMORY_SIZE)?;
+        Ok(Self::new(Emulator::new(memory, bytecode, 0), handler))
+    }
+
+    pub fn with_cycles_per_tick(mut self, cycles_per_tick: usize) -> Self {
+        self.cycles_per_tick = cycles_per_tick;
+        self
+    }
+
+    pub fn tick(&mut self) {
+        let tick = self.ticks_elapsed;
+        self.emulator.increment_ts();
+        let mut cycle_count = self.cycles_per_tick;
+        while let Some(syscall) = self.emulator.run_until_interrupt(&mut cycle_count) {
+            let number = syscall.get_number();
+            let args = self.emulator.get_syscall_args();
+            let return_value = self.handler.handle(tick, number, args);
+            self.emulator.set_syscall_return_value(return_value);
+            self.calls.push(SyscallRecord {
+                tick,
+                number,
+                name: syscall.get_name(),
+                args,
+                return_value,
+            });
+        }
+        self.ticks_elapsed += 1;
+    }
+
+    pub fn run(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    pub fn get_emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    pub fn get_emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+
+    pub fn get_handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn get_calls(&self) -> &[SyscallRecord] {
+        &self.calls
+    }
+
+    pub fn report(&self) -> RunReport {
+        RunReport {
+            ticks: self.ticks_elapsed,
+            registers: (0..16)
+                .map(|idx| {
+                    let register = RegisterName::from(idx);
+                    (register.name(), self.emulator.peek_reg(register))
+                })
+                .collect(),
+            calls: self.calls.clone(),
+            memory: self.emulator.peek_bytes_from_mem(0, MEMORY_SIZE),
+        }
+    }
+}
+
+fn parse_syscall(key: &str) -> Option<u8> {
+    let number = match key.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => key.parse().ok(),
+    };
+    number.or_else(|| {
+        SYSCALL_TABLE
+            .get_syscall_by_name(key)
+            .map(|syscall| syscall.get_number())
+    })
+}
+
+fn read_exact_file(filename: &str, length: usize) -> Result<Vec<u8>, ConfigIoError> {
+    let bytes = fs::read(filename)?;
+    if bytes.len() != length {
+        return Err(ConfigIoError {
+            message: format!(
+                "Incorrect file size {} for file {:?}",
+                bytes.len(),
+                filename
+            ),
+        });
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,138 @@
+use crate::disasm;
+use crate::vm::emulator::{Emulator, ExecutionObserver, NasalDemons, StepResult};
+use crate::vm::register::RegisterName;
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub const DEFAULT_TRACE_LIMIT: u64 = 64 << 20;
+const TRUNCATION_MARKER: &[u8] = b"{\"truncated\":true}\n";
+
+// Registers in the order of their indices, PC first and AX last.
+type RegisterValues = [u16; 16];
+
+struct PendingInstruction {
+    pc: u16,
+    opcode: u8,
+    instruction: String,
+    operands: Vec<String>,
+    registers_before: RegisterValues,
+}
+
+#[derive(Serialize)]
+struct TraceRecord<'a> {
+    pid: u16,
+    pc: u16,
+    opcode: u8,
+    instruction: &'a str,
+    operands: &'a [String],
+    registers_before: &'a RegisterValues,
+    registers_after: &'a RegisterValues,
+    cycles_left: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syscall: Option<&'static str>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    nasal_demons: &'a [NasalDemons],
+}
+
+// Writes every instruction executed by the selected processes to a JSON lines
+// file, until the file would grow past `limit` bytes.
+pub struct Tracer {
+    // Traces all processes if empty.
+    pids: BTreeSet<u16>,
+    writer: BufWriter<File>,
+    limit: u64,
+    bytes_written: u64,
+    truncated: bool,
+    pending: Option<PendingInstruction>,
+}
+
+impl Tracer {
+    pub fn create<P: AsRef<Path>>(path: P, pids: BTreeSet<u16>, limit: u64) -> io::Result<Self> {
+        Ok(Tracer {
+            pids,
+            writer: BufWriter::new(File::create(path)?),
+            limit,
+            bytes_written: 0,
+            truncated: false,
+            pending: None,
+        })
+    }
+
+    // Keeps room for the truncation marker, so that the file never grows past
+    // the limit.
+    fn write_line(&mut self, line: &[u8]) {
+        let marker_len = TRUNCATION_MARKER.len() as u64;
+        if self.bytes_written + line.len() as u64 + 1 + marker_len > self.limit {
+            self.truncated = true;
+            if self.bytes_written + marker_len <= self.limit {
+                self.writer
+                    .write_all(TRUNCATION_MARKER)
+                    .expect("Failed to write trace");
+            }
+            return;
+        }
+        self.writer.write_all(line).expect("Failed to write trace");
+        writeln!(self.writer).expect("Failed to write trace");
+        self.bytes_written += line.len() as u64 + 1;
+    }
+}
+
+impl ExecutionObserver for Tracer {
+    fn observes(&self, pid: u16) -> bool {
+        !self.truncated && (self.pids.is_empty() || self.pids.contains(&pid))
+    }
+
+    fn before_instruction(&mut self, _pid: u16, emulator: &mut Emulator, _cycle_count: usize) {
+        let pc = emulator.peek_reg(RegisterName::PC);
+        let decoded = disasm::decode_instruction(emulator.get_bytecode(), pc);
+        self.pending = Some(PendingInstruction {
+            pc,
+            opcode: decoded.bytes[0],
+            instruction: decoded.to_string(),
+            operands: decoded
+                .operands
+                .iter()
+                .map(disasm::format_operand)
+                .collect(),
+            registers_before: read_registers(emulator),
+        });
+    }
+
+    fn after_instruction(
+        &mut self,
+        pid: u16,
+        emulator: &mut Emulator,
+        cycle_count: usize,
+        result: &StepResult,
+        nasal_demons: &[NasalDemons],
+    ) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        let record = TraceRecord {
+            pid,
+            pc: pending.pc,
+            opcode: pending.opcode,
+            instruction: &pending.instruction,
+            operands: &pending.operands,
+            registers_before: &pending.registers_before,
+            registers_after: &read_registers(emulator),
+            cycles_left: cycle_count,
+            syscall: match result {
+                StepResult::Syscall(syscall) => Some(syscall.get_name()),
+                _ => None,
+            },
+            nasal_demons,
+        };
+        let line = serde_json::to_vec(&record).unwrap();
+        self.write_line(&line);
+    }
+}
+
+fn read_registers(emulator: &Emulator) -> RegisterValues {
+    std::array::from_fn(|idx| emulator.peek_reg(RegisterName::from(idx as u8)))
+}
@@ -0,0 +1,151 @@
+use crate::vm::emulator::{Emulator, OperandReader, BYTECODE_SIZE};
+use crate::vm::instructions::{Instruction, OpcodeTable, OPCODE_TABLE};
+use crate::vm::paged::PagedBytes;
+use crate::vm::register::RegisterName;
+
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+const PAGE_SIZE: usize = 256;
+const PAGE_COUNT: usize = BYTECODE_SIZE / PAGE_SIZE;
+// The longest encoding is `memcpy`, with a register and two memory operands
+// that both have an index and a displacement.
+pub const MAX_INSTRUCTION_SIZE: usize = 12;
+
+#[derive(Clone, Copy)]
+pub struct CachedInstruction {
+    pub instruction: &'static dyn Instruction,
+    pub latency: u16,
+    pub is_syscall: bool,
+    pub is_valid_sleep: bool,
+    // The code starting at the opcode, long enough for any instruction. Kept
+    // as an integer so that it is passed around in registers.
+    pub code: u128,
+}
+
+impl CachedInstruction {
+    fn decode(bytecode: &PagedBytes, address: u16) -> Self {
+        let opcode = bytecode[address as usize];
+        let instruction = OPCODE_TABLE.get_instruction(opcode);
+        let code = (0..MAX_INSTRUCTION_SIZE).rev().fold(0, |code, offset| {
+            code << 8 | bytecode[(address as usize + offset) % BYTECODE_SIZE] as u128
+        });
+        CachedInstruction {
+            instruction,
+            latency: instruction.get_latency() as u16,
+            is_syscall: opcode == OpcodeTable::get_syscall_opcode(),
+            is_valid_sleep: OpcodeTable::is_valid_sleep_opcode(opcode),
+            code,
+        }
+    }
+}
+
+type Page = [Option<CachedInstruction>; PAGE_SIZE];
+
+// Decoded instructions by code address. Pages are shared between clones of an
+// emulator until one of them writes to its code.
+#[derive(Clone)]
+pub struct DecodeCache {
+    pages: Box<[Option<Rc<Page>>; PAGE_COUNT]>,
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        Self {
+            pages: Box::new([const { None }; PAGE_COUNT]),
+        }
+    }
+}
+
+impl DecodeCache {
+    #[inline]
+    pub fn get(&mut self, bytecode: &PagedBytes, address: u16) -> &CachedInstruction {
+        if self.contains(address) {
+            let (page_idx, idx) = (address as usize / PAGE_SIZE, address as usize % PAGE_SIZE);
+            return self.pages[page_idx].as_ref().unwrap()[idx]
+                .as_ref()
+                .unwrap();
+        }
+        self.fill(bytecode, address)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn fill(&mut self, bytecode: &PagedBytes, address: u16) -> &CachedInstruction {
+        let (page_idx, idx) = (address as usize / PAGE_SIZE, address as usize % PAGE_SIZE);
+        let page = self.pages[page_idx].get_or_insert_with(|| Rc::new([None; PAGE_SIZE]));
+        Rc::make_mut(page)[idx].insert(CachedInstruction::decode(bytecode, address))
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        let (page_idx, idx) = (address as usize / PAGE_SIZE, address as usize % PAGE_SIZE);
+        matches!(&self.pages[page_idx], Some(page) if page[idx].is_some())
+    }
+
+    // Forgets every instruction that might include one of the `count` bytes
+    // starting at `address`.
+    pub fn invalidate(&mut self, address: u16, count: usize) {
+        let first = address.wrapping_sub(MAX_INSTRUCTION_SIZE as u16 - 1);
+        let count = (count + MAX_INSTRUCTION_SIZE - 1).min(BYTECODE_SIZE);
+        for offset in 0..count {
+            let address = first.wrapping_add(offset as u16) as usize;
+            if let Some(page) = self.pages[address / PAGE_SIZE].as_mut() {
+                if page[address % PAGE_SIZE].is_some() {
+                    Rc::make_mut(page)[address % PAGE_SIZE] = None;
+                }
+            }
+        }
+    }
+}
+
+// Executes an instruction with the operands taken from its cached code rather
+// than the bytecode. PC still moves past each operand as it is read.
+pub struct CachedExecution<'a> {
+    emulator: &'a mut Emulator,
+    address: u16,
+    offset: u16,
+    bytes: [u8; 16],
+}
+
+impl<'a> CachedExecution<'a> {
+    #[inline]
+    pub fn new(emulator: &'a mut Emulator, address: u16, code: u128) -> Self {
+        CachedExecution {
+            emulator,
+            address,
+            offset: 1,
+            bytes: code.to_le_bytes(),
+        }
+    }
+}
+
+impl Deref for CachedExecution<'_> {
+    type Target = Emulator;
+
+    fn deref(&self) -> &Emulator {
+        self.emulator
+    }
+}
+
+impl DerefMut for CachedExecution<'_> {
+    fn deref_mut(&mut self) -> &mut Emulator {
+        self.emulator
+    }
+}
+
+impl OperandReader for CachedExecution<'_> {
+    #[inline]
+    fn read_bytes_from_pc_fixed<const N: usize>(&mut self) -> [u8; N] {
+        let offset = self.offset as usize;
+        let result = std::array::from_fn(|idx| self.bytes[offset + idx]);
+        self.offset += N as u16;
+        self.emulator
+            .set_reg(RegisterName::PC, self.address.wrapping_add(self.offset));
+        result
+    }
+
+    #[inline]
+    fn get_reg_mut(&mut self, register: RegisterName) -> u16 {
+        self.emulator.get_reg_mut(register)
+    }
+}
@@ -1,12 +1,19 @@
+use crate::game::rng::{derive_rng, GameRng};
 use crate::kernel::syscall::*;
+use crate::vm::decode_cache::DecodeCache;
 use crate::vm::instructions;
+use crate::vm::paged::PagedBytes;
 use crate::vm::register::*;
 
-use rand::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
 
 pub const BYTECODE_SIZE: usize = 65536;
 pub const MEMORY_SIZE: usize = 65536;
 
+#[derive(Clone, Copy)]
 pub enum CpuFlag {
     Zero,
     Carry,
@@ -15,57 +22,233 @@ pub enum CpuFlag {
     Sleep,
 }
 
-#[derive(Clone)]
+impl CpuFlag {
+    // In the order of their bits in FL.
+    pub const ALL: [CpuFlag; 5] = [
+        CpuFlag::Zero,
+        CpuFlag::Carry,
+        CpuFlag::Overflow,
+        CpuFlag::Sign,
+        CpuFlag::Sleep,
+    ];
+
+    // The names used in docs/instructions.md.
+    pub fn name(self) -> &'static str {
+        match self {
+            CpuFlag::Zero => "ZF",
+            CpuFlag::Carry => "CF",
+            CpuFlag::Overflow => "OF",
+            CpuFlag::Sign => "SF",
+            CpuFlag::Sleep => "NF",
+        }
+    }
+}
+
+pub enum StepResult {
+    Executed,
+    Syscall(&'static dyn Syscall),
+    OutOfCycles,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "effect")]
+pub enum NasalDemons {
+    WriteMemory {
+        addr: u16,
+        value: u8,
+    },
+    WriteRegister {
+        register: &'static str,
+        value: u16,
+    },
+    SwapRegisters {
+        register1: &'static str,
+        register2: &'static str,
+    },
+    WriteCode {
+        addr: u16,
+        value: u8,
+    },
+}
+
+// Watches the instructions executed by the processes it `observes`.
+pub trait ExecutionObserver {
+    fn observes(&self, pid: u16) -> bool;
+    // Called for every process that exists when the observer is added, and for
+    // every process created after that.
+    fn process_created(&mut self, _pid: u16, _uid: u16) {}
+    fn before_instruction(&mut self, _pid: u16, _emulator: &mut Emulator, _cycle_count: usize) {}
+    // `nasal_demons` lists the effects applied while executing the instruction.
+    fn after_instruction(
+        &mut self,
+        _pid: u16,
+        _emulator: &mut Emulator,
+        _cycle_count: usize,
+        _result: &StepResult,
+        _nasal_demons: &[NasalDemons],
+    ) {
+    }
+}
+
+// Lets the caller keep a handle to an observer owned by the kernel.
+impl<T: ExecutionObserver> ExecutionObserver for Rc<RefCell<T>> {
+    fn observes(&self, pid: u16) -> bool {
+        self.borrow().observes(pid)
+    }
+
+    fn process_created(&mut self, pid: u16, uid: u16) {
+        self.borrow_mut().process_created(pid, uid);
+    }
+
+    fn before_instruction(&mut self, pid: u16, emulator: &mut Emulator, cycle_count: usize) {
+        self.borrow_mut()
+            .before_instruction(pid, emulator, cycle_count);
+    }
+
+    fn after_instruction(
+        &mut self,
+        pid: u16,
+        emulator: &mut Emulator,
+        cycle_count: usize,
+        result: &StepResult,
+        nasal_demons: &[NasalDemons],
+    ) {
+        self.borrow_mut()
+            .after_instruction(pid, emulator, cycle_count, result, nasal_demons);
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Emulator {
-    memory: Vec<u8>,
+    memory: PagedBytes,
     registers: Registers,
-    bytecode: Vec<u8>,
+    bytecode: PagedBytes,
+    rng: GameRng,
+    // Only recorded while observed.
+    #[serde(skip)]
+    nasal_demons_log: Option<Vec<NasalDemons>>,
+    #[serde(skip)]
+    decode_cache: DecodeCache,
 }
 
 impl Emulator {
-    pub fn new(memory: Vec<u8>, bytecode: Vec<u8>) -> Self {
-        Emulator {
-            memory,
-            registers: Registers::new(),
-            bytecode,
-        }
+    pub fn new(memory: Vec<u8>, bytecode: Vec<u8>, seed: u64) -> Self {
+        let mut emulator = Emulator {
+            memory: PagedBytes::from(memory),
+            registers: Registers::new(derive_rng(seed, "random_register")),
+            bytecode: PagedBytes::from(bytecode),
+            rng: derive_rng(seed, "nasal_demons"),
+            nasal_demons_log: None,
+            decode_cache: DecodeCache::default(),
+        };
+        emulator.reseed(seed);
+        emulator
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = derive_rng(seed, "nasal_demons");
+        self.registers.reseed(derive_rng(seed, "random_register"));
     }
 
     pub fn run_until_interrupt(&mut self, cycle_count: &mut usize) -> Option<&'static dyn Syscall> {
         loop {
-            let opcode: u8 = self.peek_from_pc();
-            let instruction = instructions::OPCODE_TABLE.get_instruction(opcode);
-            let latency = instruction.get_latency();
-            if *cycle_count < latency {
-                return None;
+            match self.step(cycle_count) {
+                StepResult::Executed => {}
+                StepResult::Syscall(syscall) => return Some(syscall),
+                StepResult::OutOfCycles => return None,
             }
-            *cycle_count -= latency;
-            self.increment_pc(1);
+        }
+    }
 
-            if self.get_cpu_flag(CpuFlag::Sleep)
-                && !instructions::OpcodeTable::is_valid_sleep_opcode(opcode)
+    pub fn run_until_interrupt_observed(
+        &mut self,
+        pid: u16,
+        cycle_count: &mut usize,
+        observers: &mut [Box<dyn ExecutionObserver>],
+    ) -> Option<&'static dyn Syscall> {
+        loop {
+            if *cycle_count < self.get_next_latency() {
+                return None;
+            }
+            for observer in observers
+                .iter_mut()
+                .filter(|observer| observer.observes(pid))
             {
-                self.nasal_demons();
-                self.set_cpu_flag(CpuFlag::Sleep, false);
+                observer.before_instruction(pid, self, *cycle_count);
             }
-
-            if opcode == instructions::OpcodeTable::get_syscall_opcode() {
-                return Some(SYSCALL_TABLE.get_syscall(self.get_syscall_number()));
+            self.nasal_demons_log = Some(Vec::new());
+            let result = self.step(cycle_count);
+            let nasal_demons = self.nasal_demons_log.take().unwrap_or_default();
+            for observer in observers
+                .iter_mut()
+                .filter(|observer| observer.observes(pid))
+            {
+                observer.after_instruction(pid, self, *cycle_count, &result, &nasal_demons);
+            }
+            match result {
+                StepResult::Executed => {}
+                StepResult::Syscall(syscall) => return Some(syscall),
+                StepResult::OutOfCycles => return None,
             }
-            instruction.execute(self);
         }
     }
 
+    pub fn get_next_latency(&self) -> usize {
+        let opcode: u8 = self.peek_from_pc();
+        instructions::OPCODE_TABLE
+            .get_instruction(opcode)
+            .get_latency()
+    }
+
+    // Executes a single instruction, or nothing if there are not enough cycles
+    // left for it. A syscall instruction only moves PC, the kernel has to
+    // carry out the returned syscall.
+    #[inline(always)]
+    pub fn step(&mut self, cycle_count: &mut usize) -> StepResult {
+        let pc = self.get_reg_internal(RegisterName::PC);
+        let cached = *self.decode_cache.get(&self.bytecode, pc);
+        let latency = cached.latency as usize;
+        if *cycle_count < latency {
+            return StepResult::OutOfCycles;
+        }
+        *cycle_count -= latency;
+        self.set_reg(RegisterName::PC, pc.wrapping_add(1));
+
+        // Nasal demons might rewrite the code or move PC, so the operands have
+        // to be read from the code in that case.
+        let sleep_violation = !cached.is_valid_sleep && self.get_cpu_flag(CpuFlag::Sleep);
+        if sleep_violation {
+            self.nasal_demons();
+            self.set_cpu_flag(CpuFlag::Sleep, false);
+        }
+
+        if cached.is_syscall {
+            return StepResult::Syscall(SYSCALL_TABLE.get_syscall(self.get_syscall_number()));
+        }
+        if sleep_violation {
+            cached.instruction.execute(self);
+        } else {
+            cached.instruction.execute_cached(self, pc, cached.code);
+        }
+        StepResult::Executed
+    }
+
     fn get_reg_internal(&self, register: RegisterName) -> u16 {
-        self.registers.get(register).get_value_internal()
+        self.registers.get_value_internal(register)
+    }
+
+    // Reads a register without the side effects of reading it from code, e.g.
+    // `CT` is not incremented.
+    pub fn peek_reg(&self, register: RegisterName) -> u16 {
+        self.get_reg_internal(register)
     }
 
     pub fn get_reg_mut(&mut self, register: RegisterName) -> u16 {
-        self.registers.get_mut(register).get_value_mut()
+        self.registers.get_value_mut(register)
     }
 
     pub fn set_reg(&mut self, register: RegisterName, value: u16) {
-        self.registers.get_mut(register).set_value(value)
+        self.registers.set_value(register, value)
     }
 
     pub fn increment_pc(&mut self, count: u16) {
@@ -87,16 +270,6 @@ impl Emulator {
         T::from_bytes(&self.peek_bytes_from_pc_fixed::<N>())
     }
 
-    fn read_bytes_from_pc_fixed<const N: usize>(&mut self) -> [u8; N] {
-        let result = self.peek_bytes_from_pc_fixed();
-        self.increment_pc(N as u16);
-        result
-    }
-
-    pub fn read_from_pc<T: FromBytes<Bytes = [u8; N]>, const N: usize>(&mut self) -> T {
-        T::from_bytes(&self.read_bytes_from_pc_fixed::<N>())
-    }
-
     pub fn peek_bytes_from_mem(&self, addr: u16, count: usize) -> Vec<u8> {
         let mut result = vec![0; count];
         let mut addr = addr as usize;
@@ -124,7 +297,7 @@ impl Emulator {
     pub fn write_bytes_to_mem(&mut self, addr: u16, bytes: &[u8]) {
         let mut addr = addr as usize;
         for byte in bytes {
-            self.memory[addr] = *byte;
+            self.memory.set(addr, *byte);
             addr = addr.wrapping_add(1) % MEMORY_SIZE;
         }
     }
@@ -137,46 +310,20 @@ impl Emulator {
         self.write_bytes_to_mem(addr, &data.to_bytes())
     }
 
-    pub fn write_bytes_to_code(&mut self, addr: u16, bytes: &[u8]) {
-        let mut addr = addr as usize;
-        for byte in bytes {
-            self.bytecode[addr] = *byte;
-            addr = addr.wrapping_add(1) % BYTECODE_SIZE;
-        }
+    pub fn get_bytecode(&self) -> &PagedBytes {
+        &self.bytecode
     }
 
-    pub fn read_registers_operand(&mut self) -> (RegisterName, RegisterName) {
-        let indices: u8 = self.read_from_pc();
-        let index_lo = indices & 0xf;
-        let index_hi = indices >> 4;
-        (index_lo.into(), index_hi.into())
+    pub fn get_decode_cache(&self) -> &DecodeCache {
+        &self.decode_cache
     }
 
-    pub fn read_address_operand(&mut self) -> u16 {
-        let mode_base: u8 = self.read_from_pc();
-        let mode = mode_base >> 6;
-        let base = self.get_reg_mut((mode_base & 0xf).into());
-        match mode {
-            0b00 => base,
-            0b01 => {
-                let displacement = self.read_from_pc();
-                base.wrapping_add(displacement)
-            }
-            0b10 => {
-                let scale_index: u8 = self.read_from_pc();
-                let index = self.get_reg_mut((scale_index & 0xf).into());
-                let scale = (scale_index >> 4).next_power_of_two() as u16;
-                base.wrapping_add(index.wrapping_mul(scale))
-            }
-            0b11 => {
-                let scale_index: u8 = self.read_from_pc();
-                let index = self.get_reg_mut((scale_index & 0xf).into());
-                let scale = (scale_index >> 4).next_power_of_two() as u16;
-                let displacement = self.read_from_pc();
-                base.wrapping_add(index.wrapping_mul(scale))
-                    .wrapping_add(displacement)
-            }
-            _ => unreachable!(),
+    pub fn write_bytes_to_code(&mut self, addr: u16, bytes: &[u8]) {
+        self.decode_cache.invalidate(addr, bytes.len());
+        let mut addr = addr as usize;
+        for byte in bytes {
+            self.bytecode.set(addr, *byte);
+            addr = addr.wrapping_add(1) % BYTECODE_SIZE;
         }
     }
 
@@ -209,29 +356,44 @@ impl Emulator {
     }
 
     pub fn nasal_demons(&mut self) {
-        let mut rng = rand::thread_rng();
-        match rng.gen_range(1..=100) {
+        let effect = match self.rng.gen_range(1..=100) {
             1..=40 => {
-                let addr = rng.gen();
-                self.write_bytes_to_mem(addr, &[rng.gen()]);
+                let addr = self.rng.gen();
+                let value = self.rng.gen();
+                self.write_bytes_to_mem(addr, &[value]);
+                NasalDemons::WriteMemory { addr, value }
             }
             41..=60 => {
-                let reg = RegisterName::from(rng.gen_range(0..=0xf));
-                self.set_reg(reg, rng.gen());
+                let reg = RegisterName::from(self.rng.gen_range(0..=0xf));
+                let value = self.rng.gen();
+                self.set_reg(reg, value);
+                NasalDemons::WriteRegister {
+                    register: reg.name(),
+                    value,
+                }
             }
             61..=85 => {
-                let reg1 = RegisterName::from(rng.gen_range(0..=0xf));
-                let reg2 = RegisterName::from(rng.gen_range(0..=0xf));
+                let reg1 = RegisterName::from(self.rng.gen_range(0..=0xf));
+                let reg2 = RegisterName::from(self.rng.gen_range(0..=0xf));
                 let value1 = self.get_reg_mut(reg1);
                 let value2 = self.get_reg_mut(reg2);
                 self.set_reg(reg1, value2);
                 self.set_reg(reg2, value1);
+                NasalDemons::SwapRegisters {
+                    register1: reg1.name(),
+                    register2: reg2.name(),
+                }
             }
             86..=100 => {
-                let addr = rng.gen();
-                self.write_bytes_to_code(addr, &[rng.gen()]);
+                let addr = self.rng.gen();
+                let value = self.rng.gen();
+                self.write_bytes_to_code(addr, &[value]);
+                NasalDemons::WriteCode { addr, value }
             }
             _ => unreachable!(),
+        };
+        if let Some(log) = self.nasal_demons_log.as_mut() {
+            log.push(effect);
         }
     }
 
@@ -259,6 +421,63 @@ impl Emulator {
     }
 }
 
+// Reads the operands of the instruction being executed, moving PC past them.
+pub trait OperandReader {
+    fn read_bytes_from_pc_fixed<const N: usize>(&mut self) -> [u8; N];
+    fn get_reg_mut(&mut self, register: RegisterName) -> u16;
+
+    fn read_from_pc<T: FromBytes<Bytes = [u8; N]>, const N: usize>(&mut self) -> T {
+        T::from_bytes(&self.read_bytes_from_pc_fixed::<N>())
+    }
+
+    fn read_registers_operand(&mut self) -> (RegisterName, RegisterName) {
+        let indices: u8 = self.read_from_pc();
+        let index_lo = indices & 0xf;
+        let index_hi = indices >> 4;
+        (index_lo.into(), index_hi.into())
+    }
+
+    fn read_address_operand(&mut self) -> u16 {
+        let mode_base: u8 = self.read_from_pc();
+        let mode = mode_base >> 6;
+        let base = self.get_reg_mut((mode_base & 0xf).into());
+        match mode {
+            0b00 => base,
+            0b01 => {
+                let displacement = self.read_from_pc();
+                base.wrapping_add(displacement)
+            }
+            0b10 => {
+                let scale_index: u8 = self.read_from_pc();
+                let index = self.get_reg_mut((scale_index & 0xf).into());
+                let scale = (scale_index >> 4).next_power_of_two() as u16;
+                base.wrapping_add(index.wrapping_mul(scale))
+            }
+            0b11 => {
+                let scale_index: u8 = self.read_from_pc();
+                let index = self.get_reg_mut((scale_index & 0xf).into());
+                let scale = (scale_index >> 4).next_power_of_two() as u16;
+                let displacement = self.read_from_pc();
+                base.wrapping_add(index.wrapping_mul(scale))
+                    .wrapping_add(displacement)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl OperandReader for Emulator {
+    fn read_bytes_from_pc_fixed<const N: usize>(&mut self) -> [u8; N] {
+        let result = self.peek_bytes_from_pc_fixed();
+        self.increment_pc(N as u16);
+        result
+    }
+
+    fn get_reg_mut(&mut self, register: RegisterName) -> u16 {
+        Emulator::get_reg_mut(self, register)
+    }
+}
+
 pub trait FromBytes {
     type Bytes;
     fn from_bytes(bytes: &Self::Bytes) -> Self;
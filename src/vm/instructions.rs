@@ -1,11 +1,44 @@
+use crate::vm::decode_cache::CachedExecution;
 use crate::vm::emulator::CpuFlag;
 use crate::vm::emulator::Emulator;
+use crate::vm::emulator::OperandReader;
 use crate::vm::register::RegisterName;
 
+use serde::Serialize;
+use std::fmt;
+
 pub trait Instruction {
     fn get_opcode(&self) -> u8;
     fn get_latency(&self) -> usize;
+    fn get_mnemonic(&self) -> &'static str;
+    fn get_operands(&self) -> &'static [Operand];
+    fn get_description(&self) -> &'static str;
     fn execute(&self, emulator: &mut Emulator);
+    // Same as `execute`, with the operands already fetched.
+    fn execute_cached(&self, emulator: &mut Emulator, address: u16, code: u128);
+}
+
+// Operands in the order they are encoded after the opcode byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Operand {
+    Reg,
+    RegReg,
+    Imm8,
+    Imm16,
+    Mem,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Operand::Reg => "reg",
+            Operand::RegReg => "reg, reg",
+            Operand::Imm8 => "imm8",
+            Operand::Imm16 => "imm16",
+            Operand::Mem => "mem",
+        };
+        f.write_str(text)
+    }
 }
 
 pub struct OpcodeTable {
@@ -30,24 +63,56 @@ impl OpcodeTable {
     pub fn get_instruction(&self, opcode: u8) -> &'static dyn Instruction {
         self.table[opcode as usize]
     }
+    pub fn instructions(&self) -> impl Iterator<Item = &'static dyn Instruction> + '_ {
+        self.table
+            .iter()
+            .zip(self._used.iter())
+            .filter(|(_, &used)| used)
+            .map(|(&instruction, _)| instruction)
+    }
+    pub fn get_instructions_by_mnemonic<'a>(
+        &'a self,
+        mnemonic: &'a str,
+    ) -> impl Iterator<Item = &'static dyn Instruction> + 'a {
+        self.instructions()
+            .filter(move |instruction| instruction.get_mnemonic().eq_ignore_ascii_case(mnemonic))
+    }
 }
 
 macro_rules! instruction_category {
     ($category_fn:ident() => [
-        $($mnemonic:ident<$opcode:literal, $latency:literal> ($arg:ident) $exec:tt),* $(,)?
+        $(
+            $(#[doc = $doc:literal])*
+            $name:ident<$opcode:literal, $latency:literal>[$mnemonic:literal $(, $operand:ident)*]
+            ($arg:ident) $exec:tt
+        ),* $(,)?
     ]) => {
         $(
-            struct $mnemonic {}
-            impl Instruction for $mnemonic {
+            $(#[doc = $doc])*
+            struct $name {}
+            impl Instruction for $name {
                 fn get_opcode(&self) -> u8 {
                     $opcode
                 }
                 fn get_latency(&self) -> usize {
                     $latency
                 }
+                fn get_mnemonic(&self) -> &'static str {
+                    $mnemonic
+                }
+                fn get_operands(&self) -> &'static [Operand] {
+                    &[$(Operand::$operand),*]
+                }
+                fn get_description(&self) -> &'static str {
+                    concat!($($doc),*).trim()
+                }
                 fn execute(&self, $arg: &mut Emulator) {
                     $exec
                 }
+                fn execute_cached(&self, emulator: &mut Emulator, address: u16, code: u128) {
+                    let execute = |$arg: &mut CachedExecution| $exec;
+                    execute(&mut CachedExecution::new(emulator, address, code));
+                }
             }
         )*
         impl OpcodeTable {
@@ -57,7 +122,7 @@ macro_rules! instruction_category {
                         panic!(concat!("duplicate opcode ", stringify!($opcode)));
                     }
                     self._used[$opcode as usize] = true;
-                    self.table[$opcode as usize] = &$mnemonic {};
+                    self.table[$opcode as usize] = &$name {};
                 )*
                 self
             }
@@ -74,70 +139,82 @@ pub const OPCODE_TABLE: OpcodeTable = OpcodeTable::new()
 
 instruction_category! {
     make_data_instructions() => [
-        MovReg8<0x23, 3>(emulator) {
+        /// Sets dst to the lower 8 bits of src.
+        MovReg8<0x23, 3>["mov8", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src) as u8;
             emulator.set_reg(dst, value as u16);
         },
-        MovReg16<0x22, 3>(emulator) {
+        /// Sets dst to src.
+        MovReg16<0x22, 3>["mov16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src);
             emulator.set_reg(dst, value);
         },
-        MovImm8<0x21, 3>(emulator) {
+        /// Sets dst to an 8-bit immediate value.
+        MovImm8<0x21, 3>["mov8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let value: u8 = emulator.read_from_pc();
             emulator.set_reg(dst, value as u16);
         },
-        MovImm16<0x20, 4>(emulator) {
+        /// Sets dst to a 16-bit immediate value.
+        MovImm16<0x20, 4>["mov16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let value = emulator.read_from_pc();
             emulator.set_reg(dst, value);
         },
 
-        Load8<0x25, 24>(emulator) {
+        /// Loads a byte from memory into dst.
+        Load8<0x25, 24>["load8", Reg, Mem](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let addr = emulator.read_address_operand();
             let value: u8 = emulator.peek_from_mem(addr);
             emulator.set_reg(dst, value as u16);
         },
-        Load16<0x24, 28>(emulator) {
+        /// Loads a 16-bit value from memory into dst.
+        Load16<0x24, 28>["load16", Reg, Mem](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let addr = emulator.read_address_operand();
             let value = emulator.peek_from_mem(addr);
             emulator.set_reg(dst, value);
         },
 
-        StoreReg8<0x29, 22>(emulator) {
+        /// Stores the lower 8 bits of src into memory.
+        StoreReg8<0x29, 22>["store8", Mem, Reg](emulator) {
             let addr = emulator.read_address_operand();
             let (src, _) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src) as u8;
             emulator.write_to_mem(addr, value);
         },
-        StoreReg16<0x28, 26>(emulator) {
+        /// Stores src into memory.
+        StoreReg16<0x28, 26>["store16", Mem, Reg](emulator) {
             let addr = emulator.read_address_operand();
             let (src, _) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src);
             emulator.write_to_mem(addr, value);
         },
-        StoreImm8<0x27, 24>(emulator) {
+        /// Stores an 8-bit immediate value into memory.
+        StoreImm8<0x27, 24>["store8", Mem, Imm8](emulator) {
             let addr = emulator.read_address_operand();
             let value: u8 = emulator.read_from_pc();
             emulator.write_to_mem(addr, value);
         },
-        StoreImm16<0x26, 28>(emulator) {
+        /// Stores a 16-bit immediate value into memory.
+        StoreImm16<0x26, 28>["store16", Mem, Imm16](emulator) {
             let addr = emulator.read_address_operand();
             let value: u16 = emulator.read_from_pc();
             emulator.write_to_mem(addr, value);
         },
 
-        Lea<0x8d, 5>(emulator) {
+        /// Sets dst to a memory address.
+        Lea<0x8d, 5>["lea", Reg, Mem](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let addr = emulator.read_address_operand();
             emulator.set_reg(dst, addr);
         },
 
-        Xchg<0x92, 3>(emulator) {
+        /// Exchanges the values of two registers.
+        Xchg<0x92, 3>["xchg", RegReg](emulator) {
             let (reg1, reg2) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(reg1);
             let val2 = emulator.get_reg_mut(reg2);
@@ -145,13 +222,15 @@ instruction_category! {
             emulator.set_reg(reg2, val1);
         },
 
-        Sex<0x62, 3>(emulator) {
+        /// Sign extends the lower 8 bits of a register to 16 bits.
+        Sex<0x62, 3>["sex16", Reg](emulator) {
             let (reg, _) = emulator.read_registers_operand();
             let val = emulator.get_reg_mut(reg) as i8;
             emulator.set_reg(reg, val as u16);
         },
 
-        CmovaReg16<0xd6, 8>(emulator) {
+        /// Sets dst to src if above (!CF && !ZF).
+        CmovaReg16<0xd6, 8>["cmova", RegReg](emulator) {
             let cf = emulator.get_cpu_flag(CpuFlag::Carry);
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             if !cf && !zf {
@@ -160,7 +239,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovaImm16<0xe6, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if above (!CF && !ZF).
+        CmovaImm16<0xe6, 9>["cmova16", Reg, Imm16](emulator) {
             let cf = emulator.get_cpu_flag(CpuFlag::Carry);
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             if !cf && !zf {
@@ -169,7 +249,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmovaeReg16<0xd7, 8>(emulator) {
+        /// Sets dst to src if above or equal (!CF).
+        CmovaeReg16<0xd7, 8>["cmovae", RegReg](emulator) {
             let cf = emulator.get_cpu_flag(CpuFlag::Carry);
             if !cf {
                 MovReg16 {}.execute(emulator);
@@ -177,7 +258,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovaeImm16<0xe7, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if above or equal (!CF).
+        CmovaeImm16<0xe7, 9>["cmovae16", Reg, Imm16](emulator) {
             let cf = emulator.get_cpu_flag(CpuFlag::Carry);
             if !cf {
                 MovImm16 {}.execute(emulator);
@@ -185,7 +267,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmovbReg16<0xd8, 8>(emulator) {
+        /// Sets dst to src if below (CF).
+        CmovbReg16<0xd8, 8>["cmovb", RegReg](emulator) {
             let cf = emulator.get_cpu_flag(CpuFlag::Carry);
             if cf {
                 MovReg16 {}.execute(emulator);
@@ -193,7 +276,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovbImm16<0xe8, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if below (CF).
+        CmovbImm16<0xe8, 9>["cmovb16", Reg, Imm16](emulator) {
             let cf = emulator.get_cpu_flag(CpuFlag::Carry);
             if cf {
                 MovImm16 {}.execute(emulator);
@@ -201,7 +285,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmovbeReg16<0xd9, 8>(emulator) {
+        /// Sets dst to src if below or equal (CF || ZF).
+        CmovbeReg16<0xd9, 8>["cmovbe", RegReg](emulator) {
             let cf = emulator.get_cpu_flag(CpuFlag::Carry);
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             if cf || zf {
@@ -210,7 +295,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovbeImm16<0xe9, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if below or equal (CF || ZF).
+        CmovbeImm16<0xe9, 9>["cmovbe16", Reg, Imm16](emulator) {
             let cf = emulator.get_cpu_flag(CpuFlag::Carry);
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             if cf || zf {
@@ -219,7 +305,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmoveReg16<0xda, 8>(emulator) {
+        /// Sets dst to src if equal (ZF).
+        CmoveReg16<0xda, 8>["cmove", RegReg](emulator) {
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             if zf {
                 MovReg16 {}.execute(emulator);
@@ -227,7 +314,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmoveImm16<0xea, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if equal (ZF).
+        CmoveImm16<0xea, 9>["cmove16", Reg, Imm16](emulator) {
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             if zf {
                 MovImm16 {}.execute(emulator);
@@ -235,7 +323,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmovneReg16<0xdb, 8>(emulator) {
+        /// Sets dst to src if not equal (!ZF).
+        CmovneReg16<0xdb, 8>["cmovne", RegReg](emulator) {
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             if !zf {
                 MovReg16 {}.execute(emulator);
@@ -243,7 +332,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovneImm16<0xeb, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if not equal (!ZF).
+        CmovneImm16<0xeb, 9>["cmovne16", Reg, Imm16](emulator) {
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             if !zf {
                 MovImm16 {}.execute(emulator);
@@ -251,7 +341,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmovgReg16<0xdc, 8>(emulator) {
+        /// Sets dst to src if greater (!ZF && SF == OF).
+        CmovgReg16<0xdc, 8>["cmovg", RegReg](emulator) {
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             let sf = emulator.get_cpu_flag(CpuFlag::Sign);
             let of = emulator.get_cpu_flag(CpuFlag::Overflow);
@@ -261,7 +352,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovgImm16<0xec, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if greater (!ZF && SF == OF).
+        CmovgImm16<0xec, 9>["cmovg16", Reg, Imm16](emulator) {
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             let sf = emulator.get_cpu_flag(CpuFlag::Sign);
             let of = emulator.get_cpu_flag(CpuFlag::Overflow);
@@ -271,7 +363,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmovgeReg16<0xdd, 8>(emulator) {
+        /// Sets dst to src if greater or equal (SF == OF).
+        CmovgeReg16<0xdd, 8>["cmovge", RegReg](emulator) {
             let sf = emulator.get_cpu_flag(CpuFlag::Sign);
             let of = emulator.get_cpu_flag(CpuFlag::Overflow);
             if sf == of {
@@ -280,7 +373,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovgeImm16<0xed, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if greater or equal (SF == OF).
+        CmovgeImm16<0xed, 9>["cmovge16", Reg, Imm16](emulator) {
             let sf = emulator.get_cpu_flag(CpuFlag::Sign);
             let of = emulator.get_cpu_flag(CpuFlag::Overflow);
             if sf == of {
@@ -289,7 +383,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmovlReg16<0xde, 8>(emulator) {
+        /// Sets dst to src if less (SF != OF).
+        CmovlReg16<0xde, 8>["cmovl", RegReg](emulator) {
             let sf = emulator.get_cpu_flag(CpuFlag::Sign);
             let of = emulator.get_cpu_flag(CpuFlag::Overflow);
             if sf != of {
@@ -298,7 +393,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovlImm16<0xee, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if less (SF != OF).
+        CmovlImm16<0xee, 9>["cmovl16", Reg, Imm16](emulator) {
             let sf = emulator.get_cpu_flag(CpuFlag::Sign);
             let of = emulator.get_cpu_flag(CpuFlag::Overflow);
             if sf != of {
@@ -307,7 +403,8 @@ instruction_category! {
                 emulator.increment_pc(3);
             }
         },
-        CmovleReg16<0xdf, 8>(emulator) {
+        /// Sets dst to src if less or equal (ZF || SF != OF).
+        CmovleReg16<0xdf, 8>["cmovle", RegReg](emulator) {
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             let sf = emulator.get_cpu_flag(CpuFlag::Sign);
             let of = emulator.get_cpu_flag(CpuFlag::Overflow);
@@ -317,7 +414,8 @@ instruction_category! {
                 emulator.increment_pc(1);
             }
         },
-        CmovleImm16<0xef, 9>(emulator) {
+        /// Sets dst to a 16-bit immediate value if less or equal (ZF || SF != OF).
+        CmovleImm16<0xef, 9>["cmovle16", Reg, Imm16](emulator) {
             let zf = emulator.get_cpu_flag(CpuFlag::Zero);
             let sf = emulator.get_cpu_flag(CpuFlag::Sign);
             let of = emulator.get_cpu_flag(CpuFlag::Overflow);
@@ -328,41 +426,47 @@ instruction_category! {
             }
         },
 
-        PushReg8<0x50, 24>(emulator) {
+        /// Pushes the lower 8 bits of a register onto the stack.
+        PushReg8<0x50, 24>["push8", Reg](emulator) {
             let (src, _) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src) as u8;
             let sp_value = emulator.get_reg_mut(RegisterName::SP);
             emulator.write_to_mem(sp_value, value);
             emulator.set_reg(RegisterName::SP, sp_value.wrapping_add(1));
         },
-        PushReg16<0x51, 28>(emulator) {
+        /// Pushes a register onto the stack.
+        PushReg16<0x51, 28>["push16", Reg](emulator) {
             let (src, _) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src);
             let sp_value = emulator.get_reg_mut(RegisterName::SP);
             emulator.write_to_mem(sp_value, value);
             emulator.set_reg(RegisterName::SP, sp_value.wrapping_add(2));
         },
-        PushImm8<0x52, 26>(emulator) {
+        /// Pushes an 8-bit immediate value onto the stack.
+        PushImm8<0x52, 26>["push8", Imm8](emulator) {
             let value: u8 = emulator.read_from_pc();
             let sp_value = emulator.get_reg_mut(RegisterName::SP);
             emulator.write_to_mem(sp_value, value);
             emulator.set_reg(RegisterName::SP, sp_value.wrapping_add(1));
         },
-        PushImm16<0x53, 30>(emulator) {
+        /// Pushes a 16-bit immediate value onto the stack.
+        PushImm16<0x53, 30>["push16", Imm16](emulator) {
             let value: u16 = emulator.read_from_pc();
             let sp_value = emulator.get_reg_mut(RegisterName::SP);
             emulator.write_to_mem(sp_value, value);
             emulator.set_reg(RegisterName::SP, sp_value.wrapping_add(2));
         },
 
-        Pop8<0x60, 26>(emulator) {
+        /// Pops a byte from the stack into a register.
+        Pop8<0x60, 26>["pop8", Reg](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let sp_value = emulator.get_reg_mut(RegisterName::SP);
             emulator.set_reg(RegisterName::SP, sp_value.wrapping_sub(1));
             let value: u8 = emulator.peek_from_mem(sp_value.wrapping_sub(1));
             emulator.set_reg(dst, value as u16);
         },
-        Pop16<0x61, 30>(emulator) {
+        /// Pops a 16-bit value from the stack into a register.
+        Pop16<0x61, 30>["pop16", Reg](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let sp_value = emulator.get_reg_mut(RegisterName::SP);
             emulator.set_reg(RegisterName::SP, sp_value.wrapping_sub(2));
@@ -370,7 +474,8 @@ instruction_category! {
             emulator.set_reg(dst, value);
         },
 
-        Memcpy<0x71, 512>(emulator) {
+        /// Copies n + 1 bytes from src to dst, where n is the lower 8 bits of a register.
+        Memcpy<0x71, 512>["memcpy", Reg, Mem, Mem](emulator) {
             let (nreg, _) = emulator.read_registers_operand();
             let n = (emulator.get_reg_mut(nreg) & 0xff) + 1;
             let dst = emulator.read_address_operand();
@@ -383,7 +488,8 @@ instruction_category! {
             emulator.write_bytes_to_mem(dst, &bytes);
         },
 
-        MemsetReg<0x81, 384>(emulator) {
+        /// Sets n + 1 bytes at an address to the lower 8 bits of a register.
+        MemsetReg<0x81, 384>["memset", Reg, Mem, Reg](emulator) {
             let (nreg, _) = emulator.read_registers_operand();
             let n = (emulator.get_reg_mut(nreg) & 0xff) + 1;
             let dst = emulator.read_address_operand();
@@ -392,7 +498,8 @@ instruction_category! {
             let bytes = vec![b; n as usize];
             emulator.write_bytes_to_mem(dst, &bytes);
         },
-        MemsetImm8<0x82, 384>(emulator) {
+        /// Sets n + 1 bytes at an address to an 8-bit immediate value.
+        MemsetImm8<0x82, 384>["memset", Reg, Mem, Imm8](emulator) {
             let (nreg, _) = emulator.read_registers_operand();
             let n = (emulator.get_reg_mut(nreg) & 0xff) + 1;
             let dst = emulator.read_address_operand();
@@ -405,7 +512,8 @@ instruction_category! {
 
 instruction_category! {
     make_arithmetic_instructions() => [
-        AddReg16<0xc4, 4>(emulator) {
+        /// Adds two integers, dst += src.
+        AddReg16<0xc4, 4>["add16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -414,7 +522,8 @@ instruction_category! {
             emulator.set_arithmetic_flags(sum, carry, overflow);
             emulator.set_reg(dst, sum);
         },
-        AddImm8<0xc5, 4>(emulator) {
+        /// Adds two integers, dst += src, with a sign extended 8-bit immediate value.
+        AddImm8<0xc5, 4>["add8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<i8, 1>() as u16;
@@ -423,7 +532,8 @@ instruction_category! {
             emulator.set_arithmetic_flags(sum, carry, overflow);
             emulator.set_reg(dst, sum);
         },
-        AddImm16<0xc6, 4>(emulator) {
+        /// Adds two integers, dst += src, with a 16-bit immediate value.
+        AddImm16<0xc6, 4>["add16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc();
@@ -433,7 +543,8 @@ instruction_category! {
             emulator.set_reg(dst, sum);
         },
 
-        SubReg16<0xb4, 4>(emulator) {
+        /// Subtracts two integers, dst -= src.
+        SubReg16<0xb4, 4>["sub16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -442,7 +553,8 @@ instruction_category! {
             emulator.set_arithmetic_flags(sum, carry, overflow);
             emulator.set_reg(dst, sum);
         },
-        SubImm8<0xb5, 4>(emulator) {
+        /// Subtracts two integers, dst -= src, with a sign extended 8-bit immediate value.
+        SubImm8<0xb5, 4>["sub8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<i8, 1>() as u16;
@@ -451,7 +563,8 @@ instruction_category! {
             emulator.set_arithmetic_flags(sum, carry, overflow);
             emulator.set_reg(dst, sum);
         },
-        SubImm16<0xb6, 4>(emulator) {
+        /// Subtracts two integers, dst -= src, with a 16-bit immediate value.
+        SubImm16<0xb6, 4>["sub16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc();
@@ -461,7 +574,8 @@ instruction_category! {
             emulator.set_reg(dst, sum);
         },
 
-        MulReg16<0xa4, 18>(emulator) {
+        /// Multiplies two unsigned integers, (dst2, dst1) = dst1 * src.
+        MulReg16<0xa4, 18>["mul16", RegReg, Reg](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let (src, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst1);
@@ -471,7 +585,8 @@ instruction_category! {
             emulator.set_reg(dst1, prod_lo);
             emulator.set_reg(dst2, prod_hi);
         },
-        MulImm8<0xa5, 16>(emulator) {
+        /// Multiplies two unsigned integers, (dst2, dst1) = dst1 * src, with a zero extended 8-bit immediate value.
+        MulImm8<0xa5, 16>["mul8", RegReg, Imm8](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst1);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -480,7 +595,8 @@ instruction_category! {
             emulator.set_reg(dst1, prod_lo);
             emulator.set_reg(dst2, prod_hi);
         },
-        MulImm16<0xa6, 18>(emulator) {
+        /// Multiplies two unsigned integers, (dst2, dst1) = dst1 * src, with a 16-bit immediate value.
+        MulImm16<0xa6, 18>["mul16", RegReg, Imm16](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst1);
             let val2 = emulator.read_from_pc();
@@ -491,7 +607,8 @@ instruction_category! {
         },
 
 
-        MulloReg16<0x94, 12>(emulator) {
+        /// Multiplies two integers modulo 2^16, dst *= src.
+        MulloReg16<0x94, 12>["mullo16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -500,7 +617,8 @@ instruction_category! {
             emulator.set_arithmetic_flags(prod, carry, overflow);
             emulator.set_reg(dst, prod);
         },
-        MulloImm8<0x95, 10>(emulator) {
+        /// Multiplies two integers modulo 2^16, dst *= src, with a zero extended 8-bit immediate value.
+        MulloImm8<0x95, 10>["mullo8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -509,7 +627,8 @@ instruction_category! {
             emulator.set_arithmetic_flags(prod, carry, overflow);
             emulator.set_reg(dst, prod);
         },
-        MulloImm16<0x96, 12>(emulator) {
+        /// Multiplies two integers modulo 2^16, dst *= src, with a 16-bit immediate value.
+        MulloImm16<0x96, 12>["mullo16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc();
@@ -519,7 +638,8 @@ instruction_category! {
             emulator.set_reg(dst, prod);
         },
 
-        ImulReg16<0x84, 18>(emulator) {
+        /// Multiplies two signed integers, (dst2, dst1) = dst1 * src.
+        ImulReg16<0x84, 18>["imul16", RegReg, Reg](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let (src, _) = emulator.read_registers_operand();
             let val1: i16 = emulator.get_reg_mut(dst1) as i16;
@@ -533,7 +653,8 @@ instruction_category! {
             emulator.set_reg(dst1, prod_lo);
             emulator.set_reg(dst2, prod_hi);
         },
-        ImulImm8<0x85, 16>(emulator) {
+        /// Multiplies two signed integers, (dst2, dst1) = dst1 * src, with a sign extended 8-bit immediate value.
+        ImulImm8<0x85, 16>["imul8", RegReg, Imm8](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let val1: i16 = emulator.get_reg_mut(dst1) as i16;
             let val2: i16 = emulator.read_from_pc::<i8, 1>() as i16;
@@ -546,7 +667,8 @@ instruction_category! {
             emulator.set_reg(dst1, prod_lo);
             emulator.set_reg(dst2, prod_hi);
         },
-        ImulImm16<0x86, 18>(emulator) {
+        /// Multiplies two signed integers, (dst2, dst1) = dst1 * src, with a 16-bit immediate value.
+        ImulImm16<0x86, 18>["imul16", RegReg, Imm16](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let val1: i16 = emulator.get_reg_mut(dst1) as i16;
             let val2: i16 = emulator.read_from_pc();
@@ -560,7 +682,8 @@ instruction_category! {
             emulator.set_reg(dst2, prod_hi);
         },
 
-        DivReg16<0x74, 30>(emulator) {
+        /// Divides two unsigned integers, dst1 = dst1 / src and dst2 = dst1 % src.
+        DivReg16<0x74, 30>["div16", RegReg, Reg](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let (src, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst1);
@@ -574,7 +697,8 @@ instruction_category! {
             emulator.set_reg(dst1, quot);
             emulator.set_reg(dst2, rem);
         },
-        DivImm8<0x75, 24>(emulator) {
+        /// Divides two unsigned integers, dst1 = dst1 / src and dst2 = dst1 % src, with a zero extended 8-bit immediate value.
+        DivImm8<0x75, 24>["div8", RegReg, Imm8](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst1);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -587,7 +711,8 @@ instruction_category! {
             emulator.set_reg(dst1, quot);
             emulator.set_reg(dst2, rem);
         },
-        DivImm16<0x76, 30>(emulator) {
+        /// Divides two unsigned integers, dst1 = dst1 / src and dst2 = dst1 % src, with a 16-bit immediate value.
+        DivImm16<0x76, 30>["div16", RegReg, Imm16](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst1);
             let val2: u16 = emulator.read_from_pc();
@@ -601,7 +726,8 @@ instruction_category! {
             emulator.set_reg(dst2, rem);
         },
 
-        IdivReg16<0x64, 30>(emulator) {
+        /// Divides two signed integers, dst1 = dst1 / src and dst2 = dst1 % src.
+        IdivReg16<0x64, 30>["idiv16", RegReg, Reg](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let (src, _) = emulator.read_registers_operand();
             let val1: i16 = emulator.get_reg_mut(dst1) as i16;
@@ -615,7 +741,8 @@ instruction_category! {
             emulator.set_reg(dst1, quot as u16);
             emulator.set_reg(dst2, rem as u16);
         },
-        IdivImm8<0x65, 24>(emulator) {
+        /// Divides two signed integers, dst1 = dst1 / src and dst2 = dst1 % src, with a sign extended 8-bit immediate value.
+        IdivImm8<0x65, 24>["idiv8", RegReg, Imm8](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let val1: i16 = emulator.get_reg_mut(dst1) as i16;
             let val2: i16 = emulator.read_from_pc::<i8, 1>() as i16;
@@ -628,7 +755,8 @@ instruction_category! {
             emulator.set_reg(dst1, quot as u16);
             emulator.set_reg(dst2, rem as u16);
         },
-        IdivImm16<0x66, 30>(emulator) {
+        /// Divides two signed integers, dst1 = dst1 / src and dst2 = dst1 % src, with a 16-bit immediate value.
+        IdivImm16<0x66, 30>["idiv16", RegReg, Imm16](emulator) {
             let (dst1, dst2) = emulator.read_registers_operand();
             let val1: i16 = emulator.get_reg_mut(dst1) as i16;
             let val2: i16 = emulator.read_from_pc();
@@ -642,7 +770,8 @@ instruction_category! {
             emulator.set_reg(dst2, rem as u16);
         },
 
-        Neg<0x7f, 3>(emulator) {
+        /// Negates a signed integer.
+        Neg<0x7f, 3>["neg", Reg](emulator) {
             let (reg, _) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(reg);
             let (result, carry) = value.overflowing_neg();
@@ -651,7 +780,8 @@ instruction_category! {
             emulator.set_reg(reg, result);
         },
 
-        Abs<0x8f, 3>(emulator) {
+        /// Computes the absolute value of a signed integer.
+        Abs<0x8f, 3>["abs", Reg](emulator) {
             let (reg, _) = emulator.read_registers_operand();
             let value: i16 = emulator.get_reg_mut(reg) as i16;
             let (result, overflow) = value.overflowing_abs();
@@ -659,7 +789,8 @@ instruction_category! {
             emulator.set_reg(reg, result as u16);
         },
 
-        CmpReg16<0x54, 4>(emulator) {
+        /// Sets the flags according to dst - src.
+        CmpReg16<0x54, 4>["cmp16", RegReg](emulator) {
             let (reg1, reg2) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(reg1);
             let val2 = emulator.get_reg_mut(reg2);
@@ -667,7 +798,8 @@ instruction_category! {
             let (_, overflow) = (val1 as i16).overflowing_sub(val2 as i16);
             emulator.set_arithmetic_flags(sum, carry, overflow);
         },
-        CmpImm8<0x55, 4>(emulator) {
+        /// Sets the flags according to dst - src, with a sign extended 8-bit immediate value.
+        CmpImm8<0x55, 4>["cmp8", Reg, Imm8](emulator) {
             let (reg, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(reg);
             let val2 = emulator.read_from_pc::<i8, 1>() as u16;
@@ -675,7 +807,8 @@ instruction_category! {
             let (_, overflow) = (val1 as i16).overflowing_sub(val2 as i16);
             emulator.set_arithmetic_flags(sum, carry, overflow);
         },
-        CmpImm16<0x56, 4>(emulator) {
+        /// Sets the flags according to dst - src, with a 16-bit immediate value.
+        CmpImm16<0x56, 4>["cmp16", Reg, Imm16](emulator) {
             let (reg, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(reg);
             let val2 = emulator.read_from_pc();
@@ -688,14 +821,16 @@ instruction_category! {
 
 instruction_category! {
     make_logical_instructions() => [
-        Not<0x80, 3>(emulator) {
+        /// Bitwise NOT of a register.
+        Not<0x80, 3>["not", Reg](emulator) {
             let (reg, _) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(reg);
             let result = !value;
             emulator.set_reg(reg, result);
         },
 
-        AndReg16<0x37, 4>(emulator) {
+        /// Bitwise AND, dst &= src.
+        AndReg16<0x37, 4>["and16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -703,7 +838,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        AndImm8<0x36, 4>(emulator) {
+        /// Bitwise AND, dst &= src, with a zero extended 8-bit immediate value.
+        AndImm8<0x36, 4>["and8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -711,7 +847,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        AndImm16<0x46, 4>(emulator) {
+        /// Bitwise AND, dst &= src, with a 16-bit immediate value.
+        AndImm16<0x46, 4>["and16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u16 = emulator.read_from_pc();
@@ -720,7 +857,8 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        OrReg16<0x48, 4>(emulator) {
+        /// Bitwise OR, dst |= src.
+        OrReg16<0x48, 4>["or16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -728,7 +866,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        OrImm8<0x47, 4>(emulator) {
+        /// Bitwise OR, dst |= src, with a zero extended 8-bit immediate value.
+        OrImm8<0x47, 4>["or8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -736,7 +875,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        OrImm16<0x57, 4>(emulator) {
+        /// Bitwise OR, dst |= src, with a 16-bit immediate value.
+        OrImm16<0x57, 4>["or16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u16 = emulator.read_from_pc();
@@ -745,7 +885,8 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        XorReg16<0x59, 4>(emulator) {
+        /// Bitwise XOR, dst ^= src.
+        XorReg16<0x59, 4>["xor16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -753,7 +894,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        XorImm8<0x58, 4>(emulator) {
+        /// Bitwise XOR, dst ^= src, with a zero extended 8-bit immediate value.
+        XorImm8<0x58, 4>["xor8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -761,7 +903,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        XorImm16<0x68, 4>(emulator) {
+        /// Bitwise XOR, dst ^= src, with a 16-bit immediate value.
+        XorImm16<0x68, 4>["xor16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u16 = emulator.read_from_pc();
@@ -770,7 +913,8 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        NandReg16<0x6a, 4>(emulator) {
+        /// Bitwise NAND, dst = ~(dst & src).
+        NandReg16<0x6a, 4>["nand16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -778,7 +922,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        NandImm8<0x69, 4>(emulator) {
+        /// Bitwise NAND, dst = ~(dst & src), with a zero extended 8-bit immediate value.
+        NandImm8<0x69, 4>["nand8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -786,7 +931,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        NandImm16<0x79, 4>(emulator) {
+        /// Bitwise NAND, dst = ~(dst & src), with a 16-bit immediate value.
+        NandImm16<0x79, 4>["nand16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u16 = emulator.read_from_pc();
@@ -795,7 +941,8 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        NorReg16<0x7b, 4>(emulator) {
+        /// Bitwise NOR, dst = ~(dst | src).
+        NorReg16<0x7b, 4>["nor16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -803,7 +950,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        NorImm8<0x7a, 4>(emulator) {
+        /// Bitwise NOR, dst = ~(dst | src), with a zero extended 8-bit immediate value.
+        NorImm8<0x7a, 4>["nor8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -811,7 +959,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        NorImm16<0x8a, 4>(emulator) {
+        /// Bitwise NOR, dst = ~(dst | src), with a 16-bit immediate value.
+        NorImm16<0x8a, 4>["nor16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u16 = emulator.read_from_pc();
@@ -820,7 +969,8 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        XnorReg16<0x8c, 4>(emulator) {
+        /// Bitwise XNOR, dst = ~(dst ^ src).
+        XnorReg16<0x8c, 4>["xnor16", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -828,7 +978,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        XnorImm8<0x8b, 4>(emulator) {
+        /// Bitwise XNOR, dst = ~(dst ^ src), with a zero extended 8-bit immediate value.
+        XnorImm8<0x8b, 4>["xnor8", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.read_from_pc::<u8, 1>() as u16;
@@ -836,7 +987,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        XnorImm16<0x9b, 4>(emulator) {
+        /// Bitwise XNOR, dst = ~(dst ^ src), with a 16-bit immediate value.
+        XnorImm16<0x9b, 4>["xnor16", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u16 = emulator.read_from_pc();
@@ -845,7 +997,8 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        ShrReg16<0x9d, 4>(emulator) {
+        /// Unsigned right shift, dst >>= src.
+        ShrReg16<0x9d, 4>["shr", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -857,7 +1010,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        ShrImm8<0x9c, 4>(emulator) {
+        /// Unsigned right shift by an immediate value.
+        ShrImm8<0x9c, 4>["shr", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u8 = emulator.read_from_pc();
@@ -870,7 +1024,8 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        SarReg16<0xae, 4>(emulator) {
+        /// Signed right shift, dst >>= src.
+        SarReg16<0xae, 4>["sar", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1: i16 = emulator.get_reg_mut(dst) as i16;
             let val2 = emulator.get_reg_mut(src);
@@ -882,7 +1037,8 @@ instruction_category! {
             emulator.set_logical_flags(result as u16);
             emulator.set_reg(dst, result as u16);
         },
-        SarImm8<0xad, 4>(emulator) {
+        /// Signed right shift by an immediate value.
+        SarImm8<0xad, 4>["sar", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1: i16 = emulator.get_reg_mut(dst) as i16;
             let val2: u8 = emulator.read_from_pc();
@@ -895,7 +1051,8 @@ instruction_category! {
             emulator.set_reg(dst, result as u16);
         },
 
-        ShlReg16<0xbf, 4>(emulator) {
+        /// Left shift, dst <<= src.
+        ShlReg16<0xbf, 4>["shl", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -907,7 +1064,8 @@ instruction_category! {
             emulator.set_logical_flags(result);
             emulator.set_reg(dst, result);
         },
-        ShlImm8<0xbe, 4>(emulator) {
+        /// Left shift by an immediate value.
+        ShlImm8<0xbe, 4>["shl", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u8 = emulator.read_from_pc();
@@ -920,35 +1078,40 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        Ctz<0xf0, 4>(emulator) {
+        /// Sets dst to the number of trailing zero bits of src.
+        Ctz<0xf0, 4>["ctz", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src);
             let result = value.trailing_zeros();
             emulator.set_reg(dst, result as u16);
         },
 
-        Clz<0xe0, 4>(emulator) {
+        /// Sets dst to the number of leading zero bits of src.
+        Clz<0xe0, 4>["clz", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src);
             let result = value.leading_zeros();
             emulator.set_reg(dst, result as u16);
         },
 
-        Popcnt<0xd0, 4>(emulator) {
+        /// Sets dst to the number of one bits of src.
+        Popcnt<0xd0, 4>["popcnt", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src);
             let result = value.count_ones();
             emulator.set_reg(dst, result as u16);
         },
 
-        RolReg16<0xc0, 4>(emulator) {
+        /// Rotates dst to the left by src bits.
+        RolReg16<0xc0, 4>["rol", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
             let result = val1.rotate_left(val2 as u32);
             emulator.set_reg(dst, result);
         },
-        RolImm8<0xc1, 4>(emulator) {
+        /// Rotates dst to the left by an immediate number of bits.
+        RolImm8<0xc1, 4>["rol", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u8 = emulator.read_from_pc();
@@ -956,14 +1119,16 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        RorReg16<0xb0, 4>(emulator) {
+        /// Rotates dst to the right by src bits.
+        RorReg16<0xb0, 4>["ror", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
             let result = val1.rotate_right(val2 as u32);
             emulator.set_reg(dst, result);
         },
-        RorImm8<0xb1, 4>(emulator) {
+        /// Rotates dst to the right by an immediate number of bits.
+        RorImm8<0xb1, 4>["ror", Reg, Imm8](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u8 = emulator.read_from_pc();
@@ -971,14 +1136,16 @@ instruction_category! {
             emulator.set_reg(dst, result);
         },
 
-        Bswap<0xa0, 3>(emulator) {
+        /// Swaps the upper and lower 8 bits of a register.
+        Bswap<0xa0, 3>["bswap", Reg](emulator) {
             let (reg, _) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(reg);
             let result = value.swap_bytes();
             emulator.set_reg(reg, result);
         },
 
-        PextReg16<0x90, 4>(emulator) {
+        /// Extracts the bits of dst selected by the mask in src.
+        PextReg16<0x90, 4>["pext", RegReg](emulator) {
             let (dst, src) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2 = emulator.get_reg_mut(src);
@@ -990,7 +1157,8 @@ instruction_category! {
             }
             emulator.set_reg(dst, result);
         },
-        PextImm16<0x91, 4>(emulator) {
+        /// Extracts the bits of dst selected by a 16-bit immediate mask.
+        PextImm16<0x91, 4>["pext", Reg, Imm16](emulator) {
             let (dst, _) = emulator.read_registers_operand();
             let val1 = emulator.get_reg_mut(dst);
             let val2: u16 = emulator.read_from_pc();
@@ -1007,7 +1175,8 @@ instruction_category! {
 
 instruction_category! {
     make_control_flow_instrucions() => [
-        CallReg<0xfe, 26>(emulator) {
+        /// Pushes PC and jumps to the address in a register.
+        CallReg<0xfe, 26>["call", Reg](emulator) {
             let (src, _) = emulator.read_registers_operand();
             let value = emulator.get_reg_mut(src);
             let pc_value = emulator.get_reg_mut(RegisterName::PC);
@@ -1016,7 +1185,8 @@ instruction_category! {
             emulator.set_reg(RegisterName::SP, sp_value.wrapping_add(2));
             emulator.set_reg(RegisterName::PC, value);
         },
-        CallImm16<0xfd, 28>(emulator) {
+        /// Pushes PC and jumps to an immediate address.
+        CallImm16<0xfd, 28>["call", Imm16](emulator) {
             let value = emulator.read_from_pc();
             let pc_value = emulator.get_reg_mut(RegisterName::PC);
             let sp_value = emulator.get_reg_mut(RegisterName::SP);
@@ -1025,7 +1195,8 @@ instruction_category! {
             emulator.set_reg(RegisterName::PC, value);
         },
 
-        Ret<0xc3, 24>(emulator) {
+        /// Pops PC from the stack.
+        Ret<0xc3, 24>["ret"](emulator) {
             let sp_value = emulator.get_reg_mut(RegisterName::SP);
             emulator.set_reg(RegisterName::SP, sp_value.wrapping_sub(2));
             let value = emulator.peek_from_mem(sp_value.wrapping_sub(2));
@@ -1036,17 +1207,20 @@ instruction_category! {
 
 instruction_category! {
     make_misc_instructions() => [
-        Nop<0x6e, 1>(emulator) {
+        /// Starts a nop chain, which sleeps for one cycle per instruction.
+        Nop<0x6e, 1>["nop"](emulator) {
             emulator.set_cpu_flag(CpuFlag::Sleep, true);
         },
 
-        Op<0x6f, 1>(emulator) {
+        /// Continues a nop chain.
+        Op<0x6f, 1>["op"](emulator) {
             if !emulator.get_cpu_flag(CpuFlag::Sleep) {
                 emulator.nasal_demons();
             }
         },
 
-        P<0x70, 1>(emulator) {
+        /// Ends a nop chain.
+        P<0x70, 1>["p"](emulator) {
             if !emulator.get_cpu_flag(CpuFlag::Sleep) {
                 emulator.nasal_demons();
                 return;
@@ -1054,9 +1228,11 @@ instruction_category! {
             emulator.set_cpu_flag(CpuFlag::Sleep, false);
         },
 
-        Syscall<0x0f, 100>(_emulator) {},
+        /// Calls the kernel with the system call number in AX and arguments in R0 to R5.
+        Syscall<0x0f, 100>["syscall"](_emulator) {},
 
-        Reserved<0xff, 420>(emulator) {
+        /// Reserved for future use, executing it is undefined behavior.
+        Reserved<0xff, 420>["reserved"](emulator) {
             emulator.nasal_demons();
         },
     ]
@@ -1,3 +1,5 @@
+pub mod decode_cache;
 pub mod emulator;
 pub mod instructions;
+pub mod paged;
 pub mod register;
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Index;
+use std::rc::Rc;
+
+const PAGE_SIZE: usize = 1024;
+
+type Page = [u8; PAGE_SIZE];
+
+// A byte buffer split into pages that are shared between clones until one of
+// them writes to the page. Forking a process only copies the pages it touches.
+#[derive(Clone)]
+pub struct PagedBytes {
+    pages: Vec<Rc<Page>>,
+    len: usize,
+}
+
+impl PagedBytes {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, addr: usize, value: u8) {
+        debug_assert!(addr < self.len, "address {} out of bounds", addr);
+        let page = &mut self.pages[addr / PAGE_SIZE];
+        if page[addr % PAGE_SIZE] != value {
+            Rc::make_mut(page)[addr % PAGE_SIZE] = value;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        self.pages
+            .iter()
+            .flat_map(|page| page.iter())
+            .copied()
+            .take(self.len)
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.iter().collect()
+    }
+}
+
+impl From<Vec<u8>> for PagedBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        let pages = bytes
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Rc::new(page)
+            })
+            .collect();
+        PagedBytes {
+            pages,
+            len: bytes.len(),
+        }
+    }
+}
+
+impl Index<usize> for PagedBytes {
+    type Output = u8;
+
+    #[inline]
+    fn index(&self, addr: usize) -> &u8 {
+        debug_assert!(addr < self.len, "address {} out of bounds", addr);
+        &self.pages[addr / PAGE_SIZE][addr % PAGE_SIZE]
+    }
+}
+
+// Stored as a flat list of bytes, like a `Vec<u8>`.
+impl Serialize for PagedBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for PagedBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(PagedBytes::from)
+    }
+}
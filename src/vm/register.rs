@@ -1,4 +1,8 @@
-use rand::{rngs::StdRng, Rng, SeedableRng};
+use crate::game::rng::GameRng;
+
+use rand::{Rng, SeedableRng};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Copy, Clone)]
 pub enum RegisterName {
@@ -44,143 +48,158 @@ impl From<u8> for RegisterName {
     }
 }
 
-pub trait Register {
-    fn get_value_internal(&self) -> u16;
-    fn get_value_mut(&mut self) -> u16;
-    fn set_value(&mut self, value: u16);
+impl RegisterName {
+    pub fn name(self) -> &'static str {
+        match self {
+            RegisterName::PC => "PC",
+            RegisterName::FL => "FL",
+            RegisterName::CT => "CT",
+            RegisterName::R0 => "R0",
+            RegisterName::R1 => "R1",
+            RegisterName::R2 => "R2",
+            RegisterName::R3 => "R3",
+            RegisterName::R4 => "R4",
+            RegisterName::R5 => "R5",
+            RegisterName::SP => "SP",
+            RegisterName::TF => "TF",
+            RegisterName::ZR => "ZR",
+            RegisterName::RR => "RR",
+            RegisterName::TS => "TS",
+            RegisterName::RE => "RE",
+            RegisterName::AX => "AX",
+        }
+    }
+}
+
+impl std::str::FromStr for RegisterName {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        (0..16)
+            .map(RegisterName::from)
+            .find(|reg| reg.name().eq_ignore_ascii_case(s))
+            .ok_or(())
+    }
 }
 
+#[derive(Serialize, Deserialize)]
+pub enum RegisterState {
+    GeneralPurpose(u16),
+    Const,
+    Random(Box<GameRng>),
+    Counter(u16),
+    BitRev(u16),
+}
+
+// Plain registers only store a value. Reading `CT` increments it afterwards,
+// reading `RE` reverses its bits, `TF` and `ZR` are constants, and `RR` yields
+// random numbers. Writes to `TF`, `ZR` and `RR` are ignored.
+#[derive(Clone)]
 pub struct Registers {
-    registers: [Box<dyn Register>; 16],
+    values: [u16; 16],
+    rng: GameRng,
 }
 
 impl Registers {
-    pub fn new() -> Self {
+    pub fn new(rng: GameRng) -> Self {
         Self {
-            registers: [
-                Box::<GeneralPurposeRegister>::default(), // PC
-                Box::<GeneralPurposeRegister>::default(), // FL
-                Box::<CounterRegister>::default(),        // CT
-                Box::<GeneralPurposeRegister>::default(), // R0
-                Box::<GeneralPurposeRegister>::default(), // R1
-                Box::<GeneralPurposeRegister>::default(), // R2
-                Box::<GeneralPurposeRegister>::default(), // R3
-                Box::<GeneralPurposeRegister>::default(), // R4
-                Box::<GeneralPurposeRegister>::default(), // R5
-                Box::<GeneralPurposeRegister>::default(), // SP
-                Box::<ConstRegister<0x1337>>::default(),  // TF
-                Box::<ConstRegister<0>>::default(),       // ZR
-                Box::<RandomValueRegister>::default(),    // RR
-                Box::<GeneralPurposeRegister>::default(), // TS
-                Box::<BitRevRegister>::default(),         // RE
-                Box::<GeneralPurposeRegister>::default(), // AX
-            ],
+            values: [0; 16],
+            rng,
         }
     }
 
-    pub fn get(&self, register: RegisterName) -> &dyn Register {
-        self.registers[register as usize].as_ref()
-    }
-
-    pub fn get_mut(&mut self, register: RegisterName) -> &mut dyn Register {
-        self.registers[register as usize].as_mut()
+    // The value as stored, without the side effects of reading it from code.
+    #[inline]
+    pub fn get_value_internal(&self, register: RegisterName) -> u16 {
+        match register {
+            RegisterName::TF => 0x1337,
+            RegisterName::ZR | RegisterName::RR => 0,
+            _ => self.values[register as usize],
+        }
     }
-}
 
-impl Clone for Registers {
-    fn clone(&self) -> Self {
-        let mut result = Self::new();
-        for (dst, src) in result.registers.iter_mut().zip(self.registers.iter()) {
-            dst.set_value(src.get_value_internal());
+    #[inline]
+    pub fn get_value_mut(&mut self, register: RegisterName) -> u16 {
+        match register {
+            RegisterName::CT => {
+                let old = self.values[RegisterName::CT as usize];
+                self.values[RegisterName::CT as usize] = old.wrapping_add(1);
+                old
+            }
+            RegisterName::RR => self.rng.gen(),
+            RegisterName::RE => self.values[RegisterName::RE as usize].reverse_bits(),
+            _ => self.get_value_internal(register),
         }
-        result
     }
-}
-
-#[derive(Default)]
-pub struct GeneralPurposeRegister {
-    value: u16,
-}
-
-#[derive(Default)]
-pub struct ConstRegister<const N: u16> {}
 
-pub struct RandomValueRegister {
-    rng: StdRng,
-}
-
-#[derive(Default)]
-pub struct CounterRegister {
-    value: u16,
-}
-
-#[derive(Default)]
-pub struct BitRevRegister {
-    value: u16,
-}
-
-impl Register for GeneralPurposeRegister {
-    fn get_value_internal(&self) -> u16 {
-        self.value
-    }
-    fn get_value_mut(&mut self) -> u16 {
-        self.value
-    }
-    fn set_value(&mut self, value: u16) {
-        self.value = value;
+    #[inline]
+    pub fn set_value(&mut self, register: RegisterName, value: u16) {
+        match register {
+            RegisterName::TF | RegisterName::ZR | RegisterName::RR => {}
+            _ => self.values[register as usize] = value,
+        }
     }
-}
 
-impl<const N: u16> Register for ConstRegister<N> {
-    fn get_value_internal(&self) -> u16 {
-        N
-    }
-    fn get_value_mut(&mut self) -> u16 {
-        N
+    pub fn reseed(&mut self, rng: GameRng) {
+        self.rng = rng;
     }
-    fn set_value(&mut self, _value: u16) {}
-}
 
-impl Default for RandomValueRegister {
-    fn default() -> Self {
-        Self {
-            rng: StdRng::from_entropy(),
+    fn get_state(&self, register: RegisterName) -> RegisterState {
+        let value = self.values[register as usize];
+        match register {
+            RegisterName::CT => RegisterState::Counter(value),
+            RegisterName::TF | RegisterName::ZR => RegisterState::Const,
+            RegisterName::RR => RegisterState::Random(Box::new(self.rng.clone())),
+            RegisterName::RE => RegisterState::BitRev(value),
+            _ => RegisterState::GeneralPurpose(value),
         }
     }
-}
 
-impl Register for RandomValueRegister {
-    fn get_value_internal(&self) -> u16 {
-        0
-    }
-    fn get_value_mut(&mut self) -> u16 {
-        self.rng.gen()
+    fn set_state(&mut self, register: RegisterName, state: RegisterState) -> bool {
+        match (register, state) {
+            (RegisterName::CT, RegisterState::Counter(value))
+            | (RegisterName::RE, RegisterState::BitRev(value)) => {
+                self.values[register as usize] = value;
+            }
+            (RegisterName::TF | RegisterName::ZR, RegisterState::Const) => {}
+            (RegisterName::RR, RegisterState::Random(rng)) => self.rng = *rng,
+            (
+                RegisterName::CT
+                | RegisterName::TF
+                | RegisterName::ZR
+                | RegisterName::RR
+                | RegisterName::RE,
+                _,
+            ) => return false,
+            (_, RegisterState::GeneralPurpose(value)) => self.values[register as usize] = value,
+            _ => return false,
+        }
+        true
     }
-    fn set_value(&mut self, _value: u16) {}
 }
 
-impl Register for CounterRegister {
-    fn get_value_internal(&self) -> u16 {
-        self.value
-    }
-    fn get_value_mut(&mut self) -> u16 {
-        let old = self.value;
-        self.value = self.value.wrapping_add(1);
-        old
-    }
-    fn set_value(&mut self, value: u16) {
-        self.value = value;
+// Kept in the same format as the previous register file, so older snapshots
+// still load.
+impl Serialize for Registers {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((0..16).map(|idx| self.get_state(RegisterName::from(idx))))
     }
 }
 
-impl Register for BitRevRegister {
-    fn get_value_internal(&self) -> u16 {
-        self.value
-    }
-    fn get_value_mut(&mut self) -> u16 {
-        self.value.reverse_bits()
-    }
-    fn set_value(&mut self, value: u16) {
-        self.value = value;
+impl<'de> Deserialize<'de> for Registers {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let states = Vec::<RegisterState>::deserialize(deserializer)?;
+        if states.len() != 16 {
+            return Err(D::Error::invalid_length(states.len(), &"16 registers"));
+        }
+        let mut result = Self::new(GameRng::seed_from_u64(0));
+        for (idx, state) in states.into_iter().enumerate() {
+            if !result.set_state(RegisterName::from(idx as u8), state) {
+                return Err(D::Error::custom(
+                    "register state does not match register type",
+                ));
+            }
+        }
+        Ok(result)
     }
 }
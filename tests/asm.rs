@@ -0,0 +1,150 @@
+use minecrab::asm::{self, Program};
+
+fn assemble(source: &str) -> Program {
+    asm::assemble(source).unwrap()
+}
+
+// The line and message of the error `source` fails with.
+fn assemble_error(source: &str) -> (usize, String) {
+    let err = asm::assemble(source).err().unwrap();
+    (err.line, err.message)
+}
+
+#[test]
+fn images_are_full_size() {
+    let program = assemble("");
+    assert_eq!(program.bytecode, vec![0; 0x10000]);
+    assert_eq!(program.memory, vec![0; 0x10000]);
+}
+
+#[test]
+fn labels_resolve_backwards_and_forwards() {
+    let program = assemble(
+        "
+        mov16 pc, :end      // jump over the nop
+:start  nop
+:end
+        mov16 r0, :start
+        ",
+    );
+    assert_eq!(
+        program.bytecode[..9],
+        [0x20, 0x00, 0x05, 0x00, 0x6e, 0x20, 0x03, 0x04, 0x00]
+    );
+}
+
+#[test]
+fn constants_fold_into_immediates() {
+    let program = assemble(
+        "
+        .const STEP 2
+        .const TWICE STEP + STEP
+        add8 r0, STEP
+        mov16 r1, TWICE - 5
+        mov16 r2, 'a' + 0b10 + 0o10 + 0x10
+        ",
+    );
+    assert_eq!(
+        program.bytecode[..11],
+        [0xc5, 0x03, 0x02, 0x20, 0x04, 0xff, 0xff, 0x20, 0x05, 0x7b, 0x00]
+    );
+}
+
+#[test]
+fn org_and_data_move_their_own_cursors() {
+    let program = assemble(
+        "
+        nop
+        .data
+        .org 0x200
+:message
+        .ascii \"hi\\n\"
+        .word :message, -1
+        .code
+        nop
+        .org 0x10
+        .byte 1, 255, -128
+        ",
+    );
+    assert_eq!(program.bytecode[..2], [0x6e, 0x6e]);
+    assert_eq!(program.bytecode[0x10..0x14], [0x01, 0xff, 0x80, 0x00]);
+    assert_eq!(
+        program.memory[0x200..0x208],
+        [b'h', b'i', b'\n', 0x00, 0x02, 0xff, 0xff, 0x00]
+    );
+}
+
+#[test]
+fn every_addressing_mode_is_encoded() {
+    let program = assemble(
+        "
+        load16 r0, [r1]
+        load16 r0, [r1+2]
+        load16 r0, [r1+r2*4]
+        load16 r0, [r1 + r2*16 + :here]
+:here   load16 r0, [sp-2]
+        load16 r0, [r1+r2]
+        ",
+    );
+    let expected = [
+        &[0x24, 0x03, 0x04][..],
+        &[0x24, 0x03, 0x44, 0x02, 0x00],
+        &[0x24, 0x03, 0x84, 0x45],
+        &[0x24, 0x03, 0xc4, 0xf5, 0x12, 0x00],
+        &[0x24, 0x03, 0x49, 0xfe, 0xff],
+        &[0x24, 0x03, 0x84, 0x15],
+    ]
+    .concat();
+    assert_eq!(program.bytecode[..expected.len()], expected);
+}
+
+#[test]
+fn operands_pick_the_opcode() {
+    let program = assemble(
+        "
+        add16 r0, r1
+        add8 r0, 1
+        add16 r0, 1
+        ",
+    );
+    assert_eq!(
+        program.bytecode[..9],
+        [0xc4, 0x43, 0xc5, 0x03, 0x01, 0xc6, 0x03, 0x01, 0x00]
+    );
+}
+
+#[test]
+fn errors_point_at_their_line() {
+    assert_eq!(
+        assemble_error("nop\n\n    frobnicate r0\n"),
+        (3, "unknown instruction `frobnicate`".to_string())
+    );
+    // Labels are only resolved after the whole source has been read.
+    assert_eq!(
+        assemble_error("nop\nmov16 pc, :nowhere\nnop\n"),
+        (2, "undefined label `:nowhere`".to_string())
+    );
+    assert_eq!(
+        assemble_error(":a\nnop\n:a nop\n"),
+        (3, "duplicate label `:a`".to_string())
+    );
+    assert_eq!(
+        assemble_error("nop\n.byte 1, 256\n"),
+        (2, "value 256 does not fit in 8 bits".to_string())
+    );
+    assert_eq!(
+        assemble_error(".data\n\nnop\n"),
+        (
+            3,
+            "instructions are only allowed in the code section".to_string()
+        )
+    );
+    assert_eq!(
+        assemble_error(".const A :label\n"),
+        (1, "label `:label` cannot be used here".to_string())
+    );
+    assert_eq!(assemble_error("nop\nload16 r0, [r1+r2*3]\n").0, 2);
+    let (line, message) = assemble_error("\n\ncmovne pc, 1\n");
+    assert_eq!(line, 3);
+    assert!(message.starts_with("invalid operands for `cmovne`"));
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use minecrab::game::map::{
+    GameMap, Location, MapGeometry, DESTRUCTIBLE_WALL, DESTRUCTIBLE_WALL_HP, LAND, WALL, WATER,
+};
+use minecrab::game::replay::{GameEvent, MemorySink};
+use minecrab::kernel::syscall::{BUILD_COOLDOWN, SYSCALL_TABLE};
+use minecrab::kernel::Kernel;
+
+use std::rc::Rc;
+
+const GEOMETRY: MapGeometry = MapGeometry {
+    width: 8,
+    height: 8,
+    bounded: true,
+};
+
+const INIT_LOCATION: Location = (1, 1);
+
+fn new_kernel(cells: &[(Location, u8)]) -> (Kernel, Rc<MemorySink>) {
+    let mut map_data = vec![LAND; GEOMETRY.cell_count()];
+    for &(location, cell) in cells {
+        map_data[GEOMETRY.cell_index(location)] = cell;
+    }
+    let sink = Rc::new(MemorySink::new());
+    let kernel = common::new_kernel(
+        common::kernel_config(3),
+        GameMap::new(&map_data, GEOMETRY).unwrap(),
+        sink.clone(),
+        common::IDLE_BOT,
+        &[INIT_LOCATION],
+    );
+    (kernel, sink)
+}
+
+fn call(kernel: &mut Kernel, pid: u16, name: &str, (x, y): Location) -> Option<u16> {
+    SYSCALL_TABLE.get_syscall_by_name(name).unwrap().call(
+        kernel,
+        pid,
+        (x as u16, y as u16, 0, 0, 0, 0),
+    )
+}
+
+fn init_pid(kernel: &Kernel) -> u16 {
+    common::init_pid(kernel, 1)
+}
+
+#[test]
+fn init_walls_itself_in() {
+    let (mut kernel, sink) = new_kernel(&[((0, 0), WATER)]);
+    let pid = init_pid(&kernel);
+    let mut built = vec![];
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let location = GEOMETRY.offset(INIT_LOCATION, dx, dy).unwrap();
+            if call(&mut kernel, pid, "Build", location) == Some(1) {
+                built.push(location);
+            }
+            for _ in 0..BUILD_COOLDOWN {
+                kernel.step();
+            }
+        }
+    }
+    // Neither the water nor the cell of init itself can be built on.
+    assert_eq!(built.len(), 7);
+    for &location in &built {
+        assert_eq!(kernel.get_map_cell(location).status(), WALL);
+    }
+    let events: Vec<_> = sink
+        .take_events()
+        .into_iter()
+        .filter_map(|event| match event {
+            GameEvent::WallBuilt { pid, location } => Some((pid, location)),
+            _ => None,
+        })
+        .collect();
+    let expected: Vec<_> = built.iter().map(|&location| (pid, location)).collect();
+    assert_eq!(events, expected);
+}
+
+#[test]
+fn build_waits_for_its_cooldown() {
+    let (mut kernel, _) = new_kernel(&[]);
+    let pid = init_pid(&kernel);
+    assert_eq!(call(&mut kernel, pid, "Build", (2, 1)), Some(1));
+    assert_eq!(call(&mut kernel, pid, "Build", (2, 2)), None);
+    // Digging has a cooldown of its own.
+    assert_eq!(call(&mut kernel, pid, "Dig", (2, 1)), Some(1));
+    for _ in 1..BUILD_COOLDOWN {
+        kernel.step();
+    }
+    assert_eq!(call(&mut kernel, pid, "Build", (2, 2)), None);
+    kernel.step();
+    assert_eq!(call(&mut kernel, pid, "Build", (2, 2)), Some(1));
+
+    // Forking does not reset the cooldown.
+    let child_pid = (0..16).find_map(|_| kernel.fork_process(pid)).unwrap();
+    let child_location = kernel.get_process_location(child_pid);
+    let target = (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter_map(|(dx, dy)| GEOMETRY.offset(child_location, dx, dy))
+        .find(|&location| kernel.get_map_cell(location).is_empty())
+        .unwrap();
+    assert_eq!(call(&mut kernel, child_pid, "Build", target), None);
+}
+
+#[test]
+fn dig_only_takes_adjacent_walls() {
+    let (mut kernel, _) =
+        new_kernel(&[((2, 1), WALL), ((3, 1), WALL), ((0, 2), DESTRUCTIBLE_WALL)]);
+    let pid = init_pid(&kernel);
+    assert_eq!(call(&mut kernel, pid, "Dig", (3, 1)), None);
+    assert_eq!(call(&mut kernel, pid, "Dig", (1, 2)), None);
+    assert_eq!(call(&mut kernel, pid, "Dig", (9, 1)), None);
+    assert_eq!(call(&mut kernel, pid, "Dig", (0, 2)), Some(1));
+    assert_eq!(kernel.get_map_cell((0, 2)).status(), LAND);
+}
+
+// Replaying the wall events on top of the initial map gives the current map.
+#[test]
+fn replays_follow_the_map() {
+    let (mut kernel, sink) = new_kernel(&[((2, 2), WALL), ((0, 0), DESTRUCTIBLE_WALL)]);
+    let pid = init_pid(&kernel);
+    assert_eq!(call(&mut kernel, pid, "Build", (2, 1)), Some(1));
+    assert_eq!(call(&mut kernel, pid, "Dig", (2, 2)), Some(1));
+    while kernel.get_map_cell((0, 0)).status() != LAND {
+        assert_eq!(call(&mut kernel, pid, "Attack1", (0, 0)), Some(1));
+    }
+
+    let mut map_data = vec![];
+    for event in sink.take_events() {
+        match event {
+            GameEvent::InitMap { map_data: data, .. } => map_data = data.into_owned(),
+            GameEvent::WallBuilt { location, .. } => {
+                map_data[GEOMETRY.cell_index(location)] = WALL;
+            }
+            GameEvent::WallDestroyed { location, .. } => {
+                map_data[GEOMETRY.cell_index(location)] = LAND;
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(map_data, kernel.get_game_map().to_map_data());
+}
+
+#[test]
+fn every_hit_on_a_destructible_wall_is_logged() {
+    let (mut kernel, sink) = new_kernel(&[((0, 0), DESTRUCTIBLE_WALL)]);
+    let pid = init_pid(&kernel);
+    sink.take_events();
+    for _ in 0..DESTRUCTIBLE_WALL_HP {
+        assert_eq!(call(&mut kernel, pid, "Attack1", (0, 0)), Some(1));
+    }
+
+    let hits: Vec<_> = sink
+        .take_events()
+        .into_iter()
+        .filter_map(|event| match event {
+            GameEvent::WallDamaged { location, hp, .. } => Some((location, Some(hp))),
+            GameEvent::WallDestroyed { location, .. } => Some((location, None)),
+            _ => None,
+        })
+        .collect();
+    let mut expected: Vec<_> = (0..DESTRUCTIBLE_WALL_HP)
+        .rev()
+        .map(|hp| ((0, 0), Some(hp)))
+        .collect();
+    expected.push(((0, 0), None));
+    assert_eq!(hits, expected);
+}
@@ -0,0 +1,65 @@
+// Fixtures shared by the integration tests. Each test crate only uses some of
+// them.
+#![allow(dead_code)]
+
+use minecrab::asm;
+use minecrab::config::{KernelConfiguration, UserConfiguration};
+use minecrab::game::map::{GameMap, Location};
+use minecrab::game::replay::EventSink;
+use minecrab::kernel::Kernel;
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+pub const IDLE_BOT: &str = "
+:loop
+    mov16 pc, :loop
+";
+
+// A game without challenges that only ends once every process is gone.
+pub fn kernel_config(seed: u64) -> KernelConfiguration {
+    KernelConfiguration {
+        max_processes: 16,
+        initd_lifetime: 1000,
+        default_nice: 0,
+        crypto_spawn: BTreeMap::new(),
+        seed,
+        max_ticks: None,
+        last_user_standing: false,
+        score_threshold: None,
+    }
+}
+
+// User `idx + 1` runs `source` from `spawn_points[idx]`.
+pub fn user_configs(source: &str, spawn_points: &[Location]) -> Vec<UserConfiguration> {
+    let program = asm::assemble(source).unwrap();
+    spawn_points
+        .iter()
+        .enumerate()
+        .map(|(idx, &spawn_point)| UserConfiguration {
+            initd_memory: program.memory.clone(),
+            initd_bytecode: program.bytecode.clone(),
+            uid: idx as u16 + 1,
+            spawn_point,
+        })
+        .collect()
+}
+
+// Starts a game where user `idx + 1` runs `source` from `spawn_points[idx]`.
+pub fn new_kernel(
+    config: KernelConfiguration,
+    game_map: GameMap,
+    event_sink: Rc<dyn EventSink>,
+    source: &str,
+    spawn_points: &[Location],
+) -> Kernel {
+    let mut kernel = Kernel::new(config, game_map, event_sink);
+    kernel
+        .setup_users(user_configs(source, spawn_points))
+        .unwrap();
+    kernel
+}
+
+pub fn init_pid(kernel: &Kernel, uid: u16) -> u16 {
+    kernel.get_user(uid).initd_pid.unwrap()
+}
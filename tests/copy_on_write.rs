@@ -0,0 +1,112 @@
+mod common;
+
+use minecrab::game::map::{GameMap, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::replay::NullSink;
+use minecrab::kernel::syscall::SYSCALL_TABLE;
+use minecrab::kernel::Kernel;
+
+use std::rc::Rc;
+
+// Crosses from the first 1 KiB page into the second.
+const ADDR: u16 = 0x3fe;
+const DATA: [u8; 4] = [1, 2, 3, 4];
+
+// A game with an init and two forked children of it.
+fn new_family() -> (Kernel, u16, u16, u16) {
+    let mut kernel = common::new_kernel(
+        common::kernel_config(4),
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        Rc::new(NullSink),
+        common::IDLE_BOT,
+        &[(10, 10)],
+    );
+    let parent = common::init_pid(&kernel, 1);
+    let child = kernel.fork_process(parent).unwrap();
+    let sibling = kernel.fork_process(parent).unwrap();
+    (kernel, parent, child, sibling)
+}
+
+fn memory(kernel: &Kernel, pid: u16) -> Vec<u8> {
+    let emulator = &kernel.get_process(pid).emulator;
+    emulator.peek_bytes_from_mem(ADDR, DATA.len())
+}
+
+fn code(kernel: &Kernel, pid: u16) -> Vec<u8> {
+    let bytecode = kernel.get_process(pid).emulator.get_bytecode();
+    (0..DATA.len())
+        .map(|idx| bytecode[ADDR as usize + idx])
+        .collect()
+}
+
+fn call(kernel: &mut Kernel, pid: u16, name: &str, args: (u16, u16, u16, u16)) -> Option<u16> {
+    SYSCALL_TABLE.get_syscall_by_name(name).unwrap().call(
+        kernel,
+        pid,
+        (args.0, args.1, args.2, args.3, 0, 0),
+    )
+}
+
+#[test]
+fn memory_writes_stay_in_their_process() {
+    let (mut kernel, parent, child, sibling) = new_family();
+    let emulator = &mut kernel.get_process_mut(child).emulator;
+    emulator.write_bytes_to_mem(ADDR, &DATA);
+    assert_eq!(memory(&kernel, child), DATA);
+    assert_eq!(memory(&kernel, parent), [0; 4]);
+    assert_eq!(memory(&kernel, sibling), [0; 4]);
+
+    let emulator = &mut kernel.get_process_mut(parent).emulator;
+    emulator.write_bytes_to_mem(ADDR, &[5; 4]);
+    assert_eq!(memory(&kernel, parent), [5; 4]);
+    assert_eq!(memory(&kernel, child), DATA);
+    assert_eq!(memory(&kernel, sibling), [0; 4]);
+    kernel.check_invariants();
+}
+
+#[test]
+fn code_writes_stay_in_their_process() {
+    let (mut kernel, parent, child, sibling) = new_family();
+    let emulator = &mut kernel.get_process_mut(child).emulator;
+    emulator.write_bytes_to_code(ADDR, &DATA);
+    assert_eq!(code(&kernel, child), DATA);
+    assert_eq!(code(&kernel, parent), [0; 4]);
+    assert_eq!(code(&kernel, sibling), [0; 4]);
+
+    let emulator = &mut kernel.get_process_mut(parent).emulator;
+    emulator.write_bytes_to_code(ADDR, &[5; 4]);
+    assert_eq!(code(&kernel, parent), [5; 4]);
+    assert_eq!(code(&kernel, child), DATA);
+    assert_eq!(code(&kernel, sibling), [0; 4]);
+    // Memory and code are separate buffers.
+    assert_eq!(memory(&kernel, parent), [0; 4]);
+}
+
+#[test]
+fn shared_memory_only_reaches_the_target() {
+    let (mut kernel, parent, child, sibling) = new_family();
+    let emulator = &mut kernel.get_process_mut(parent).emulator;
+    emulator.write_bytes_to_mem(0x800, &DATA);
+    let n = DATA.len() as u16 - 1;
+    assert_eq!(
+        call(&mut kernel, parent, "ShareMemory", (child, ADDR, 0x800, n)),
+        Some(1)
+    );
+    assert_eq!(memory(&kernel, child), DATA);
+    assert_eq!(memory(&kernel, parent), [0; 4]);
+    assert_eq!(memory(&kernel, sibling), [0; 4]);
+}
+
+#[test]
+fn updated_code_only_changes_the_caller() {
+    let (mut kernel, parent, child, sibling) = new_family();
+    let emulator = &mut kernel.get_process_mut(child).emulator;
+    emulator.write_bytes_to_mem(0x800, &DATA);
+    let n = DATA.len() as u16 - 1;
+    assert_eq!(
+        call(&mut kernel, child, "UpdateCode", (0x800, ADDR, n, 0)),
+        Some(1)
+    );
+    assert_eq!(code(&kernel, child), DATA);
+    assert_eq!(code(&kernel, parent), [0; 4]);
+    assert_eq!(code(&kernel, sibling), [0; 4]);
+}
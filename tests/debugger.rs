@@ -0,0 +1,146 @@
+use minecrab::asm;
+use minecrab::debugger::Debugger;
+use minecrab::vm::emulator::{Emulator, ExecutionObserver};
+use minecrab::vm::register::RegisterName;
+
+use std::cell::RefCell;
+use std::io::{self, Cursor, Write};
+use std::rc::Rc;
+
+const PROGRAM: &str = "
+        mov16 r0, 1
+        call :func
+        mov16 r1, 2
+        mov16 ax, 0x00
+        syscall
+:loop
+        mov16 pc, :loop
+:func
+        mov16 r2, 3
+        ret
+";
+
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Runs PROGRAM until its syscall or until the debugger detaches, answering
+// every prompt with the next line of `commands`. Returns the lines starting
+// with "pid " or "=>", which report where the debugger stopped.
+fn debug(commands: &str) -> (Emulator, Vec<String>) {
+    let program = asm::assemble(PROGRAM).unwrap();
+    let mut emulator = Emulator::new(program.memory, program.bytecode, 0);
+    let output = SharedOutput::default();
+    let debugger = Debugger::with_io(
+        7,
+        Box::new(Cursor::new(commands.to_string())),
+        Box::new(output.clone()),
+    );
+    let mut observers: Vec<Box<dyn ExecutionObserver>> = vec![Box::new(debugger)];
+    let mut cycle_count = 1000;
+    emulator.run_until_interrupt_observed(7, &mut cycle_count, &mut observers);
+
+    let output = String::from_utf8(output.0.take()).unwrap();
+    let stops = output
+        .split("(mdb) ")
+        .flat_map(str::lines)
+        .filter(|line| line.starts_with("pid ") || line.starts_with("=>"))
+        .map(str::to_string)
+        .collect();
+    (emulator, stops)
+}
+
+#[test]
+fn step_stops_before_every_instruction() {
+    let (_, stops) = debug("step\nstep 2\nquit\n");
+    assert_eq!(
+        stops,
+        [
+            "pid 7 stopped: step",
+            "=> 0000: mov16 r0, 0x0001",
+            "pid 7 stopped: step",
+            "=> 0004: call 0x0014",
+            "pid 7 stopped: step",
+            "=> 0018: ret",
+        ]
+    );
+}
+
+#[test]
+fn next_runs_calls_until_they_return() {
+    let (emulator, stops) = debug("next\nnext\nquit\n");
+    assert_eq!(
+        stops,
+        [
+            "pid 7 stopped: step",
+            "=> 0000: mov16 r0, 0x0001",
+            "pid 7 stopped: step",
+            "=> 0004: call 0x0014",
+            "pid 7 stopped: next",
+            "=> 0007: mov16 r1, 0x0002",
+        ]
+    );
+    assert_eq!(emulator.peek_reg(RegisterName::R2), 3);
+}
+
+#[test]
+fn continue_stops_at_address_breakpoints() {
+    let (emulator, stops) = debug("break 0x18\nbreak 7\ndelete 7\ncontinue\nquit\n");
+    assert_eq!(
+        stops,
+        [
+            "pid 7 stopped: step",
+            "=> 0000: mov16 r0, 0x0001",
+            "pid 7 stopped: breakpoint at 0x0018",
+            "=> 0018: ret",
+        ]
+    );
+    assert_eq!(emulator.peek_reg(RegisterName::R2), 3);
+}
+
+#[test]
+fn continue_stops_at_syscall_breakpoints() {
+    let (emulator, stops) = debug("bs 0x01\nbs GetPid\ncontinue\nstep\n");
+    assert_eq!(
+        stops,
+        [
+            "pid 7 stopped: step",
+            "=> 0000: mov16 r0, 0x0001",
+            "pid 7 stopped: syscall 0x00 (GetPid)",
+            "=> 000f: syscall",
+            "pid 7 calls GetPid",
+        ]
+    );
+    assert_eq!(emulator.peek_reg(RegisterName::R1), 2);
+}
+
+#[test]
+fn regs_prints_registers_and_flags() {
+    let program = asm::assemble(PROGRAM).unwrap();
+    let mut emulator = Emulator::new(program.memory, program.bytecode, 0);
+    let output = SharedOutput::default();
+    let mut debugger = Debugger::with_io(
+        7,
+        Box::new(Cursor::new("regs\nquit\n")),
+        Box::new(output.clone()),
+    );
+    debugger.before_instruction(7, &mut emulator, 1000);
+    assert!(!debugger.is_attached());
+
+    let output = String::from_utf8(output.0.take()).unwrap();
+    assert!(output.contains("PC 0x0000   FL 0x0000"), "{}", output);
+    assert!(
+        output.contains("ZF=0 CF=0 OF=0 SF=0 NF=0   cycles left 1000"),
+        "{}",
+        output
+    );
+}
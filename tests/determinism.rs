@@ -0,0 +1,100 @@
+mod common;
+
+use minecrab::config::{GameConfiguration, KernelConfiguration};
+use minecrab::game::map::{GameMap, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::replay::{GameEvent, MemorySink, OwnedGameEvent};
+use minecrab::kernel::Kernel;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+// Forks, spins for a random number of loops, then teleports next to itself.
+const RESTLESS_BOT: &str = "
+:loop
+    mov16 ax, 0x02
+    syscall
+    mov16 r1, rr
+    and16 r1, 0xff
+:wait
+    sub8 r1, 1
+    cmovne16 pc, :wait
+    mov16 ax, 0x00
+    syscall
+    mov16 r0, ax
+    mov16 ax, 0x41
+    syscall
+    mov16 pc, :loop
+";
+
+fn play(seed: u64) -> Vec<OwnedGameEvent> {
+    let config = KernelConfiguration {
+        crypto_spawn: BTreeMap::from([("dog".to_string(), vec![(1, 0.5)])]),
+        max_ticks: Some(60),
+        ..common::kernel_config(seed)
+    };
+    let sink = Rc::new(MemorySink::new());
+    let mut kernel = common::new_kernel(
+        config,
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        sink.clone(),
+        RESTLESS_BOT,
+        &[(10, 10), (100, 100)],
+    );
+    kernel.run_full_game();
+    sink.take_events()
+}
+
+#[test]
+fn same_seed_plays_the_same_game() {
+    let events = play(42);
+    assert!(events.len() > 1000);
+    assert_eq!(events, play(42));
+}
+
+#[test]
+fn other_seeds_play_other_games() {
+    let events = play(42);
+    let other_events = play(43);
+    // The seed is the first event, so skip it to compare the games themselves.
+    assert!(matches!(events[0], GameEvent::Seed { seed: 42 }));
+    assert_ne!(events[1..], other_events[1..]);
+}
+
+#[test]
+fn unseeded_games_draw_and_report_a_seed() {
+    let game_config = GameConfiguration {
+        user_configs: vec![],
+        default_nice: 0,
+        initd_lifetime: 1000,
+        max_processes: 16,
+        mapdata_path: PathBuf::from("map.bin"),
+        map_size: None,
+        bounded_map: false,
+        crypto_spawn: BTreeMap::new(),
+        seed: None,
+        max_ticks: None,
+        last_user_standing: false,
+        score_threshold: None,
+    };
+    let config = game_config.get_kernel_config();
+    let seed = config.seed;
+    assert_ne!(seed, game_config.get_kernel_config().seed);
+
+    let sink = Rc::new(MemorySink::new());
+    let kernel = Kernel::new(
+        config,
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        sink.clone(),
+    );
+    assert_eq!(sink.take_events()[0], GameEvent::Seed { seed });
+
+    // Snapshots keep the seed, so the game can be replayed from its config.
+    let path = std::env::temp_dir().join(format!("minecrab-seed-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    kernel.save_snapshot(path).unwrap();
+    let snapshot: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(snapshot["config"]["seed"], seed);
+}
@@ -0,0 +1,59 @@
+use minecrab::asm;
+use minecrab::disasm;
+use minecrab::vm::instructions::{Operand, OPCODE_TABLE};
+use minecrab::vm::paged::PagedBytes;
+
+// One encoding of `mem` per addressing mode, with a power of 2 scale.
+const MEM_OPERANDS: [&[u8]; 4] = [
+    &[0x03],
+    &[0x44, 0xfe, 0xff],
+    &[0x85, 0x46],
+    &[0xc6, 0xf7, 0x10, 0x00],
+];
+
+// Encodes `opcode` once per addressing mode if it takes a memory operand, and
+// once otherwise. Branches go to the start of the code so that some of them
+// get labels.
+fn encode(opcode: u8, operands: &[Operand]) -> Vec<Vec<u8>> {
+    let modes = if operands.contains(&Operand::Mem) {
+        &MEM_OPERANDS[..]
+    } else {
+        &MEM_OPERANDS[..1]
+    };
+    modes
+        .iter()
+        .enumerate()
+        .map(|(variant, mem)| {
+            let mut bytes = vec![opcode];
+            for operand in operands {
+                match operand {
+                    Operand::Reg => bytes.push(0x05),
+                    Operand::RegReg => bytes.push(0x43),
+                    Operand::Imm8 => bytes.push(0x9a),
+                    Operand::Imm16 if variant % 2 == 0 => bytes.extend([0x00, 0x00]),
+                    Operand::Imm16 => bytes.extend([0xef, 0xbe]),
+                    Operand::Mem => bytes.extend(*mem),
+                }
+            }
+            bytes
+        })
+        .collect()
+}
+
+#[test]
+fn disassembly_reassembles_to_the_same_bytes() {
+    let mut code = Vec::new();
+    for instruction in OPCODE_TABLE.instructions() {
+        for bytes in encode(instruction.get_opcode(), instruction.get_operands()) {
+            code.extend(bytes);
+        }
+    }
+    let unused = (0..=255u8)
+        .find(|&opcode| OPCODE_TABLE.get_instruction(opcode).get_opcode() != opcode)
+        .unwrap();
+    code.push(unused);
+
+    let source = disasm::disassemble(&PagedBytes::from(code.clone()), 0, code.len());
+    let program = asm::assemble(&source).unwrap_or_else(|err| panic!("{}\n{}", err, source));
+    assert_eq!(program.bytecode[..code.len()], code, "{}", source);
+}
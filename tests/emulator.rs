@@ -0,0 +1,143 @@
+mod common;
+
+use minecrab::asm;
+use minecrab::game::map::{GameMap, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::replay::NullSink;
+use minecrab::vm::emulator::Emulator;
+use minecrab::vm::instructions::OPCODE_TABLE;
+use minecrab::vm::register::RegisterName;
+
+use std::rc::Rc;
+
+fn new_emulator(source: &str) -> Emulator {
+    let program = asm::assemble(source).unwrap();
+    Emulator::new(program.memory, program.bytecode, 0)
+}
+
+// Runs until the next syscall.
+fn run(emulator: &mut Emulator) {
+    let mut cycle_count = 1000;
+    assert!(emulator.run_until_interrupt(&mut cycle_count).is_some());
+}
+
+const LOOP: &str = "
+:loop
+        mov16 r0, 1
+        syscall
+        mov16 pc, :loop
+";
+
+#[test]
+fn executed_instructions_are_cached() {
+    let mut emulator = new_emulator(LOOP);
+    assert!(!emulator.get_decode_cache().contains(0));
+    run(&mut emulator);
+    let cache = emulator.get_decode_cache();
+    assert!(cache.contains(0));
+    assert!(cache.contains(4));
+    assert!(!cache.contains(1));
+    assert!(!cache.contains(5));
+}
+
+#[test]
+fn code_writes_drop_the_instructions_they_overlap() {
+    let mut emulator = new_emulator(LOOP);
+    run(&mut emulator);
+    run(&mut emulator);
+    assert!(emulator.get_decode_cache().contains(5));
+    emulator.write_bytes_to_code(2, &[2]);
+    let cache = emulator.get_decode_cache();
+    assert!(!cache.contains(0));
+    assert!(cache.contains(4));
+    assert!(cache.contains(5));
+}
+
+#[test]
+fn clones_drop_entries_only_from_their_own_cache() {
+    let mut parent = new_emulator(LOOP);
+    run(&mut parent);
+    let mut child = parent.clone();
+    child.write_bytes_to_code(2, &[2]);
+    assert!(parent.get_decode_cache().contains(0));
+    assert!(!child.get_decode_cache().contains(0));
+
+    run(&mut parent);
+    run(&mut child);
+    assert_eq!(parent.peek_reg(RegisterName::R0), 1);
+    assert_eq!(child.peek_reg(RegisterName::R0), 2);
+}
+
+#[test]
+fn written_code_runs_the_next_time_around() {
+    let mut emulator = new_emulator(LOOP);
+    run(&mut emulator);
+    assert_eq!(emulator.peek_reg(RegisterName::R0), 1);
+    emulator.write_bytes_to_code(2, &[2]);
+    run(&mut emulator);
+    assert_eq!(emulator.peek_reg(RegisterName::R0), 2);
+    // A write that starts before the instruction and ends inside it.
+    emulator.write_bytes_to_code(0xffff, &[0x6e, 0x20, 0x05, 0x03]);
+    run(&mut emulator);
+    assert_eq!(emulator.peek_reg(RegisterName::R0), 2);
+    assert_eq!(emulator.peek_reg(RegisterName::R2), 3);
+}
+
+#[test]
+fn nasal_demons_can_rewrite_the_next_instruction() {
+    // Runs through the whole bytecode, which is all `mov16 r0, r0`.
+    let mut emulator = new_emulator("");
+    emulator.write_bytes_to_code(0, &[0x22, 0x33].repeat(0x8000));
+    let mov_latency = OPCODE_TABLE.get_instruction(0x22).get_latency();
+    let mut cycle_count = 0x8000 * mov_latency;
+    assert!(emulator.run_until_interrupt(&mut cycle_count).is_none());
+
+    let (addr, latency) = (0..1000)
+        .find_map(|_| {
+            let before = emulator.get_bytecode().to_vec();
+            emulator.nasal_demons();
+            let addr = (0..0x10000).find(|&addr| emulator.get_bytecode()[addr] != before[addr])?;
+            let opcode = emulator.get_bytecode()[addr];
+            let latency = OPCODE_TABLE.get_instruction(opcode).get_latency();
+            (addr % 2 == 0 && latency != mov_latency).then_some((addr as u16, latency))
+        })
+        .unwrap();
+    emulator.set_reg(RegisterName::PC, addr);
+    let mut cycle_count = 1000;
+    emulator.step(&mut cycle_count);
+    assert_eq!(cycle_count, 1000 - latency);
+}
+
+#[test]
+fn update_code_changes_the_running_process() {
+    // Replaces its first instruction with `mov16 r5, 2`, then runs it again.
+    let bot = "
+:start
+        mov16 r5, 1
+        cmp16 r4, 0
+        cmovne16 pc, :done
+        mov16 r4, 1
+        mov16 r0, :patch
+        mov16 r1, :start
+        mov16 r2, 3
+        mov16 ax, 0x30
+        syscall
+        mov16 pc, :start
+:done
+        mov16 pc, :done
+
+        .data
+:patch
+        .byte 0x20, 0x08, 0x02, 0x00
+    ";
+    let mut kernel = common::new_kernel(
+        common::kernel_config(1),
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        Rc::new(NullSink),
+        bot,
+        &[(10, 10)],
+    );
+    kernel.step();
+    let emulator = &kernel.get_process(common::init_pid(&kernel, 1)).emulator;
+    assert_eq!(emulator.peek_reg(RegisterName::AX), 1);
+    assert_eq!(emulator.peek_reg(RegisterName::R5), 2);
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use minecrab::config::KernelConfiguration;
+use minecrab::game::map::{GameMap, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::replay::{
+    EventSink, GameEvent, JsonLinesSink, MemorySink, NullSink, OwnedGameEvent,
+};
+use minecrab::kernel::Kernel;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::rc::Rc;
+
+// Passes every event on to two other sinks.
+struct TeeSink(Rc<dyn EventSink>, Rc<dyn EventSink>);
+
+impl EventSink for TeeSink {
+    fn log_event(&self, seq: u64, event: &GameEvent) {
+        self.0.log_event(seq, event);
+        self.1.log_event(seq, event);
+    }
+}
+
+// Keeps only the sequence numbers.
+#[derive(Default)]
+struct SeqSink(RefCell<Vec<u64>>);
+
+impl EventSink for SeqSink {
+    fn log_event(&self, seq: u64, _event: &GameEvent) {
+        self.0.borrow_mut().push(seq);
+    }
+}
+
+fn new_game(event_sink: Rc<dyn EventSink>) -> Kernel {
+    let config = KernelConfiguration {
+        crypto_spawn: BTreeMap::from([("dog".to_string(), vec![(1, 1.0)])]),
+        max_ticks: Some(30),
+        ..common::kernel_config(5)
+    };
+    common::new_kernel(
+        config,
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        event_sink,
+        common::IDLE_BOT,
+        &[(10, 10), (100, 100)],
+    )
+}
+
+#[test]
+fn memory_and_json_lines_sinks_see_the_same_events() {
+    let path = std::env::temp_dir().join(format!("minecrab-events-{}.jsonl", std::process::id()));
+    let memory_sink = Rc::new(MemorySink::new());
+    let json_sink = Rc::new(JsonLinesSink::create(&path).unwrap());
+    let mut kernel = new_game(Rc::new(TeeSink(memory_sink.clone(), json_sink.clone())));
+    kernel.run_full_game();
+    json_sink.flush().unwrap();
+
+    let events = memory_sink.take_events();
+    assert!(matches!(events[0], GameEvent::Seed { seed: 5 }));
+    assert!(matches!(events[1], GameEvent::InitMap { .. }));
+    assert!(matches!(events.last(), Some(GameEvent::Ranking { .. })));
+    let lines = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let lines: Vec<_> = lines.lines().collect();
+    assert_eq!(lines.len(), events.len());
+    for (seq, (line, event)) in lines.iter().zip(events).enumerate() {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(value["seq"], seq);
+        let parsed: OwnedGameEvent = serde_json::from_str(line).unwrap();
+        assert_eq!(parsed, event);
+    }
+}
+
+#[test]
+fn a_new_sink_only_sees_later_events() {
+    let first_sink = Rc::new(MemorySink::new());
+    let mut kernel = new_game(first_sink.clone());
+    kernel.step();
+    let second_sink = Rc::new(MemorySink::new());
+    kernel.set_event_sink(second_sink.clone());
+    kernel.step();
+    kernel.set_event_sink(Rc::new(NullSink));
+    kernel.step();
+
+    let first_events = first_sink.take_events();
+    let second_events = second_sink.take_events();
+    assert_eq!(first_events.last(), Some(&GameEvent::TickEnd { tick: 0 }));
+    assert_eq!(second_events[0], GameEvent::TickStart { tick: 1 });
+    assert_eq!(second_events.last(), Some(&GameEvent::TickEnd { tick: 1 }));
+    // The map logs its challenges to the new sink as well.
+    assert!(second_events
+        .iter()
+        .any(|event| matches!(event, GameEvent::NewChallenge { .. })));
+}
+
+#[test]
+fn sequence_numbers_continue_across_sinks() {
+    let sinks: Vec<_> = (0..3).map(|_| Rc::new(SeqSink::default())).collect();
+    let mut kernel = new_game(sinks[0].clone());
+    kernel.step();
+    for sink in &sinks[1..] {
+        kernel.set_event_sink(sink.clone());
+        kernel.step();
+    }
+    kernel.finish();
+
+    let seqs: Vec<_> = sinks.iter().flat_map(|sink| sink.0.take()).collect();
+    assert_eq!(seqs, (0..seqs.len() as u64).collect::<Vec<_>>());
+}
+
+#[test]
+fn ties_share_a_rank() {
+    let sink = Rc::new(MemorySink::new());
+    let mut kernel = common::new_kernel(
+        common::kernel_config(5),
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        sink.clone(),
+        common::IDLE_BOT,
+        &[(10, 10), (100, 100), (10, 100), (100, 10)],
+    );
+    for (uid, bonus) in [(2, 10), (3, 5), (4, 10)] {
+        kernel.get_user_mut(uid).score += bonus;
+    }
+    let ranking = kernel.finish();
+
+    let ranks: Vec<_> = ranking
+        .iter()
+        .map(|entry| (entry.rank, entry.uid, entry.score - ranking[3].score))
+        .collect();
+    assert_eq!(ranks, [(1, 2, 10), (1, 4, 10), (3, 3, 5), (4, 1, 0)]);
+    assert_eq!(
+        sink.take_events().last(),
+        Some(&GameEvent::Ranking { ranking })
+    );
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use minecrab::config::KernelConfiguration;
+use minecrab::game::crypto::Wallet;
+use minecrab::game::map::{GameMap, Location, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::replay::{GameEndReason, GameEvent, MemorySink};
+use minecrab::kernel::Kernel;
+
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+const TWO_USERS: [Location; 2] = [(10, 10), (100, 100)];
+
+fn new_game(config: KernelConfiguration, spawn_points: &[Location]) -> (Kernel, Rc<MemorySink>) {
+    let sink = Rc::new(MemorySink::new());
+    let kernel = common::new_kernel(
+        config,
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        sink.clone(),
+        common::IDLE_BOT,
+        spawn_points,
+    );
+    (kernel, sink)
+}
+
+#[test]
+fn step_runs_one_tick() {
+    let (mut kernel, sink) = new_game(common::kernel_config(7), &TWO_USERS);
+    let init_pid = common::init_pid(&kernel, 1);
+    kernel.fork_process(init_pid).unwrap();
+    sink.take_events();
+    for tick in 0..5 {
+        let summary = kernel.step();
+        let events = sink.take_events();
+        assert_eq!(summary.tick, tick);
+        assert_eq!(summary.events_emitted, events.len() as u64);
+        assert_eq!(events[0], GameEvent::TickStart { tick });
+        assert_eq!(events.last(), Some(&GameEvent::TickEnd { tick }));
+        assert_eq!(summary.alive_processes, BTreeMap::from([(1, 2), (2, 1)]));
+        assert!(!summary.game_over);
+        assert_eq!(summary.end_reason, None);
+        assert_eq!(kernel.get_ticks_elapsed(), tick + 1);
+    }
+}
+
+#[test]
+fn step_reports_the_end_of_the_game() {
+    let config = KernelConfiguration {
+        initd_lifetime: 3,
+        ..common::kernel_config(7)
+    };
+    let (mut kernel, _) = new_game(config, &TWO_USERS);
+    for _ in 0..2 {
+        assert!(!kernel.step().game_over);
+    }
+    let summary = kernel.step();
+    assert!(summary.game_over);
+    assert_eq!(summary.end_reason, Some(GameEndReason::AllUsersLeft));
+    assert_eq!(summary.alive_processes, BTreeMap::from([(1, 0), (2, 0)]));
+}
+
+#[test]
+fn finish_turns_wallets_into_scores() {
+    let (mut kernel, sink) = new_game(common::kernel_config(7), &TWO_USERS);
+    for _ in 0..3 {
+        kernel.step();
+    }
+    let before = [1, 2].map(|uid| {
+        let user = kernel.get_user(uid);
+        (user.score, user.wallet.clone())
+    });
+    sink.take_events();
+    let ranking = kernel.finish();
+
+    assert_eq!(
+        sink.take_events()[0],
+        GameEvent::GameOver {
+            tick: 3,
+            reason: GameEndReason::Stopped,
+        }
+    );
+    for (uid, (score, wallet)) in [1, 2].into_iter().zip(before) {
+        let user = kernel.get_user(uid);
+        assert_eq!(user.wallet, Wallet::default());
+        assert_eq!(user.score, score + wallet.convert_to_score());
+        let entry = ranking.iter().find(|entry| entry.uid == uid).unwrap();
+        assert_eq!(entry.score, user.score);
+        assert_eq!(entry.wallet, wallet);
+    }
+}
+
+// Plays the game to its end and returns the reason given by its GameOver
+// event.
+fn play_to_the_end(kernel: &mut Kernel, sink: &MemorySink) -> GameEndReason {
+    kernel.run_full_game();
+    sink.take_events()
+        .into_iter()
+        .find_map(|event| match event {
+            GameEvent::GameOver { reason, .. } => Some(reason),
+            _ => None,
+        })
+        .unwrap()
+}
+
+#[test]
+fn max_ticks_ends_the_game() {
+    let config = KernelConfiguration {
+        max_ticks: Some(4),
+        ..common::kernel_config(7)
+    };
+    let (mut kernel, sink) = new_game(config, &TWO_USERS);
+    assert_eq!(play_to_the_end(&mut kernel, &sink), GameEndReason::MaxTicks);
+    assert_eq!(kernel.get_ticks_elapsed(), 4);
+}
+
+#[test]
+fn last_user_standing_ends_the_game() {
+    let config = KernelConfiguration {
+        last_user_standing: true,
+        max_ticks: Some(20),
+        ..common::kernel_config(7)
+    };
+    let (mut kernel, sink) = new_game(config, &TWO_USERS);
+    kernel.step();
+    kernel.kill_process_recursive(common::init_pid(&kernel, 2));
+    assert_eq!(
+        play_to_the_end(&mut kernel, &sink),
+        GameEndReason::LastUserStanding
+    );
+    assert_eq!(kernel.get_ticks_elapsed(), 1);
+
+    // A user playing alone is not the last one standing.
+    let config = KernelConfiguration {
+        last_user_standing: true,
+        max_ticks: Some(20),
+        ..common::kernel_config(7)
+    };
+    let (mut kernel, sink) = new_game(config, &[(10, 10)]);
+    assert_eq!(play_to_the_end(&mut kernel, &sink), GameEndReason::MaxTicks);
+}
+
+#[test]
+fn score_threshold_ends_the_game() {
+    let config = KernelConfiguration {
+        score_threshold: Some(100),
+        ..common::kernel_config(7)
+    };
+    let (mut kernel, sink) = new_game(config, &TWO_USERS);
+    let best_score = |kernel: &Kernel| kernel.get_user(1).score.max(kernel.get_user(2).score);
+    while !kernel.is_game_over() {
+        assert!(best_score(&kernel) < 100);
+        kernel.step();
+    }
+    assert!(kernel.get_ticks_elapsed() > 1);
+    assert!(best_score(&kernel) >= 100);
+    assert_eq!(
+        play_to_the_end(&mut kernel, &sink),
+        GameEndReason::ScoreThreshold
+    );
+}
@@ -0,0 +1,253 @@
+mod common;
+
+use minecrab::config::KernelConfiguration;
+use minecrab::game::map::{GameMap, Location, MapGeometry};
+use minecrab::game::mapfile::{MapFile, MapFormat};
+use minecrab::game::mapgen::{self, MapGenOptions, MapStyle};
+use minecrab::game::replay::NullSink;
+use minecrab::kernel::syscall::SYSCALL_TABLE;
+use minecrab::kernel::Kernel;
+
+use std::collections::{BTreeMap, HashSet};
+use std::rc::Rc;
+
+fn geometry(width: usize, height: usize, bounded: bool) -> MapGeometry {
+    MapGeometry::new(width, height, bounded).unwrap()
+}
+
+fn new_map(geometry: MapGeometry, walls: &[Location]) -> GameMap {
+    let mut map_data = vec![0; geometry.cell_count()];
+    for &wall in walls {
+        map_data[geometry.cell_index(wall)] = 1;
+    }
+    GameMap::new(&map_data, geometry).unwrap()
+}
+
+fn new_kernel(game_map: GameMap, crypto_spawn: BTreeMap<String, Vec<(i64, f64)>>) -> Kernel {
+    let config = KernelConfiguration {
+        crypto_spawn,
+        ..common::kernel_config(3)
+    };
+    common::new_kernel(
+        config,
+        game_map,
+        Rc::new(NullSink),
+        common::IDLE_BOT,
+        &[(1, 1)],
+    )
+}
+
+fn call(kernel: &mut Kernel, name: &str, args: (u16, u16, u16, u16, u16, u16)) -> Option<u16> {
+    let pid = common::init_pid(kernel, 1);
+    SYSCALL_TABLE
+        .get_syscall_by_name(name)
+        .unwrap()
+        .call(kernel, pid, args)
+}
+
+#[test]
+fn small_maps_wrap_around() {
+    let geometry = geometry(10, 6, false);
+    assert_eq!(geometry.locate(-1, 6), Some((9, 0)));
+    assert_eq!(geometry.locate(23, -13), Some((3, 5)));
+    assert_eq!(geometry.offset((9, 5), 1, 1), Some((0, 0)));
+    assert_eq!(geometry.distance((0, 0), (9, 5)), 1);
+    assert_eq!(geometry.distance((0, 0), (5, 3)), 5);
+}
+
+#[test]
+fn bounded_maps_stop_at_borders() {
+    let geometry = geometry(10, 6, true);
+    assert_eq!(geometry.locate(-1, 0), None);
+    assert_eq!(geometry.locate(10, 0), None);
+    assert_eq!(geometry.locate(0, 6), None);
+    assert_eq!(geometry.locate(9, 5), Some((9, 5)));
+    assert_eq!(geometry.offset((9, 5), 1, 0), None);
+    assert_eq!(geometry.distance((0, 0), (9, 5)), 9);
+}
+
+#[test]
+fn paths_only_wrap_around_wrapping_maps() {
+    let map = new_map(geometry(16, 16, false), &[]);
+    assert_eq!(
+        map.pathfind((0, 0), (15, 0), 100).unwrap().locations,
+        [(15, 0)]
+    );
+
+    let map = new_map(geometry(16, 16, true), &[]);
+    let path = map.pathfind((0, 0), (15, 0), 100).unwrap();
+    assert_eq!(path.cost, 15);
+    assert!(path
+        .locations
+        .iter()
+        .all(|&location| map.geometry().contains(location)));
+}
+
+#[test]
+fn read_map_beyond_bounded_borders_reads_walls() {
+    let args = (0x100, 6, 0, 9, 0, 0);
+
+    let mut kernel = new_kernel(new_map(geometry(8, 8, false), &[(0, 0)]), BTreeMap::new());
+    assert_eq!(call(&mut kernel, "ReadMap", args), Some(4));
+    let pid = common::init_pid(&kernel, 1);
+    let data = kernel
+        .get_process(pid)
+        .emulator
+        .peek_bytes_from_mem(0x100, 4);
+    assert_eq!(data, [0, 0, 1, 0]);
+
+    let mut kernel = new_kernel(new_map(geometry(8, 8, true), &[(0, 0)]), BTreeMap::new());
+    assert_eq!(call(&mut kernel, "ReadMap", args), Some(4));
+    let pid = common::init_pid(&kernel, 1);
+    let data = kernel
+        .get_process(pid)
+        .emulator
+        .peek_bytes_from_mem(0x100, 4);
+    assert_eq!(data, [0, 0, 1, 1]);
+}
+
+#[test]
+fn targets_beyond_bounded_borders_fail() {
+    let mut kernel = new_kernel(new_map(geometry(8, 8, false), &[]), BTreeMap::new());
+    assert_eq!(
+        call(&mut kernel, "PathFind", (0x100, 10, 1, 5, 0, 0)),
+        Some(1)
+    );
+    assert_eq!(call(&mut kernel, "Attack1", (9, 1, 0, 0, 0, 0)), Some(1));
+
+    let mut kernel = new_kernel(new_map(geometry(8, 8, true), &[]), BTreeMap::new());
+    assert_eq!(call(&mut kernel, "PathFind", (0x100, 10, 1, 5, 0, 0)), None);
+    assert_eq!(call(&mut kernel, "Attack1", (9, 1, 0, 0, 0, 0)), None);
+    assert_eq!(call(&mut kernel, "Attack1", (1, 1, 0, 0, 0, 0)), Some(1));
+}
+
+#[test]
+fn challenges_spawn_inside_small_maps() {
+    let crypto_spawn = BTreeMap::from([("dog".to_string(), vec![(1, 1.0)])]);
+    let mut kernel = new_kernel(new_map(geometry(20, 12, true), &[]), crypto_spawn);
+    for _ in 0..200 {
+        kernel.step();
+    }
+    let map = kernel.get_game_map();
+    let challenges = (0..20u8)
+        .flat_map(|x| (0..12u8).map(move |y| (x, y)))
+        .filter(|&location| map.get_cell(location).crypto_data().is_some())
+        .count();
+    assert!(challenges > 0);
+}
+
+#[test]
+fn sizes_must_fit_the_largest_map() {
+    assert!(MapGeometry::new(1, 1, true).is_ok());
+    assert!(MapGeometry::new(256, 256, false).is_ok());
+    for (width, height) in [(0, 8), (8, 0), (257, 8), (8, 257)] {
+        let err = MapGeometry::new(width, height, false).err().unwrap();
+        assert_eq!(
+            err.message,
+            format!(
+                "Map size must be between 1x1 and 256x256, not {}x{}",
+                width, height
+            )
+        );
+    }
+}
+
+#[test]
+fn raw_maps_take_their_size_from_the_config() {
+    let map_data = vec![0; 64];
+    let map_file = MapFile::parse(&map_data, MapFormat::Raw, Some((8, 8))).unwrap();
+    assert_eq!((map_file.width, map_file.height), (8, 8));
+    let err = MapFile::parse(&map_data, MapFormat::Raw, None)
+        .err()
+        .unwrap();
+    assert_eq!(err.message, "Map data must be 65536 bytes, not 64");
+
+    let ascii = map_file.to_bytes(MapFormat::Ascii).unwrap();
+    assert_eq!(ascii.len(), 9 * 8);
+    let err = MapFile::parse(&ascii, MapFormat::Ascii, Some((8, 4)))
+        .err()
+        .unwrap();
+    assert_eq!(err.message, "Map is 8x8, not 8x4");
+    let pgm = map_file.to_bytes(MapFormat::Pgm).unwrap();
+    let parsed = MapFile::parse(&pgm, MapFormat::Pgm, None).unwrap();
+    assert_eq!((parsed.width, parsed.height), (8, 8));
+}
+
+// Checks that every land cell of a generated map is reachable without leaving
+// the map, and returns the number of land cells.
+fn assert_connected(map_data: &[u8], geometry: MapGeometry, start: Location) -> usize {
+    let mut seen = HashSet::from([start]);
+    let mut stack = vec![start];
+    while let Some(location) = stack.pop() {
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let Some(next) = geometry.offset(location, dx, dy) else {
+                    continue;
+                };
+                if map_data[geometry.cell_index(next)] == 0 && seen.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    let land = map_data.iter().filter(|&&cell| cell == 0).count();
+    assert_eq!(seen.len(), land);
+    land
+}
+
+#[test]
+fn mapgen_makes_small_and_bounded_maps() {
+    let cases = [
+        (MapStyle::Caves, geometry(40, 30, true), 2),
+        (MapStyle::Caves, geometry(48, 48, false), 4),
+        (MapStyle::Arena, geometry(33, 33, true), 4),
+        (MapStyle::Maze, geometry(31, 31, true), 4),
+        (MapStyle::Maze, geometry(32, 20, false), 2),
+    ];
+    for (style, geometry, symmetry) in cases {
+        let map = mapgen::generate(&MapGenOptions {
+            style,
+            geometry,
+            seed: 11,
+            players: symmetry,
+            symmetry,
+            wall_density: None,
+        })
+        .unwrap();
+        assert_eq!(map.map_data.len(), geometry.cell_count());
+        for &spawn_point in &map.spawn_points {
+            assert!(geometry.contains(spawn_point));
+            assert_eq!(map.map_data[geometry.cell_index(spawn_point)], 0);
+        }
+        assert!(assert_connected(&map.map_data, geometry, map.spawn_points[0]) > 0);
+        GameMap::new(&map.map_data, geometry).unwrap();
+    }
+}
+
+#[test]
+fn mapgen_rejects_sizes_its_styles_cannot_fill() {
+    let options = |style, geometry, symmetry| MapGenOptions {
+        style,
+        geometry,
+        seed: 0,
+        players: 4,
+        symmetry,
+        wall_density: None,
+    };
+    let cases = [
+        (MapStyle::Caves, geometry(40, 30, false), 4),
+        (MapStyle::Maze, geometry(32, 32, true), 1),
+        (MapStyle::Maze, geometry(31, 31, false), 1),
+        (
+            MapStyle::Arena,
+            MapGeometry {
+                width: 300,
+                ..geometry(30, 30, false)
+            },
+            1,
+        ),
+    ];
+    for (style, geometry, symmetry) in cases {
+        assert!(mapgen::generate(&options(style, geometry, symmetry)).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use minecrab::config::{GameConfiguration, RawUserConfiguration};
+use minecrab::game::map::{GameMap, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::mapfile::{MapFile, MapFormat};
+use minecrab::vm::emulator::{BYTECODE_SIZE, MEMORY_SIZE};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+fn sample_map() -> MapFile {
+    let mut map_data = vec![0; MAP_WIDTH * MAP_HEIGHT];
+    for y in 0..MAP_HEIGHT {
+        map_data[3 * MAP_HEIGHT + y] = 1;
+    }
+    map_data[200 * MAP_HEIGHT + 7] = 1;
+    MapFile {
+        map_data,
+        width: MAP_WIDTH,
+        height: MAP_HEIGHT,
+        spawn_points: BTreeMap::from([(1, (10, 20)), (2, (250, 5))]),
+    }
+}
+
+fn ascii_rows() -> Vec<Vec<u8>> {
+    vec![vec![b'.'; MAP_WIDTH]; MAP_HEIGHT]
+}
+
+fn join_rows(rows: &[Vec<u8>]) -> Vec<u8> {
+    rows.join(&b'\n')
+}
+
+fn parse_error(bytes: &[u8], format: MapFormat) -> String {
+    match MapFile::parse(bytes, format, None) {
+        Ok(_) => panic!("parsed an invalid map"),
+        Err(err) => err.message,
+    }
+}
+
+#[test]
+fn format_follows_extension() {
+    assert_eq!(
+        MapFormat::from_path(Path::new("a/map.txt")),
+        MapFormat::Ascii
+    );
+    assert_eq!(MapFormat::from_path(Path::new("map.PGM")), MapFormat::Pgm);
+    assert_eq!(MapFormat::from_path(Path::new("map.bin")), MapFormat::Raw);
+    assert_eq!(MapFormat::from_path(Path::new("map")), MapFormat::Raw);
+}
+
+#[test]
+fn every_format_round_trips() {
+    let map = sample_map();
+    for format in [MapFormat::Raw, MapFormat::Ascii, MapFormat::Pgm] {
+        let parsed = MapFile::parse(&map.to_bytes(format).unwrap(), format, None).unwrap();
+        assert_eq!(parsed.map_data, map.map_data, "{:?}", format);
+    }
+    let parsed = MapFile::parse(
+        &map.to_bytes(MapFormat::Ascii).unwrap(),
+        MapFormat::Ascii,
+        None,
+    )
+    .unwrap();
+    assert_eq!(parsed.spawn_points, map.spawn_points);
+}
+
+#[test]
+fn ascii_rows_run_along_x() {
+    let mut rows = ascii_rows();
+    rows[7][200] = b'#';
+    rows[20][10] = b'3';
+    let map = MapFile::parse(&join_rows(&rows), MapFormat::Ascii, None).unwrap();
+    let game_map = GameMap::from_map_data(&map.map_data).unwrap();
+    assert_eq!(game_map.get_cell((200, 7)).status(), 1);
+    assert_eq!(game_map.get_cell((7, 200)).status(), 0);
+    assert_eq!(game_map.get_cell((10, 20)).status(), 0);
+    assert_eq!(map.spawn_points, BTreeMap::from([(3, (10, 20))]));
+}
+
+#[test]
+fn ascii_accepts_crlf() {
+    let mut bytes = ascii_rows().join(&b"\r\n"[..]);
+    bytes.extend(b"\r\n");
+    MapFile::parse(&bytes, MapFormat::Ascii, None).unwrap();
+}
+
+#[test]
+fn ascii_errors_point_at_the_cell() {
+    let mut rows = ascii_rows();
+    rows[9][4] = b'?';
+    assert_eq!(
+        parse_error(&join_rows(&rows), MapFormat::Ascii),
+        "Invalid character 0x3f at (4, 9)"
+    );
+
+    let mut rows = ascii_rows();
+    rows[1][1] = b'2';
+    rows[5][5] = b'2';
+    assert_eq!(
+        parse_error(&join_rows(&rows), MapFormat::Ascii),
+        "Spawn point of uid 2 marked at both (1, 1) and (5, 5)"
+    );
+
+    let mut rows = ascii_rows();
+    rows[12].pop();
+    assert_eq!(
+        parse_error(&join_rows(&rows), MapFormat::Ascii),
+        "Row 12 must have 256 cells like the first row, not 255"
+    );
+}
+
+#[test]
+fn ascii_rejects_empty_maps() {
+    for bytes in [&b""[..], b"\n"] {
+        let err = MapFile::parse(bytes, MapFormat::Ascii, None).err().unwrap();
+        assert_eq!(err.to_string(), "Map has no rows");
+    }
+    assert_eq!(
+        parse_error(b"\n\n", MapFormat::Ascii),
+        "Map size must be between 1x1 and 256x256, not 0x2"
+    );
+}
+
+#[test]
+fn raw_errors_point_at_the_cell() {
+    let mut map_data = vec![0; MAP_WIDTH * MAP_HEIGHT];
+    map_data[2 * MAP_HEIGHT + 9] = 7;
+    assert_eq!(
+        parse_error(&map_data, MapFormat::Raw),
+        "Invalid cell value 7 at (2, 9)"
+    );
+    assert_eq!(
+        GameMap::from_map_data(&map_data).err().unwrap().message,
+        "Invalid cell value 7 at (2, 9)"
+    );
+    assert_eq!(
+        parse_error(&map_data[1..], MapFormat::Raw),
+        "Map data must be 65536 bytes, not 65535"
+    );
+}
+
+#[test]
+fn plain_pgm_with_comments() {
+    let mut text = String::from("P2\n# made by hand\n256 256\n# white is land\n15\n");
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            text += if (x, y) == (30, 40) { "0 " } else { "15 " };
+        }
+        text += "\n";
+    }
+    let map = MapFile::parse(text.as_bytes(), MapFormat::Pgm, None).unwrap();
+    let game_map = GameMap::from_map_data(&map.map_data).unwrap();
+    assert_eq!(game_map.get_cell((30, 40)).status(), 1);
+    assert_eq!(map.map_data.iter().filter(|&&cell| cell == 1).count(), 1);
+}
+
+#[test]
+fn pgm_errors_point_at_the_pixel() {
+    let mut bytes = sample_map().to_bytes(MapFormat::Pgm).unwrap();
+    let header_len = bytes.len() - MAP_WIDTH * MAP_HEIGHT;
+    bytes[header_len + 9 * MAP_WIDTH + 4] = 128;
+    assert_eq!(
+        parse_error(&bytes, MapFormat::Pgm),
+        "Invalid pixel value 128 at (4, 9)"
+    );
+    assert_eq!(
+        parse_error(b"P5\n128 256\n255\n", MapFormat::Pgm),
+        "Image must have 32768 pixels, not 0"
+    );
+    assert_eq!(
+        parse_error(b"P5\n300 256\n255\n", MapFormat::Pgm),
+        "Map size must be between 1x1 and 256x256, not 300x256"
+    );
+    assert_eq!(parse_error(b"P6\n", MapFormat::Pgm), "Not a PGM image");
+}
+
+#[test]
+fn spawn_markers_fill_in_missing_spawn_points() {
+    let dir = std::env::temp_dir().join(format!("minecrab-mapfile-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let bytecode_path = dir.join("user.bin");
+    let memory_path = dir.join("user.mem");
+    fs::write(&bytecode_path, vec![0; BYTECODE_SIZE]).unwrap();
+    fs::write(&memory_path, vec![0; MEMORY_SIZE]).unwrap();
+    let user = |uid, spawn_point| RawUserConfiguration {
+        initd_bytecode: bytecode_path.clone(),
+        initd_memory: memory_path.clone(),
+        uid,
+        spawn_point,
+    };
+    let mut config: GameConfiguration = serde_json::from_str(
+        r#"{
+            "user_configs": [],
+            "default_nice": 0,
+            "initd_lifetime": 100,
+            "max_processes": 16,
+            "mapdata_path": "map.txt",
+            "crypto_spawn": {}
+        }"#,
+    )
+    .unwrap();
+    let map = sample_map();
+
+    config.user_configs = vec![user(1, None), user(2, Some((0, 0)))];
+    let spawn_points: Vec<_> = config
+        .get_user_configs(&map)
+        .unwrap()
+        .iter()
+        .map(|user| user.spawn_point)
+        .collect();
+    assert_eq!(spawn_points, [(10, 20), (0, 0)]);
+
+    config.user_configs = vec![user(3, None)];
+    let err = config.get_user_configs(&map).err().unwrap();
+    assert_eq!(err.message, "No spawn point for user 3");
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,125 @@
+use minecrab::game::map::{GameMap, Location, MapGeometry, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::mapgen::{self, GeneratedMap, MapGenOptions, MapStyle};
+
+use std::collections::HashSet;
+use std::path::Path;
+
+fn generate(style: MapStyle, players: usize, symmetry: usize) -> GeneratedMap {
+    mapgen::generate(&MapGenOptions {
+        style,
+        geometry: MapGeometry::default(),
+        seed: 5,
+        players,
+        symmetry,
+        wall_density: None,
+    })
+    .unwrap()
+}
+
+fn is_wall(map: &GeneratedMap, (x, y): Location) -> bool {
+    map.map_data[x as usize * MAP_HEIGHT + y as usize] == 1
+}
+
+// Checks that every land cell can reach every other one, moving the way
+// processes do.
+fn assert_connected(map: &GeneratedMap) {
+    let start = map.spawn_points[0];
+    let mut seen = HashSet::from([start]);
+    let mut stack = vec![start];
+    while let Some((x, y)) = stack.pop() {
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy));
+                if !is_wall(map, next) && seen.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    let land = map.map_data.iter().filter(|&&cell| cell == 0).count();
+    assert_eq!(seen.len(), land);
+}
+
+#[test]
+fn every_style_is_connected_with_spawns_on_land() {
+    for style in [MapStyle::Caves, MapStyle::Maze, MapStyle::Arena] {
+        for symmetry in [1, 2, 4] {
+            let map = generate(style, 4, symmetry);
+            assert_eq!(map.map_data.len(), MAP_WIDTH * MAP_HEIGHT);
+            assert!(map.map_data.iter().all(|&cell| cell <= 1));
+            assert_eq!(map.spawn_points.len(), 4);
+            let distinct: HashSet<_> = map.spawn_points.iter().collect();
+            assert_eq!(distinct.len(), 4);
+            for &spawn_point in &map.spawn_points {
+                assert!(!is_wall(&map, spawn_point), "{:?} spawns in a wall", style);
+            }
+            assert_connected(&map);
+            GameMap::from_map_data(&map.map_data).unwrap();
+        }
+    }
+}
+
+#[test]
+fn symmetric_maps_turn_into_themselves() {
+    for style in [MapStyle::Caves, MapStyle::Maze, MapStyle::Arena] {
+        let map = generate(style, 4, 4);
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                assert_eq!(is_wall(&map, (x, y)), is_wall(&map, (y.wrapping_neg(), x)));
+            }
+        }
+        let spawn_points: HashSet<_> = map.spawn_points.iter().copied().collect();
+        for &(x, y) in &map.spawn_points {
+            assert!(spawn_points.contains(&(y.wrapping_neg(), x)));
+        }
+
+        let map = generate(style, 2, 2);
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                assert_eq!(
+                    is_wall(&map, (x, y)),
+                    is_wall(&map, (x.wrapping_neg(), y.wrapping_neg()))
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn same_seed_gives_same_map() {
+    let map1 = generate(MapStyle::Caves, 3, 1);
+    let map2 = generate(MapStyle::Caves, 3, 1);
+    assert_eq!(map1.map_data, map2.map_data);
+    assert_eq!(map1.spawn_points, map2.spawn_points);
+}
+
+#[test]
+fn rejects_bad_options() {
+    let options = |players, symmetry, wall_density| MapGenOptions {
+        style: MapStyle::Arena,
+        geometry: MapGeometry::default(),
+        seed: 0,
+        players,
+        symmetry,
+        wall_density,
+    };
+    assert!(mapgen::generate(&options(3, 3, None)).is_err());
+    assert!(mapgen::generate(&options(3, 2, None)).is_err());
+    assert!(mapgen::generate(&options(0, 1, None)).is_err());
+    assert!(mapgen::generate(&options(2, 1, Some(1.5))).is_err());
+    let err = mapgen::generate(&options(2, 3, None)).err().unwrap();
+    assert_eq!(err.to_string(), "Symmetry must be 1, 2 or 4, not 3");
+}
+
+#[test]
+fn game_config_uses_spawn_points() {
+    let map = generate(MapStyle::Arena, 2, 2);
+    let config = map.to_game_config(Path::new("arena.bin"));
+    assert_eq!(config.mapdata_path, Path::new("arena.bin"));
+    let spawn_points: Vec<_> = config
+        .user_configs
+        .iter()
+        .map(|user| user.spawn_point.unwrap())
+        .collect();
+    assert_eq!(spawn_points, map.spawn_points);
+}
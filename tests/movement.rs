@@ -0,0 +1,116 @@
+use minecrab::game::map::{GameMap, Location, MAP_HEIGHT, MAP_WIDTH};
+
+const PID: u16 = 1;
+
+fn map_with_walls(walls: &[Location]) -> GameMap {
+    let mut map_data = vec![0; MAP_WIDTH * MAP_HEIGHT];
+    for &(x, y) in walls {
+        map_data[x as usize * MAP_HEIGHT + y as usize] = 1;
+    }
+    GameMap::from_map_data(&map_data).unwrap()
+}
+
+// The example from the docs of `move`:
+// ....
+// ##B.
+// .A##
+// ....
+const SQUEEZE_WALLS: [Location; 4] = [(11, 10), (11, 11), (12, 12), (12, 13)];
+const SQUEEZE_A: Location = (12, 11);
+const SQUEEZE_B: Location = (11, 12);
+
+#[test]
+fn move_squeezes_diagonally_between_walls() {
+    let mut map = map_with_walls(&SQUEEZE_WALLS);
+    map.add_process_to_map(PID, SQUEEZE_A);
+    assert!(map.move_process_to(PID, SQUEEZE_B));
+    assert_eq!(map.get_process_location(PID), SQUEEZE_B);
+    assert!(map.move_process_to(PID, SQUEEZE_A));
+    assert_eq!(map.get_process_location(PID), SQUEEZE_A);
+}
+
+#[test]
+fn move_into_wall_fails() {
+    let mut map = map_with_walls(&SQUEEZE_WALLS);
+    map.add_process_to_map(PID, SQUEEZE_A);
+    for wall in [(11, 10), (11, 11), (12, 12)] {
+        assert!(!map.move_process_to(PID, wall));
+        assert_eq!(map.get_process_location(PID), SQUEEZE_A);
+    }
+}
+
+#[test]
+fn pathfind_squeezes_diagonally_between_walls() {
+    let map = map_with_walls(&SQUEEZE_WALLS);
+    assert_eq!(
+        map.pathfind(SQUEEZE_A, SQUEEZE_B, 1).unwrap().locations,
+        [SQUEEZE_B]
+    );
+    assert_eq!(
+        map.pathfind(SQUEEZE_B, SQUEEZE_A, 1).unwrap().locations,
+        [SQUEEZE_A]
+    );
+}
+
+#[test]
+fn pathfind_squeezes_through_diagonal_gap_in_wall() {
+    // A wall running down the map whose only gap is two cells touching at a
+    // corner, so crossing it takes a diagonal step.
+    let mut walls = vec![];
+    for x in 0..=255u8 {
+        if x != 20 {
+            walls.push((x, 50));
+        }
+        if x != 21 {
+            walls.push((x, 51));
+        }
+    }
+    let map = map_with_walls(&walls);
+    let path = map.pathfind((21, 49), (20, 52), 16).unwrap();
+    assert_eq!(path.locations, [(20, 50), (21, 51), (20, 52)]);
+}
+
+#[test]
+fn pathfind_routes_around_walls() {
+    let walls = [(9, 11), (10, 11), (11, 11)];
+    let map = map_with_walls(&walls);
+    let path = map.pathfind((10, 10), (10, 12), 16).unwrap();
+    assert_eq!(path.locations.len(), 4);
+    assert_eq!(path.cost, 4);
+    assert!(path
+        .locations
+        .iter()
+        .all(|location| !walls.contains(location)));
+    assert_eq!(map.pathfind((10, 10), (10, 12), 3), None);
+}
+
+#[test]
+fn pathfind_to_wall_fails() {
+    let map = map_with_walls(&SQUEEZE_WALLS);
+    assert_eq!(map.pathfind(SQUEEZE_A, (12, 12), 16), None);
+}
+
+#[test]
+fn empty_location_nearby_is_never_a_wall() {
+    // Every cell around the center is a wall except one, so each attempt either
+    // finds that cell or fails.
+    let open = (101, 99);
+    let mut walls = vec![];
+    for x in 98..=102 {
+        for y in 98..=102 {
+            if (x, y) != open {
+                walls.push((x, y));
+            }
+        }
+    }
+    let mut map = map_with_walls(&walls);
+    map.reseed(7);
+    let mut found = 0;
+    for _ in 0..1000 {
+        if let Some(location) = map.find_empty_location_nearby((100, 100), 5, 2) {
+            assert_eq!(location, open);
+            found += 1;
+        }
+    }
+    assert!(found > 0);
+}
@@ -0,0 +1,340 @@
+mod common;
+
+use minecrab::config::KernelConfiguration;
+use minecrab::game::crypto::{CryptoCurrency, Wallet};
+use minecrab::game::map::{GameMap, Location, MapGeometry};
+use minecrab::game::replay::NullSink;
+use minecrab::kernel::syscall::{SIGHT_RADIUS, SYSCALL_TABLE};
+use minecrab::kernel::Kernel;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+const BED: u16 = 0xbed;
+
+const GEOMETRY: MapGeometry = MapGeometry {
+    width: 32,
+    height: 32,
+    bounded: false,
+};
+
+fn bed_spawn() -> BTreeMap<String, Vec<(i64, f64)>> {
+    BTreeMap::from([("bed".to_string(), vec![(1, 1.0)])])
+}
+
+fn new_kernel(
+    spawn_points: &[Location],
+    crypto_spawn: BTreeMap<String, Vec<(i64, f64)>>,
+) -> Kernel {
+    let config = KernelConfiguration {
+        crypto_spawn,
+        ..common::kernel_config(9)
+    };
+    common::new_kernel(
+        config,
+        GameMap::new(&vec![0; GEOMETRY.cell_count()], GEOMETRY).unwrap(),
+        Rc::new(NullSink),
+        common::IDLE_BOT,
+        spawn_points,
+    )
+}
+
+fn call(kernel: &mut Kernel, pid: u16, name: &str, args: (u16, u16, u16)) -> Option<u16> {
+    SYSCALL_TABLE.get_syscall_by_name(name).unwrap().call(
+        kernel,
+        pid,
+        (args.0, args.1, args.2, 0, 0, 0),
+    )
+}
+
+fn read_memory(kernel: &Kernel, pid: u16, addr: u16, len: usize) -> Vec<u8> {
+    kernel
+        .get_process(pid)
+        .emulator
+        .peek_bytes_from_mem(addr, len)
+}
+
+// The nearest bed challenge found by looking at every cell of the map.
+fn nearest_bed(kernel: &Kernel, center: Location, radius: u16) -> Option<Location> {
+    let map = kernel.get_game_map();
+    (0..32u8)
+        .flat_map(|x| (0..32u8).map(move |y| (x, y)))
+        .filter(|&location| {
+            map.get_cell(location)
+                .crypto_data()
+                .is_some_and(|data| data[..2] == BED.to_le_bytes())
+        })
+        .map(|location| (GEOMETRY.distance(center, location), location))
+        .filter(|&(distance, _)| distance as u16 <= radius)
+        .min()
+        .map(|(_, location)| location)
+}
+
+#[test]
+fn init_finds_the_nearest_challenge_anywhere() {
+    let mut kernel = new_kernel(&[(5, 5)], bed_spawn());
+    for _ in 0..40 {
+        kernel.step();
+    }
+    let pid = common::init_pid(&kernel, 1);
+    for radius in [0, 3, 8, 16] {
+        let found = match call(&mut kernel, pid, "FindChallenge", (0x100, BED, radius)) {
+            Some(2) => {
+                let data = read_memory(&kernel, pid, 0x100, 2);
+                Some((data[0], data[1]))
+            }
+            None => None,
+            ret => panic!("unexpected return value {:?}", ret),
+        };
+        assert_eq!(
+            found,
+            nearest_bed(&kernel, (5, 5), radius),
+            "radius {}",
+            radius
+        );
+    }
+    assert!(nearest_bed(&kernel, (5, 5), 16).is_some());
+    assert_eq!(
+        call(&mut kernel, pid, "FindChallenge", (0x100, 0x420, 16)),
+        None
+    );
+}
+
+#[test]
+fn other_processes_only_find_what_they_see() {
+    let mut kernel = new_kernel(&[(5, 5)], bed_spawn());
+    for _ in 0..40 {
+        kernel.step();
+    }
+    let init_pid = common::init_pid(&kernel, 1);
+    let pid = (0..16).find_map(|_| kernel.fork_process(init_pid)).unwrap();
+    let location = kernel.get_process_location(pid);
+    let expected = nearest_bed(&kernel, location, SIGHT_RADIUS);
+    let ret = call(&mut kernel, pid, "FindChallenge", (0x100, BED, 16));
+    match expected {
+        Some((x, y)) => {
+            assert_eq!(ret, Some(2));
+            assert_eq!(read_memory(&kernel, pid, 0x100, 2), [x, y]);
+        }
+        None => assert_eq!(ret, None),
+    }
+}
+
+#[test]
+fn enemies_are_processes_of_other_users() {
+    let mut kernel = new_kernel(&[(5, 5), (12, 5), (5, 14)], BTreeMap::new());
+    let pid = common::init_pid(&kernel, 1);
+    assert_eq!(call(&mut kernel, pid, "FindEnemy", (0x100, 6, 0)), None);
+    assert_eq!(call(&mut kernel, pid, "FindEnemy", (0x100, 7, 0)), Some(4));
+    let [pid_lo, pid_hi] = common::init_pid(&kernel, 2).to_le_bytes();
+    assert_eq!(read_memory(&kernel, pid, 0x100, 4), [12, 5, pid_lo, pid_hi]);
+
+    // A process of the same user is no enemy, however close.
+    let child_pid = (0..16).find_map(|_| kernel.fork_process(pid)).unwrap();
+    assert_eq!(call(&mut kernel, pid, "FindEnemy", (0x100, 6, 0)), None);
+    // The child cannot see that far.
+    assert_eq!(
+        call(&mut kernel, child_pid, "FindEnemy", (0x100, 16, 0)),
+        None
+    );
+}
+
+#[test]
+fn search_cost_scales_with_radius() {
+    let syscall = SYSCALL_TABLE.get_syscall_by_name("FindEnemy").unwrap();
+    let cost = |radius| {
+        let wallet = syscall.compute_cost((0x100, radius, 0, 0, 0, 0));
+        wallet.get_currency(CryptoCurrency::DogeCoin)
+    };
+    assert_eq!(cost(0), 1);
+    assert_eq!(cost(4), 2);
+    assert_eq!(cost(16), 18);
+    assert_eq!(cost(128), 1033);
+}
+
+// Forks, and the child looks for enemies farther than it can see. It stores
+// the return value plus 1, so 0 means it has not searched yet.
+const SEARCH_BOT: &str = "
+    mov16 ax, 0x02
+    syscall
+    cmp16 ax, 0xffff
+    cmove16 pc, :search
+:idle
+    mov16 pc, :idle
+:search
+    mov16 r0, 0x100
+    mov16 r1, 16
+    mov16 ax, 0x19
+    syscall
+    add8 ax, 1
+    store16 [zr+0x200], ax
+    mov16 pc, :idle
+";
+
+#[test]
+fn searches_only_charge_what_they_search() {
+    let doge = |wallet: Wallet| wallet.get_currency(CryptoCurrency::DogeCoin);
+    let fork_cost = doge(
+        SYSCALL_TABLE
+            .get_syscall_by_name("Fork")
+            .unwrap()
+            .compute_cost((0, 0, 0, 0, 0, 0)),
+    );
+    let search_cost = |radius| {
+        let syscall = SYSCALL_TABLE.get_syscall_by_name("FindEnemy").unwrap();
+        doge(syscall.compute_cost((0x100, radius, 0, 0, 0, 0)))
+    };
+    // The enemy init is in sight of the child, or across the map.
+    for (enemy_spawn, ret, cost) in [((6, 5), 5, search_cost(SIGHT_RADIUS)), ((21, 5), 1, 0)] {
+        let mut kernel = common::new_kernel(
+            common::kernel_config(9),
+            GameMap::new(&vec![0; GEOMETRY.cell_count()], GEOMETRY).unwrap(),
+            Rc::new(NullSink),
+            SEARCH_BOT,
+            &[(5, 5), enemy_spawn],
+        );
+        let before = doge(kernel.get_user(1).wallet.clone());
+        for _ in 0..3 {
+            kernel.step();
+        }
+        let init_pid = common::init_pid(&kernel, 1);
+        let child_pid = kernel.get_process(init_pid).children[0];
+        assert_eq!(read_memory(&kernel, child_pid, 0x200, 2), [ret, 0]);
+        let spent = before - doge(kernel.get_user(1).wallet.clone());
+        assert_eq!(spent, fork_cost + cost);
+    }
+}
+
+#[test]
+fn solved_challenges_leave_the_index() {
+    let mut map = GameMap::new(&vec![0; GEOMETRY.cell_count()], GEOMETRY).unwrap();
+    for _ in 0..20 {
+        map.tick(&bed_spawn());
+    }
+    let location = map.find_nearest_challenge((0, 0), BED, 32).unwrap();
+    map.check_challenge_index();
+    assert_eq!(map.solve_challenge(location, (1, 1, 1, 1)), None);
+    assert!(map.solve_challenge(location, (0, 1, 2, 3)).is_some());
+    assert!(map.get_cell(location).crypto_data().is_none());
+    assert_ne!(map.find_nearest_challenge((0, 0), BED, 32), Some(location));
+    map.check_challenge_index();
+}
+
+// The nearest location for which `found` holds, by looking at every cell of
+// the map.
+fn nearest_cell<F: Fn(Location) -> bool>(
+    map: &GameMap,
+    center: Location,
+    radius: u16,
+    found: F,
+) -> Option<Location> {
+    let geometry = map.geometry();
+    (0..geometry.cell_count())
+        .map(|idx| geometry.cell_location(idx))
+        .filter(|&location| found(location))
+        .map(|location| (geometry.distance(center, location), location))
+        .filter(|&(distance, _)| distance as u16 <= radius)
+        .min()
+        .map(|(_, location)| location)
+}
+
+fn random_location(rng: &mut ChaCha8Rng, geometry: MapGeometry) -> Location {
+    (
+        rng.gen_range(0..geometry.width) as u8,
+        rng.gen_range(0..geometry.height) as u8,
+    )
+}
+
+// The index is searched by buckets, which these maps do not all fill evenly.
+#[test]
+fn nearest_searches_match_a_full_scan() {
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    for (width, height, bounded) in [
+        (256, 256, false),
+        (250, 250, false),
+        (40, 24, true),
+        (20, 37, false),
+    ] {
+        let geometry = MapGeometry::new(width, height, bounded).unwrap();
+        let mut map = GameMap::new(&vec![0; geometry.cell_count()], geometry).unwrap();
+        for _ in 0..50 {
+            map.tick(&bed_spawn());
+        }
+        for pid in 1..=60 {
+            let location = random_location(&mut rng, geometry);
+            if map.get_process_at(location).is_none() {
+                map.add_process_to_map(pid, location);
+            }
+        }
+        for pid in (1..=60).step_by(4) {
+            if map.has_process(pid) {
+                map.remove_process_from_map(pid);
+            }
+        }
+        map.check_process_index();
+        map.check_challenge_index();
+
+        for _ in 0..100 {
+            let center = random_location(&mut rng, geometry);
+            let radius = rng.gen_range(0..300);
+            let is_enemy = |pid: u16| pid % 3 != 0;
+            assert_eq!(
+                map.find_nearest_process(center, radius, is_enemy),
+                nearest_cell(&map, center, radius, |location| {
+                    map.get_process_at(location).is_some_and(is_enemy)
+                }),
+                "process near {:?} within {} on {:?}",
+                center,
+                radius,
+                geometry
+            );
+            assert_eq!(
+                map.find_nearest_challenge(center, BED, radius),
+                nearest_cell(&map, center, radius, |location| {
+                    map.get_cell(location)
+                        .crypto_data()
+                        .is_some_and(|data| data[..2] == BED.to_le_bytes())
+                }),
+                "challenge near {:?} within {} on {:?}",
+                center,
+                radius,
+                geometry
+            );
+        }
+    }
+}
+
+// The last bucket of each axis is only 10 cells wide, so the process is
+// nearer than the 2 buckets between it and the center suggest.
+#[test]
+fn nearest_search_crosses_narrow_buckets() {
+    let geometry = MapGeometry::new(250, 250, false).unwrap();
+    let mut map = GameMap::new(&vec![0; geometry.cell_count()], geometry).unwrap();
+    map.add_process_to_map(1, (239, 5));
+    map.add_process_to_map(2, (100, 100));
+    assert_eq!(
+        map.find_nearest_process((0, 0), 11, |_| true),
+        Some((239, 5))
+    );
+    assert_eq!(map.find_nearest_process((0, 0), 10, |_| true), None);
+    assert_eq!(
+        map.find_nearest_process((5, 0), 16, |_| true),
+        Some((239, 5))
+    );
+}
+
+#[test]
+fn init_reads_details_anywhere() {
+    let mut kernel = new_kernel(&[(5, 5), (20, 20)], BTreeMap::new());
+    let pid = common::init_pid(&kernel, 1);
+    let args = (0x100, 20, 20, 20, 20, 0);
+    let ret = SYSCALL_TABLE
+        .get_syscall_by_name("ReadMapDetail")
+        .unwrap()
+        .call(&mut kernel, pid, args);
+    assert_eq!(ret, Some(3));
+    let [pid_lo, pid_hi] = common::init_pid(&kernel, 2).to_le_bytes();
+    assert_eq!(read_memory(&kernel, pid, 0x100, 3), [2, pid_lo, pid_hi]);
+}
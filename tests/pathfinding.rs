@@ -0,0 +1,137 @@
+use minecrab::game::map::{GameMap, Location, MapCell, MAP_HEIGHT, MAP_WIDTH};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+
+fn map_from_walls(walls: impl IntoIterator<Item = Location>) -> GameMap {
+    let mut map_data = vec![0; MAP_WIDTH * MAP_HEIGHT];
+    for (x, y) in walls {
+        map_data[x as usize * MAP_HEIGHT + y as usize] = 1;
+    }
+    GameMap::from_map_data(&map_data).unwrap()
+}
+
+// The number of moves on a shortest path, found by a plain BFS over the whole
+// map.
+fn bfs_distance(map: &GameMap, start: Location, end: Location) -> Option<u32> {
+    let mut distances = vec![u32::MAX; MAP_WIDTH * MAP_HEIGHT];
+    let index = |(x, y): Location| x as usize * MAP_HEIGHT + y as usize;
+    distances[index(start)] = 0;
+    let mut queue = VecDeque::from([start]);
+    while let Some(cur) = queue.pop_front() {
+        let distance = distances[index(cur)];
+        if cur == end {
+            return Some(distance);
+        }
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = (cur.0.wrapping_add_signed(dx), cur.1.wrapping_add_signed(dy));
+                if map.get_cell(next).is_passable() && distances[index(next)] == u32::MAX {
+                    distances[index(next)] = distance + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn assert_valid_path(map: &GameMap, start: Location, locations: &[Location]) {
+    let mut cur = start;
+    for &next in locations {
+        assert_eq!(map.geometry().distance(cur, next), 1);
+        assert!(map.get_cell(next).is_passable());
+        cur = next;
+    }
+}
+
+#[test]
+fn crosses_most_of_the_map() {
+    let map = map_from_walls([]);
+    let path = map.pathfind((0, 0), (100, 120), 1000).unwrap();
+    assert_eq!(path.cost, 120);
+    assert_eq!(path.locations.len(), 120);
+    assert_eq!(path.locations.last(), Some(&(100, 120)));
+    assert_valid_path(&map, (0, 0), &path.locations);
+    assert_eq!(map.pathfind((0, 0), (100, 120), 119), None);
+}
+
+#[test]
+fn wraps_around_map_borders() {
+    let map = map_from_walls([]);
+    let path = map.pathfind((250, 5), (3, 250), 16).unwrap();
+    assert_eq!(path.cost, 11);
+    assert_valid_path(&map, (250, 5), &path.locations);
+}
+
+#[test]
+fn start_is_destination() {
+    let map = map_from_walls([]);
+    let path = map.pathfind((7, 7), (7, 7), 0).unwrap();
+    assert!(path.locations.is_empty());
+    assert_eq!(path.cost, 0);
+}
+
+#[test]
+fn matches_bfs_on_random_walls() {
+    let mut rng = ChaCha8Rng::seed_from_u64(17);
+    for _ in 0..20 {
+        let walls: Vec<Location> = (0..20000).map(|_| rng.gen()).collect();
+        let map = map_from_walls(walls);
+        for _ in 0..10 {
+            let start: Location = rng.gen();
+            let end = (
+                start.0.wrapping_add(rng.gen_range(0..40)),
+                start.1.wrapping_add(rng.gen_range(0..40)),
+            );
+            if !map.get_cell(end).is_passable() {
+                continue;
+            }
+            let expected = bfs_distance(&map, start, end);
+            let path = map.pathfind(start, end, 200);
+            assert_eq!(
+                path.as_ref().map(|path| path.cost),
+                expected.filter(|&distance| distance <= 200)
+            );
+            if let Some(path) = path {
+                assert_eq!(path.locations.len() as u32, path.cost);
+                assert_valid_path(&map, start, &path.locations);
+            }
+        }
+    }
+}
+
+#[test]
+fn prefers_cheaper_cells() {
+    // Walls are made passable at a cost. The wall runs across the whole map
+    // except for a gap 50 cells away, which is only worth the detour when
+    // walls are expensive.
+    let walls: Vec<Location> = (0..=255).filter(|&x| x != 60).map(|x| (x, 15)).collect();
+    let map = map_from_walls(walls.clone());
+    let wall_cost =
+        |wall_cost| move |cell: &MapCell| Some(if cell.status() == 1 { wall_cost } else { 1 });
+
+    let path = map
+        .find_path((10, 10), (10, 20), 1000, wall_cost(100))
+        .unwrap();
+    assert_eq!(path.cost, 100);
+    assert!(path.locations.contains(&(60, 15)));
+    assert!(path
+        .locations
+        .iter()
+        .all(|location| !walls.contains(location)));
+
+    let path = map
+        .find_path((10, 10), (10, 20), 1000, wall_cost(2))
+        .unwrap();
+    assert_eq!(path.cost, 11);
+    assert_eq!(path.locations.len(), 10);
+    assert_eq!(
+        path.locations
+            .iter()
+            .filter(|location| walls.contains(location))
+            .count(),
+        1
+    );
+}
@@ -0,0 +1,116 @@
+use minecrab::asm;
+use minecrab::profile::Profiler;
+use minecrab::vm::emulator::{Emulator, ExecutionObserver};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// Runs the loop body three times, then calls GetPid.
+const PROGRAM: &str = "
+        mov16 r0, 3
+:loop
+        sub16 r0, 1
+        cmp16 r0, 0
+        cmovne16 pc, :loop
+        mov16 ax, 0x00
+        syscall
+";
+
+// Profiles PROGRAM as pid 7 of user 3.
+fn profile() -> Profiler {
+    let program = asm::assemble(PROGRAM).unwrap();
+    let mut emulator = Emulator::new(program.memory, program.bytecode, 0);
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    let mut observers: Vec<Box<dyn ExecutionObserver>> = vec![Box::new(profiler.clone())];
+    observers[0].process_created(7, 3);
+    let mut cycle_count = 1000;
+    assert!(emulator
+        .run_until_interrupt_observed(7, &mut cycle_count, &mut observers)
+        .is_some());
+    drop(observers);
+    Rc::try_unwrap(profiler).ok().unwrap().into_inner()
+}
+
+#[test]
+fn counts_every_instruction_by_opcode_address_and_syscall() {
+    let profiler = profile();
+    let processes = profiler.get_process_profiles();
+    assert_eq!(processes.keys().collect::<Vec<_>>(), [&(3, 7)]);
+    let profile = &processes[&(3, 7)];
+    assert_eq!((profile.total.count, profile.total.cycles), (12, 159));
+
+    let opcodes: Vec<_> = profile
+        .opcodes
+        .iter()
+        .map(|(opcode, counter)| (*opcode, counter.count, counter.cycles))
+        .collect();
+    assert_eq!(
+        opcodes,
+        [
+            (0x0f, 1, 100),
+            (0x20, 2, 8),
+            (0x56, 3, 12),
+            (0xb6, 3, 12),
+            (0xeb, 3, 27)
+        ]
+    );
+    let addresses: Vec<_> = profile
+        .addresses
+        .iter()
+        .map(|(pc, (opcode, counter))| (*pc, *opcode, counter.count, counter.cycles))
+        .collect();
+    assert_eq!(
+        addresses,
+        [
+            (0x00, 0x20, 1, 4),
+            (0x04, 0xb6, 3, 12),
+            (0x08, 0x56, 3, 12),
+            (0x0c, 0xeb, 3, 27),
+            (0x10, 0x20, 1, 4),
+            (0x14, 0x0f, 1, 100)
+        ]
+    );
+    let syscalls: Vec<_> = profile
+        .syscalls
+        .iter()
+        .map(|(number, counter)| (*number, counter.count, counter.cycles))
+        .collect();
+    assert_eq!(syscalls, [(0x00, 1, 100)]);
+
+    let users = profiler.get_user_profiles();
+    assert_eq!(users.keys().collect::<Vec<_>>(), [&3]);
+    assert_eq!(users[&3].total.cycles, 159);
+}
+
+#[test]
+fn report_lists_the_hottest_entries_first() {
+    let mut report = Vec::new();
+    profile().write_report(&mut report, 3).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    let expected = "\
+== User 3 ==
+12 instructions, 159 cycles
+
+Processes:
+   pid        count       cycles
+     7           12          159
+
+Hot addresses:
+    pc mnemonic          count       cycles       %
+0x0014 syscall               1          100  62.89%
+0x000c cmovne16              3           27  16.98%
+0x0004 sub16                 3           12   7.55%
+
+Expensive opcodes:
+opcode mnemonic   latency        count       cycles       %
+  0x0f syscall        100            1          100  62.89%
+  0xeb cmovne16         9            3           27  16.98%
+  0x56 cmp16            4            3           12   7.55%
+
+Syscalls:
+number name                            count       cycles
+  0x00 GetPid                              1          100
+
+";
+    assert_eq!(report, expected);
+}
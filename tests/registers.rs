@@ -0,0 +1,101 @@
+mod common;
+
+use minecrab::asm;
+use minecrab::game::map::{GameMap, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::replay::NullSink;
+use minecrab::vm::emulator::Emulator;
+use minecrab::vm::register::RegisterName;
+
+use std::rc::Rc;
+
+fn new_emulator(source: &str, seed: u64) -> Emulator {
+    let program = asm::assemble(source).unwrap();
+    Emulator::new(program.memory, program.bytecode, seed)
+}
+
+fn read_rr(emulator: &mut Emulator) -> Vec<u16> {
+    (0..8)
+        .map(|_| emulator.get_reg_mut(RegisterName::RR))
+        .collect()
+}
+
+// The random register sequence of a child forked from the init process of a
+// game with this seed.
+fn forked_rr(seed: u64) -> (Vec<u16>, Vec<u16>) {
+    let mut kernel = common::new_kernel(
+        common::kernel_config(seed),
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        Rc::new(NullSink),
+        common::IDLE_BOT,
+        &[(10, 10)],
+    );
+    let parent = common::init_pid(&kernel, 1);
+    let child = kernel.fork_process(parent).unwrap();
+    let parent_rr = read_rr(&mut kernel.get_process_mut(parent).emulator);
+    let child_rr = read_rr(&mut kernel.get_process_mut(child).emulator);
+    (parent_rr, child_rr)
+}
+
+#[test]
+fn special_registers_keep_their_semantics() {
+    let mut emulator = new_emulator(
+        "
+        mov16 ct, 5
+        mov16 r0, ct
+        mov16 r1, ct
+        mov16 re, 0x0001
+        mov16 r2, re
+        mov16 tf, 1
+        mov16 r3, tf
+        mov16 zr, 1
+        mov16 r4, zr
+        mov16 rr, 1
+        mov16 ax, 0x00
+        syscall
+        ",
+        0,
+    );
+    let mut cycle_count = 1000;
+    assert!(emulator.run_until_interrupt(&mut cycle_count).is_some());
+
+    // Reading CT returns its value, then increments it.
+    assert_eq!(emulator.peek_reg(RegisterName::R0), 5);
+    assert_eq!(emulator.peek_reg(RegisterName::R1), 6);
+    assert_eq!(emulator.peek_reg(RegisterName::CT), 7);
+    // RE stores what is written and reverses it on reads.
+    assert_eq!(emulator.peek_reg(RegisterName::R2), 0x8000);
+    assert_eq!(emulator.peek_reg(RegisterName::RE), 0x0001);
+    // TF, ZR and RR ignore writes.
+    assert_eq!(emulator.peek_reg(RegisterName::R3), 0x1337);
+    assert_eq!(emulator.peek_reg(RegisterName::TF), 0x1337);
+    assert_eq!(emulator.peek_reg(RegisterName::R4), 0);
+    assert_eq!(emulator.peek_reg(RegisterName::ZR), 0);
+    assert_eq!(emulator.peek_reg(RegisterName::RR), 0);
+
+    assert_eq!(emulator.get_reg_mut(RegisterName::CT), 7);
+    assert_eq!(emulator.get_reg_mut(RegisterName::CT), 8);
+    assert_eq!(emulator.get_reg_mut(RegisterName::RE), 0x8000);
+    assert_eq!(emulator.get_reg_mut(RegisterName::TF), 0x1337);
+    assert_eq!(emulator.get_reg_mut(RegisterName::ZR), 0);
+}
+
+#[test]
+fn random_register_follows_the_seed() {
+    let mut emulator = new_emulator("", 1);
+    let sequence = read_rr(&mut emulator);
+    assert!(sequence.windows(2).any(|pair| pair[0] != pair[1]));
+    assert_eq!(read_rr(&mut new_emulator("", 1)), sequence);
+    assert_ne!(read_rr(&mut new_emulator("", 2)), sequence);
+
+    // A clone continues from the same generator state.
+    let mut clone = emulator.clone();
+    assert_eq!(read_rr(&mut clone), read_rr(&mut emulator));
+}
+
+#[test]
+fn forked_children_draw_the_same_random_numbers_for_a_seed() {
+    let (parent_rr, child_rr) = forked_rr(3);
+    assert_eq!(forked_rr(3), (parent_rr.clone(), child_rr.clone()));
+    assert_ne!(child_rr, parent_rr);
+    assert_ne!(forked_rr(4).1, child_rr);
+}
@@ -0,0 +1,115 @@
+use minecrab::asm;
+use minecrab::runner::{BotRunner, MockScript, MockSyscalls, SyscallHandler};
+use minecrab::vm::emulator::Emulator;
+
+use std::fs;
+
+const ARGS: (u16, u16, u16, u16, u16, u16) = (0, 0, 0, 0, 0, 0);
+
+// Adds the result of GetPid to R1, then teleports to (5, R1). Each loop takes
+// exactly 220 cycles.
+const BOT: &str = "
+:loop
+        mov16 ax, 0x00
+        syscall
+        add16 r1, ax
+        mov16 ax, 0x41
+        mov16 r0, 5
+        syscall
+        mov16 pc, :loop
+";
+
+fn from_json(json: &str) -> Result<MockSyscalls, String> {
+    let script: MockScript = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    MockSyscalls::from_script(script).map_err(|err| err.message)
+}
+
+fn returns(mock: &mut MockSyscalls, number: u8, count: usize) -> Vec<u16> {
+    (0..count).map(|_| mock.handle(0, number, ARGS)).collect()
+}
+
+#[test]
+fn scripts_key_syscalls_by_name_or_number() {
+    let mut mock = from_json(
+        r#"{
+            "default": 9,
+            "syscalls": {
+                "GetPid": {"returns": [10, 20], "default": 1},
+                "0x41": {"returns": [30]},
+                "24": {"default": 40}
+            }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(returns(&mut mock, 0x00, 4), [10, 20, 1, 1]);
+    assert_eq!(returns(&mut mock, 0x41, 3), [30, 9, 9]);
+    assert_eq!(returns(&mut mock, 0x18, 2), [40, 40]);
+    assert_eq!(returns(&mut mock, 0x19, 1), [9]);
+}
+
+#[test]
+fn unscripted_syscalls_return_the_default() {
+    assert_eq!(returns(&mut MockSyscalls::new(), 0x00, 2), [0, 0]);
+    assert_eq!(returns(&mut from_json("{}").unwrap(), 0x41, 1), [0]);
+    let mut mock = MockSyscalls::new().with_default(3);
+    mock.push_return(0x00, 4);
+    assert_eq!(returns(&mut mock, 0x00, 2), [4, 3]);
+}
+
+#[test]
+fn malformed_scripts_are_rejected() {
+    let err = from_json(r#"{"syscalls": {"NoSuchSyscall": {"default": 1}}}"#);
+    assert_eq!(err.err().unwrap(), "Unknown syscall \"NoSuchSyscall\"");
+    assert!(from_json(r#"{"syscalls": {"256": {"default": 1}}}"#).is_err());
+    assert!(from_json(r#"{"syscalls": {"GetPid": {"returns": 1}}}"#).is_err());
+    assert!(from_json(r#"{"default": -1}"#).is_err());
+
+    let path = std::env::temp_dir().join(format!("minecrab-script-{}.json", std::process::id()));
+    fs::write(&path, "{\"syscalls\": ").unwrap();
+    let result = MockSyscalls::load(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    assert!(result.is_err());
+}
+
+#[test]
+fn report_records_every_call_of_every_tick() {
+    let mock =
+        from_json(r#"{"default": 7, "syscalls": {"GetPid": {"returns": [10, 20], "default": 1}}}"#)
+            .unwrap();
+    let program = asm::assemble(BOT).unwrap();
+    let emulator = Emulator::new(program.memory, program.bytecode, 0);
+    let mut runner = BotRunner::new(emulator, mock).with_cycles_per_tick(220);
+    runner.run(3);
+
+    let report = runner.report();
+    assert_eq!(report.ticks, 3);
+    let calls: Vec<_> = report
+        .calls
+        .iter()
+        .map(|call| {
+            (
+                call.tick,
+                call.name,
+                call.args.0,
+                call.args.1,
+                call.return_value,
+            )
+        })
+        .collect();
+    assert_eq!(
+        calls,
+        [
+            (0, "GetPid", 0, 0, 10),
+            (0, "Teleport", 5, 10, 7),
+            (1, "GetPid", 5, 10, 20),
+            (1, "Teleport", 5, 30, 7),
+            (2, "GetPid", 5, 30, 1),
+            (2, "Teleport", 5, 31, 7),
+        ]
+    );
+    assert_eq!(report.registers["R1"], 31);
+    assert_eq!(report.registers["AX"], 7);
+    assert_eq!(report.registers["PC"], 0);
+    assert_eq!(report.registers["TS"], 3);
+    assert_eq!(report.memory.len(), 0x10000);
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use minecrab::config::KernelConfiguration;
+use minecrab::game::map::{GameMap, Location, MapGeometry};
+use minecrab::game::replay::{GameEvent, MemorySink, OwnedGameEvent, RankingEntry};
+use minecrab::kernel::Kernel;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::rc::Rc;
+
+// Steps onto a dog challenge next to it and solves it, then forks and waits
+// for about a tick.
+const DOG_BOT: &str = "
+:loop
+    mov16 r0, 0x100
+    mov16 r1, 0x420
+    mov16 r2, 1
+    mov16 ax, 0x18
+    syscall
+    cmp16 ax, 0
+    cmove16 pc, :move
+    mov16 r5, 0x100
+    load8 r0, [r5]
+    load8 r1, [r5+1]
+    mov16 ax, 0x10
+    syscall
+    mov16 r0, 0x100
+    mov16 r1, 16
+    mov16 ax, 0x13
+    syscall
+    cmp16 ax, 0
+    cmove16 pc, :move
+    load16 r0, [r5+6]
+    load16 r1, [r5+8]
+    load16 r2, [r5+10]
+    load16 r3, [r5+12]
+    mov16 ax, 0x14
+    syscall
+:move
+    mov16 ax, 0x02
+    syscall
+    mov16 r1, 80
+:wait
+    sub8 r1, 1
+    cmovne16 pc, :wait
+    mov16 pc, :loop
+";
+
+const GEOMETRY: MapGeometry = MapGeometry {
+    width: 16,
+    height: 16,
+    bounded: false,
+};
+
+const SNAPSHOT_TICK: u64 = 30;
+
+fn new_game(sink: Rc<MemorySink>) -> Kernel {
+    let crypto_spawn = BTreeMap::from([
+        ("dog".to_string(), vec![(2, 1.0); 4]),
+        ("bed".to_string(), vec![(2, 0.5)]),
+        ("ether".to_string(), vec![(2, 0.5)]),
+        ("btc".to_string(), vec![(2, 0.5)]),
+        ("crab".to_string(), vec![(2, 0.5)]),
+    ]);
+    let config = KernelConfiguration {
+        crypto_spawn,
+        max_processes: 8,
+        max_ticks: Some(80),
+        ..common::kernel_config(13)
+    };
+    common::new_kernel(
+        config,
+        GameMap::new(&vec![0; GEOMETRY.cell_count()], GEOMETRY).unwrap(),
+        sink,
+        DOG_BOT,
+        &[(2, 2), (10, 10)],
+    )
+}
+
+fn challenges(kernel: &Kernel) -> BTreeMap<Location, Vec<u8>> {
+    (0..16u8)
+        .flat_map(|x| (0..16u8).map(move |y| (x, y)))
+        .filter_map(|location| Some((location, kernel.get_map_cell(location).crypto_data()?)))
+        .collect()
+}
+
+fn solved_locations(events: &[OwnedGameEvent]) -> Vec<Location> {
+    events
+        .iter()
+        .filter_map(|event| match *event {
+            GameEvent::ChallengeSolved { location, .. } => Some(location),
+            _ => None,
+        })
+        .collect()
+}
+
+fn play_uninterrupted() -> (Vec<OwnedGameEvent>, Vec<RankingEntry>) {
+    let sink = Rc::new(MemorySink::new());
+    let ranking = new_game(sink.clone()).run_full_game();
+    (sink.take_events(), ranking)
+}
+
+#[test]
+fn resumed_games_play_on_like_uninterrupted_ones() {
+    let (expected_events, expected_ranking) = play_uninterrupted();
+
+    let sink = Rc::new(MemorySink::new());
+    let mut kernel = new_game(sink.clone());
+    for _ in 0..SNAPSHOT_TICK {
+        kernel.step();
+    }
+    let path = std::env::temp_dir().join(format!("minecrab-snapshot-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    kernel.save_snapshot(path).unwrap();
+    let resumed_sink = Rc::new(MemorySink::new());
+    let mut resumed = Kernel::load_snapshot(path, resumed_sink.clone()).unwrap();
+    fs::remove_file(path).unwrap();
+
+    // Every kind of challenge is on the map and comes back as it was.
+    let saved_challenges = challenges(&kernel);
+    let kinds: BTreeSet<_> = saved_challenges
+        .values()
+        .map(|data| data[..2].to_vec())
+        .collect();
+    assert_eq!(kinds.len(), 5);
+    assert_eq!(challenges(&resumed), saved_challenges);
+    resumed.check_invariants();
+
+    let ranking = resumed.run_full_game();
+    let events_before = sink.take_events();
+    let events_after = resumed_sink.take_events();
+    assert_eq!(
+        events_after[0],
+        GameEvent::TickStart {
+            tick: SNAPSHOT_TICK
+        }
+    );
+    // Dog challenges placed before the snapshot keep their answers after it.
+    assert!(!solved_locations(&events_before).is_empty());
+    assert!(solved_locations(&events_after)
+        .iter()
+        .any(|location| saved_challenges.contains_key(location)));
+    assert_eq!([events_before, events_after].concat(), expected_events);
+    assert_eq!(ranking, expected_ranking);
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use minecrab::config::KernelConfiguration;
+use minecrab::game::map::{GameMap, Location, MapGeometry, MAP_HEIGHT, MAP_WIDTH};
+use minecrab::game::replay::NullSink;
+use minecrab::kernel::Kernel;
+
+use std::collections::HashSet;
+use std::rc::Rc;
+
+// Forks, then teleports next to itself, forever.
+const FORK_BOT: &str = "
+:loop
+    mov16 ax, 0x02
+    syscall
+    mov16 ax, 0x00
+    syscall
+    mov16 r0, ax
+    mov16 ax, 0x41
+    syscall
+    mov16 pc, :loop
+";
+
+fn new_kernel(spawn_points: &[Location]) -> Kernel {
+    let config = KernelConfiguration {
+        initd_lifetime: 100,
+        ..common::kernel_config(1)
+    };
+    common::new_kernel(
+        config,
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        Rc::new(NullSink),
+        FORK_BOT,
+        spawn_points,
+    )
+}
+
+fn distance(location1: Location, location2: Location) -> u8 {
+    MapGeometry::default().distance(location1, location2)
+}
+
+// Checks both indices of every process in `pids`, and that the map holds no
+// other processes.
+fn assert_consistent(kernel: &Kernel, pids: &[u16]) {
+    kernel.check_invariants();
+    let map = kernel.get_game_map();
+    assert_eq!(map.count_processes(), pids.len());
+    let mut locations = HashSet::new();
+    for &pid in pids {
+        let location = kernel.get_process_location(pid);
+        assert_eq!(kernel.get_map_cell(location).get_process(), Some(pid));
+        assert_eq!(map.get_process_at(location), Some(pid));
+        assert!(!kernel.get_map_cell(location).is_empty());
+        assert!(
+            locations.insert(location),
+            "two processes at {:?}",
+            location
+        );
+    }
+}
+
+#[test]
+fn spawned_init_is_on_its_cell() {
+    let kernel = new_kernel(&[(10, 10), (200, 30)]);
+    let init1 = common::init_pid(&kernel, 1);
+    let init2 = common::init_pid(&kernel, 2);
+    assert_eq!(kernel.get_map_cell((10, 10)).get_process(), Some(init1));
+    assert_eq!(kernel.get_map_cell((200, 30)).get_process(), Some(init2));
+    assert_consistent(&kernel, &[init1, init2]);
+}
+
+#[test]
+fn shared_spawn_point_is_rejected() {
+    let mut kernel = Kernel::new(
+        common::kernel_config(1),
+        GameMap::from_map_data(&vec![0; MAP_WIDTH * MAP_HEIGHT]).unwrap(),
+        Rc::new(NullSink),
+    );
+    let user_configs = common::user_configs(FORK_BOT, &[(10, 10), (12, 12), (10, 10)]);
+    let err = kernel.setup_users(user_configs).unwrap_err();
+    assert_eq!(
+        err.message,
+        "Spawn point (10, 10) of user 3 is already taken"
+    );
+    assert_eq!(kernel.get_game_map().count_processes(), 0);
+
+    // Inits from an earlier call take their cells too.
+    let mut kernel = new_kernel(&[(10, 10)]);
+    let user_configs = common::user_configs(FORK_BOT, &[(10, 10)]);
+    assert!(kernel.setup_users(user_configs).is_err());
+    assert_consistent(&kernel, &[common::init_pid(&kernel, 1)]);
+}
+
+#[test]
+fn forked_child_is_on_its_cell() {
+    let mut kernel = new_kernel(&[(10, 10)]);
+    let init = common::init_pid(&kernel, 1);
+    let child = kernel.fork_process(init).unwrap();
+    let location = kernel.get_process_location(child);
+    assert_ne!(location, (10, 10));
+    assert_eq!(kernel.get_map_cell(location).get_process(), Some(child));
+    assert_consistent(&kernel, &[init, child]);
+}
+
+#[test]
+fn forks_never_share_a_cell() {
+    let mut kernel = new_kernel(&[(10, 10)]);
+    let mut pids = vec![common::init_pid(&kernel, 1)];
+    while pids.len() < 16 {
+        let parent = pids[pids.len() / 2];
+        if let Some(child) = kernel.fork_process(parent) {
+            pids.push(child);
+        }
+        assert_consistent(&kernel, &pids);
+    }
+}
+
+#[test]
+fn move_updates_both_indices() {
+    let mut kernel = new_kernel(&[(10, 10)]);
+    let init = common::init_pid(&kernel, 1);
+    let child = kernel.fork_process(init).unwrap();
+    let (x, y) = kernel.get_process_location(child);
+    let destination = (x.wrapping_add(1), y);
+    let destination = if destination == (10, 10) {
+        (x.wrapping_sub(1), y)
+    } else {
+        destination
+    };
+    assert!(kernel.move_process_to(child, destination));
+    assert_eq!(kernel.get_process_location(child), destination);
+    assert!(kernel.get_map_cell((x, y)).is_empty());
+    assert_consistent(&kernel, &[init, child]);
+
+    // The init blocks its cell for moves and paths alike.
+    assert_eq!(kernel.pathfind_process_to(child, (10, 10), 16), None);
+}
+
+#[test]
+fn teleport_updates_both_indices() {
+    let mut kernel = new_kernel(&[(10, 10), (100, 100)]);
+    let init1 = common::init_pid(&kernel, 1);
+    let init2 = common::init_pid(&kernel, 2);
+    let child = kernel.fork_process(init1).unwrap();
+    let old_location = kernel.get_process_location(child);
+    while !kernel.teleport_process_to(child, (100, 100)) {}
+    let location = kernel.get_process_location(child);
+    assert!(distance(location, (100, 100)) <= 2);
+    assert_ne!(location, (100, 100));
+    assert!(kernel.get_map_cell(old_location).is_empty());
+    assert_consistent(&kernel, &[init1, init2, child]);
+}
+
+#[test]
+fn killed_processes_leave_both_indices() {
+    let mut kernel = new_kernel(&[(10, 10), (100, 100)]);
+    let init1 = common::init_pid(&kernel, 1);
+    let init2 = common::init_pid(&kernel, 2);
+    let child = kernel.fork_process(init1).unwrap();
+    let grandchild = kernel.fork_process(child).unwrap();
+    let locations = [
+        kernel.get_process_location(child),
+        kernel.get_process_location(grandchild),
+    ];
+    kernel.kill_process_recursive(child);
+    for location in locations {
+        assert!(kernel.get_map_cell(location).is_empty());
+    }
+    assert!(!kernel.get_game_map().has_process(grandchild));
+    assert_consistent(&kernel, &[init1, init2]);
+
+    kernel.kill_process_recursive(init2);
+    assert!(kernel.get_map_cell((100, 100)).is_empty());
+    assert_consistent(&kernel, &[init1]);
+}
+
+#[test]
+fn ticks_keep_both_indices_consistent() {
+    // Every tick checks the invariants in debug builds. Processes fork,
+    // teleport around each other and die of old age along the way.
+    let mut kernel = new_kernel(&[(10, 10), (12, 12), (11, 11)]);
+    let mut most_processes = 0;
+    for _ in 0..150 {
+        kernel.step();
+        kernel.check_invariants();
+        most_processes = most_processes.max(kernel.get_game_map().count_processes());
+    }
+    assert!(most_processes > 3);
+    assert_eq!(kernel.get_game_map().count_processes(), 0);
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use minecrab::game::crypto::{CryptoCurrency, Wallet};
+use minecrab::game::map::{
+    GameMap, Location, MapGeometry, DESTRUCTIBLE_WALL, PORTAL, PORTAL_PAIR_BASE, WALL, WATER,
+};
+use minecrab::game::mapfile::{MapFile, MapFormat};
+use minecrab::game::replay::NullSink;
+use minecrab::kernel::syscall::SYSCALL_TABLE;
+use minecrab::kernel::Kernel;
+
+use std::rc::Rc;
+
+const GEOMETRY: MapGeometry = MapGeometry {
+    width: 8,
+    height: 8,
+    bounded: false,
+};
+
+const PORTAL_A: Location = (3, 0);
+const PORTAL_B: Location = (6, 6);
+
+fn new_map_data(cells: &[(Location, u8)]) -> Vec<u8> {
+    let mut map_data = vec![0; GEOMETRY.cell_count()];
+    for &(location, cell) in cells {
+        map_data[GEOMETRY.cell_index(location)] = cell;
+    }
+    map_data
+}
+
+// Water, a destructible wall and the portals of pair 3.
+fn sample_map_data() -> Vec<u8> {
+    new_map_data(&[
+        ((1, 0), WATER),
+        ((2, 0), DESTRUCTIBLE_WALL),
+        (PORTAL_A, PORTAL_PAIR_BASE + 3),
+        (PORTAL_B, PORTAL_PAIR_BASE + 3),
+        ((7, 7), WALL),
+    ])
+}
+
+fn new_kernel(map_data: &[u8]) -> Kernel {
+    common::new_kernel(
+        common::kernel_config(3),
+        GameMap::new(map_data, GEOMETRY).unwrap(),
+        Rc::new(NullSink),
+        common::IDLE_BOT,
+        &[(1, 1)],
+    )
+}
+
+fn call(kernel: &mut Kernel, pid: u16, name: &str, x: u16, y: u16) -> Option<u16> {
+    SYSCALL_TABLE
+        .get_syscall_by_name(name)
+        .unwrap()
+        .call(kernel, pid, (x, y, 0, 0, 0, 0))
+}
+
+fn doge_coins(kernel: &Kernel) -> i64 {
+    kernel
+        .get_user(1)
+        .wallet
+        .get_currency(CryptoCurrency::DogeCoin)
+}
+
+#[test]
+fn terrain_has_its_own_status_codes() {
+    let map_data = sample_map_data();
+    let map = GameMap::new(&map_data, GEOMETRY).unwrap();
+    assert_eq!(map.get_cell((1, 0)).status(), WATER);
+    assert_eq!(map.get_cell((1, 0)).status_detail(), [WATER, 0, 0]);
+    assert_eq!(map.get_cell((2, 0)).status(), DESTRUCTIBLE_WALL);
+    assert_eq!(
+        map.get_cell((2, 0)).status_detail(),
+        [DESTRUCTIBLE_WALL, 8, 0]
+    );
+    assert_eq!(map.get_cell(PORTAL_A).status(), PORTAL);
+    assert_eq!(map.get_cell(PORTAL_A).status_detail(), [PORTAL, 6, 6]);
+    assert_eq!(map.get_cell(PORTAL_B).status_detail(), [PORTAL, 3, 0]);
+    assert_eq!(map.to_map_data(), map_data);
+}
+
+#[test]
+fn portals_come_in_pairs() {
+    let map_data = new_map_data(&[(PORTAL_A, PORTAL_PAIR_BASE)]);
+    assert_eq!(
+        GameMap::new(&map_data, GEOMETRY).err().unwrap().message,
+        "Portal pair 0 must have 2 portals, not 1 at [(3, 0)]"
+    );
+    for cell in [2, 3, PORTAL, PORTAL_PAIR_BASE + 52] {
+        let map_data = new_map_data(&[(PORTAL_A, cell)]);
+        assert_eq!(
+            GameMap::new(&map_data, GEOMETRY).err().unwrap().message,
+            format!("Invalid cell value {} at (3, 0)", cell)
+        );
+    }
+}
+
+#[test]
+fn stepping_on_a_portal_comes_out_of_the_other() {
+    let mut map = GameMap::new(&sample_map_data(), GEOMETRY).unwrap();
+    map.add_process_to_map(1, (4, 1));
+    assert!(map.move_process_to(1, PORTAL_A));
+    assert_eq!(map.get_process_location(1), PORTAL_B);
+    assert!(map.move_process_to(1, (5, 5)));
+    assert!(map.move_process_to(1, PORTAL_B));
+    assert_eq!(map.get_process_location(1), PORTAL_A);
+
+    // The way out is blocked, so the process stays on the portal.
+    map.add_process_to_map(2, (5, 5));
+    assert!(map.move_process_to(2, PORTAL_B));
+    assert_eq!(map.get_process_location(2), PORTAL_B);
+    map.check_process_index();
+}
+
+#[test]
+fn paths_pay_for_water_and_avoid_portals() {
+    let water: Vec<_> = (0..8).map(|y| ((1, y), WATER)).collect();
+    let map = GameMap::new(&new_map_data(&water), GEOMETRY).unwrap();
+    let path = map.pathfind((0, 0), (2, 0), 100).unwrap();
+    assert_eq!(path.cost, 4);
+    assert_eq!(map.pathfind((0, 0), (2, 0), 3), None);
+
+    let map = GameMap::new(&sample_map_data(), GEOMETRY).unwrap();
+    assert_eq!(map.pathfind((4, 1), PORTAL_A, 100), None);
+    let path = map.pathfind((2, 1), (4, 0), 100).unwrap();
+    assert!(!path.locations.contains(&PORTAL_A));
+}
+
+#[test]
+fn moving_into_water_costs_extra() {
+    // Land and water alternate like on a checkerboard, so every land cell has
+    // water next to it.
+    let water: Vec<_> = (0..8u8)
+        .flat_map(|x| (0..8u8).map(move |y| (x, y)))
+        .filter(|&(x, y)| (x + y) % 2 == 1)
+        .map(|location| (location, WATER))
+        .collect();
+    let mut kernel = new_kernel(&new_map_data(&water));
+    let init_pid = common::init_pid(&kernel, 1);
+    let pid = (0..16).find_map(|_| kernel.fork_process(init_pid)).unwrap();
+    let (x, y) = kernel.get_process_location(pid);
+    let target = ((x + 1) % 8, y);
+    assert_eq!(kernel.get_map_cell(target).status(), WATER);
+
+    kernel.get_user_mut(1).wallet = Wallet::default().add_currency(CryptoCurrency::DogeCoin, 2);
+    assert_eq!(
+        call(&mut kernel, pid, "Move", target.0 as u16, y as u16),
+        None
+    );
+    assert_eq!(kernel.get_process_location(pid), (x, y));
+
+    kernel.get_user_mut(1).wallet = Wallet::default().add_currency(CryptoCurrency::DogeCoin, 3);
+    assert_eq!(
+        call(&mut kernel, pid, "Move", target.0 as u16, y as u16),
+        Some(1)
+    );
+    assert_eq!(kernel.get_process_location(pid), target);
+    // The move itself is charged by the kernel, after the syscall.
+    assert_eq!(doge_coins(&kernel), 1);
+}
+
+#[test]
+fn attack1_breaks_down_destructible_walls() {
+    let mut kernel = new_kernel(&sample_map_data());
+    let pid = common::init_pid(&kernel, 1);
+    for hp in (1..8).rev() {
+        assert_eq!(call(&mut kernel, pid, "Attack1", 2, 0), Some(1));
+        assert_eq!(
+            kernel.get_map_cell((2, 0)).status_detail(),
+            [DESTRUCTIBLE_WALL, hp, 0]
+        );
+    }
+    assert_eq!(call(&mut kernel, pid, "Attack1", 2, 0), Some(1));
+    assert_eq!(kernel.get_map_cell((2, 0)).status_detail(), [0, 0, 0]);
+    assert!(kernel.get_map_cell((2, 0)).is_passable());
+    assert_eq!(call(&mut kernel, pid, "Attack1", 2, 0), None);
+    assert_eq!(call(&mut kernel, pid, "Attack1", 7, 7), None);
+}
+
+#[test]
+fn map_files_store_terrain() {
+    let map_file = MapFile::parse(&sample_map_data(), MapFormat::Raw, Some((8, 8))).unwrap();
+    let ascii = map_file.to_bytes(MapFormat::Ascii).unwrap();
+    assert_eq!(&ascii[..9], b".~%D....\n");
+    let parsed = MapFile::parse(&ascii, MapFormat::Ascii, None).unwrap();
+    assert_eq!(parsed.map_data, map_file.map_data);
+
+    let err = map_file.to_bytes(MapFormat::Pgm).err().unwrap();
+    assert_eq!(
+        err.message,
+        "Portal at (3, 0) cannot be stored in a PGM image"
+    );
+
+    let parsed = MapFile::parse(b"P2\n2 1\n3\n1 2\n", MapFormat::Pgm, None).unwrap();
+    assert_eq!(parsed.map_data, [DESTRUCTIBLE_WALL, WATER]);
+    let pgm = parsed.to_bytes(MapFormat::Pgm).unwrap();
+    assert_eq!(&pgm[pgm.len() - 2..], [85, 170]);
+    assert!(MapFile::parse(b"P2\n2 1\n1\n1 0\n", MapFormat::Pgm, None).is_ok());
+    assert!(MapFile::parse(b"P2\n2 1\n4\n1 0\n", MapFormat::Pgm, None).is_err());
+}
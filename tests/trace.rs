@@ -0,0 +1,93 @@
+use minecrab::asm;
+use minecrab::trace::{Tracer, DEFAULT_TRACE_LIMIT};
+use minecrab::vm::emulator::{Emulator, ExecutionObserver};
+
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+
+const PROGRAM: &str = "
+        mov16 r0, 1
+        add16 r0, r0
+        mov16 ax, 0x00
+        syscall
+";
+const MARKER: &str = "{\"truncated\":true}\n";
+
+// Runs PROGRAM as pids 1 and 2 until their syscalls and returns the trace.
+fn trace(pids: &[u16], limit: u64) -> String {
+    let path = std::env::temp_dir().join(format!(
+        "minecrab-trace-{}-{:?}-{}.jsonl",
+        std::process::id(),
+        pids,
+        limit
+    ));
+    let tracer = Tracer::create(&path, pids.iter().copied().collect::<BTreeSet<_>>(), limit);
+    let mut observers: Vec<Box<dyn ExecutionObserver>> = vec![Box::new(tracer.unwrap())];
+    for pid in [1, 2] {
+        let program = asm::assemble(PROGRAM).unwrap();
+        let mut emulator = Emulator::new(program.memory, program.bytecode, 0);
+        let mut cycle_count = 1000;
+        emulator.run_until_interrupt_observed(pid, &mut cycle_count, &mut observers);
+    }
+    drop(observers);
+    let trace = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    trace
+}
+
+fn parse(trace: &str) -> Vec<Value> {
+    trace
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn every_instruction_is_one_json_line() {
+    let records = parse(&trace(&[], DEFAULT_TRACE_LIMIT));
+    assert_eq!(records.len(), 8);
+
+    let first = &records[0];
+    assert_eq!(first["pid"], 1);
+    assert_eq!(first["pc"], 0);
+    assert_eq!(first["opcode"], 0x20);
+    assert_eq!(first["instruction"], "mov16 r0, 0x0001");
+    assert_eq!(first["operands"], serde_json::json!(["r0", "0x0001"]));
+    assert_eq!(first["registers_before"][3], 0);
+    assert_eq!(first["registers_after"][0], 4);
+    assert_eq!(first["registers_after"][3], 1);
+    assert_eq!(first["cycles_left"], 996);
+    assert!(first.get("syscall").is_none());
+    assert!(first.get("nasal_demons").is_none());
+
+    assert_eq!(records[1]["registers_after"][3], 2);
+    assert_eq!(records[3]["instruction"], "syscall");
+    assert_eq!(records[3]["syscall"], "GetPid");
+    assert_eq!(records[4]["pid"], 2);
+}
+
+#[test]
+fn only_selected_pids_are_traced() {
+    let records = parse(&trace(&[2], DEFAULT_TRACE_LIMIT));
+    assert_eq!(records.len(), 4);
+    assert!(records.iter().all(|record| record["pid"] == 2));
+}
+
+#[test]
+fn truncation_stays_within_the_limit() {
+    let full = trace(&[], DEFAULT_TRACE_LIMIT);
+    let lines: Vec<_> = full.split_inclusive('\n').collect();
+    let kept: String = lines[..3].concat();
+
+    let limit = (kept.len() + MARKER.len()) as u64;
+    let truncated = trace(&[], limit);
+    assert_eq!(truncated, kept.clone() + MARKER);
+    assert_eq!(truncated.len() as u64, limit);
+
+    let truncated = trace(&[], limit - 1);
+    assert_eq!(truncated, lines[..2].concat() + MARKER);
+
+    assert_eq!(trace(&[], MARKER.len() as u64), MARKER);
+    assert_eq!(trace(&[], MARKER.len() as u64 - 1), "");
+}